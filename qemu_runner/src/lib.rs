@@ -12,6 +12,8 @@ use bootloader::DiskImageBuilder;
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum QemuMode {
     Run,
+    /// Runs without a display, the console is only reachable over serial.
+    Headless,
     Test,
 }
 
@@ -19,6 +21,7 @@ pub fn run_qemu_with_kernel<P, R>(
     kernel_path: P,
     ramdisk_path: Option<R>,
     mode: QemuMode,
+    cpus: u32,
 ) -> Result<ExitStatus, Box<dyn Error>>
 where
     P: AsRef<Path>,
@@ -28,10 +31,14 @@ where
         kernel_path.as_ref(),
         ramdisk_path.as_ref().map(|p| p.as_ref()),
     )?;
-    run_qemu_with_image(&image_path, mode)
+    run_qemu_with_image(&image_path, mode, cpus)
 }
 
-fn run_qemu_with_image(image_path: &Path, mode: QemuMode) -> Result<ExitStatus, Box<dyn Error>> {
+fn run_qemu_with_image(
+    image_path: &Path,
+    mode: QemuMode,
+    cpus: u32,
+) -> Result<ExitStatus, Box<dyn Error>> {
     let mut cmd = Command::new("qemu-system-x86_64");
     cmd.args([
         "-drive",
@@ -40,8 +47,10 @@ fn run_qemu_with_image(image_path: &Path, mode: QemuMode) -> Result<ExitStatus,
         "isa-debug-exit,iobase=0xf4,iosize=0x04",
         "-serial",
         "stdio",
+        "-smp",
+        &cpus.to_string(),
     ]);
-    if mode == QemuMode::Test {
+    if mode != QemuMode::Run {
         cmd.arg("-display").arg("none");
     }
 
@@ -49,7 +58,7 @@ fn run_qemu_with_image(image_path: &Path, mode: QemuMode) -> Result<ExitStatus,
 
     match mode {
         QemuMode::Test => wait_with_timeout(&mut child, Duration::from_secs(300)),
-        QemuMode::Run => Ok(child.wait()?),
+        QemuMode::Run | QemuMode::Headless => Ok(child.wait()?),
     }
 }
 
@@ -14,6 +14,14 @@ struct Args {
     #[arg(long)]
     ramdisk: Option<PathBuf>,
 
+    /// Number of CPUs of the virtual machine.
+    #[arg(long, default_value_t = 1)]
+    smp: u32,
+
+    /// Run without a display and use the serial console instead.
+    #[arg(long)]
+    headless: bool,
+
     #[arg(value_name = "KERNEL")]
     kernel: PathBuf,
 }
@@ -27,11 +35,13 @@ fn main() {
 
     let mode = if is_test {
         QemuMode::Test
+    } else if args.headless {
+        QemuMode::Headless
     } else {
         QemuMode::Run
     };
 
-    let status = match run_qemu_with_kernel(kernel_path, ramdisk_path, mode) {
+    let status = match run_qemu_with_kernel(kernel_path, ramdisk_path, mode, args.smp) {
         Ok(status) => status,
         Err(err) => {
             eprintln!("failed to run qemu: {err}");
@@ -14,7 +14,11 @@ pub struct DummyAllocator;
 static ALLOCATOR: LockedHeap = LockedHeap::empty();
 
 pub const HEAP_START: usize = 0x_6969_0420_0000; // random not used address
-pub const HEAP_SIZE: usize = 500 * 1024; // 500 KiB
+/// Most of the heap goes to the text of the six consoles: at 1280x800 a
+/// screen of character cells takes about 130 KiB, plus one more for what is
+/// drawn, and up to about 80 KiB of scrollback each. That is about 1.4 MiB
+/// next to the 500 KiB the kernel needed before.
+pub const HEAP_SIZE: usize = 2 * 1024 * 1024; // 2 MiB
 
 pub fn init_heap(
     mapper: &mut impl Mapper<Size4KiB>,
@@ -0,0 +1,52 @@
+use alloc::{collections::BTreeMap, string::String};
+use core::str::FromStr;
+
+use conquer_once::spin::OnceCell;
+
+use crate::filesystem::{self, with_filesystem};
+
+pub const CONFIG_PATH: &str = "/etc/config.txt";
+
+static CONFIG: OnceCell<BTreeMap<String, String>> = OnceCell::uninit();
+
+/// Loads the kernel configuration from [`CONFIG_PATH`].
+///
+/// The file consists of `KEY=VALUE` lines, empty lines and lines starting with
+/// `#` are ignored. A missing file results in an empty configuration.
+pub fn load() -> filesystem::Result<()> {
+    let content = match with_filesystem(|fs| fs.read_to_string(CONFIG_PATH)) {
+        Some(Ok(content)) => content,
+        Some(Err(filesystem::Error::NotFound)) | None => String::new(),
+        Some(Err(e)) => return Err(e),
+    };
+    CONFIG.init_once(|| parse(&content));
+    Ok(())
+}
+
+/// Returns the raw value of `key`, if it is configured.
+pub fn get(key: &str) -> Option<&'static str> {
+    CONFIG.get()?.get(key).map(String::as_str)
+}
+
+/// Returns the value of `key` parsed as `T`, if it is configured and valid.
+pub fn get_parsed<T: FromStr>(key: &str) -> Option<T> {
+    get(key)?.parse().ok()
+}
+
+fn parse(content: &str) -> BTreeMap<String, String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (String::from(key.trim()), String::from(value.trim())))
+        .collect()
+}
+
+#[test_case]
+fn test_parse_config() {
+    let config = parse("# comment\nA=1\n\n  B = some value \ninvalid\n");
+    assert_eq!(config.len(), 2);
+    assert_eq!(config.get("A").map(String::as_str), Some("1"));
+    assert_eq!(config.get("B").map(String::as_str), Some("some value"));
+}
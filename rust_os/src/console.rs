@@ -0,0 +1,106 @@
+use alloc::boxed::Box;
+use core::{
+    future::{Future, poll_fn},
+    pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll},
+};
+
+use futures_util::task::AtomicWaker;
+
+use crate::framebuffer;
+use crate::smp::percpu;
+
+/// Number of virtual consoles, shown with Alt+F1 to Alt+F6.
+pub const CONSOLES: usize = 6;
+/// The console log records are written to. Output of code that does not
+/// run on a console goes here as well.
+pub const LOG_CONSOLE: usize = 0;
+/// The console shown after boot. Its shell is also the one on the serial
+/// port.
+pub const SHELL_CONSOLE: usize = 1;
+
+const NO_REQUEST: usize = usize::MAX;
+
+/// The console on the screen, which gets the keyboard input.
+static ACTIVE: AtomicUsize = AtomicUsize::new(LOG_CONSOLE);
+/// The console to switch to, set by the keyboard interrupt handler.
+static REQUESTED: AtomicUsize = AtomicUsize::new(NO_REQUEST);
+static REQUEST_WAKER: AtomicWaker = AtomicWaker::new();
+
+/// The console on the screen.
+pub fn active() -> usize {
+    ACTIVE.load(Ordering::SeqCst)
+}
+
+/// The console the running code writes to.
+pub fn current() -> usize {
+    percpu::current().console.load(Ordering::Relaxed)
+}
+
+/// Calls `f` with its output going to `console`.
+pub fn with_console<R>(console: usize, f: impl FnOnce() -> R) -> R {
+    let cpu = percpu::current();
+    let previous = cpu.console.swap(console, Ordering::Relaxed);
+    let result = f();
+    cpu.console.store(previous, Ordering::Relaxed);
+    result
+}
+
+/// Runs `future` on `console`. Its output goes there and it takes the
+/// keyboard focus of that console.
+pub fn on_console<F: Future>(console: usize, future: F) -> OnConsole<F> {
+    OnConsole {
+        console,
+        future: Box::pin(future),
+    }
+}
+
+/// Future returned by [`on_console`].
+pub struct OnConsole<F> {
+    console: usize,
+    future: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for OnConsole<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<F::Output> {
+        let console = self.console;
+        with_console(console, || self.future.as_mut().poll(cx))
+    }
+}
+
+/// Asks for `console` to be shown. Called by the keyboard interrupt
+/// handler, the switch happens in [`run`].
+pub fn request_switch(console: usize) {
+    if console < CONSOLES {
+        REQUESTED.store(console, Ordering::SeqCst);
+        REQUEST_WAKER.wake();
+    }
+}
+
+/// Takes the console [`request_switch`] asked for, if any.
+pub fn take_request() -> Option<usize> {
+    match REQUESTED.swap(NO_REQUEST, Ordering::SeqCst) {
+        NO_REQUEST => None,
+        console => Some(console),
+    }
+}
+
+/// Shows the shell console and then switches consoles when asked to.
+pub async fn run() {
+    let mut console = SHELL_CONSOLE;
+    loop {
+        framebuffer::switch_console(console);
+        ACTIVE.store(console, Ordering::SeqCst);
+        console = poll_fn(|cx| {
+            REQUEST_WAKER.register(cx.waker());
+            match take_request() {
+                Some(console) => Poll::Ready(console),
+                None => Poll::Pending,
+            }
+        })
+        .await;
+    }
+}
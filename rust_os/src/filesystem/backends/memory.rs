@@ -0,0 +1,67 @@
+use alloc::{collections::btree_map::BTreeMap, string::String, vec::Vec};
+
+use crate::filesystem::{Error, FileMetadata, FileType, Result, path::CanonPathString};
+
+use super::is_immediate_child;
+
+/// Files kept in memory, lost on reboot.
+pub struct MemoryBackend {
+    files: BTreeMap<CanonPathString, Vec<u8>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        MemoryBackend {
+            files: BTreeMap::new(),
+        }
+    }
+
+    pub fn read_into(
+        &mut self,
+        path: &CanonPathString,
+        position: usize,
+        buffer: &mut [u8],
+    ) -> Result<usize> {
+        let content = self.files.get(path).ok_or(Error::NotFound)?;
+        let rest = content.get(position..).unwrap_or_default();
+        let bytes_read = rest.len().min(buffer.len());
+        buffer[..bytes_read].copy_from_slice(&rest[..bytes_read]);
+        Ok(bytes_read)
+    }
+
+    pub fn file_metadata(&self, path: &CanonPathString) -> Result<FileMetadata> {
+        let (path, content) = self.files.get_key_value(path).ok_or(Error::NotFound)?;
+        Ok(metadata(path, content))
+    }
+
+    pub fn read_dir(&self, path: &CanonPathString) -> Result<Vec<FileMetadata>> {
+        let entries = self
+            .files
+            .iter()
+            .filter(|e| is_immediate_child(path.as_str(), e.0.as_str()))
+            .map(|(path, content)| metadata(path, content))
+            .collect();
+        Ok(entries)
+    }
+
+    /// Only files are kept, no links.
+    pub fn read_link(&self, path: &CanonPathString) -> Result<String> {
+        match self.files.contains_key(path) {
+            true => Err(Error::InvalidInput),
+            false => Err(Error::NotFound),
+        }
+    }
+
+    pub fn write(&mut self, path: &CanonPathString, content: &[u8]) -> Result<()> {
+        self.files.insert(path.clone(), content.to_vec());
+        Ok(())
+    }
+}
+
+fn metadata(path: &CanonPathString, content: &[u8]) -> FileMetadata {
+    FileMetadata {
+        path: String::from(path.as_str()),
+        size: content.len(),
+        file_type: FileType::File,
+    }
+}
@@ -1,11 +1,17 @@
+mod memory;
 mod tar;
 
-use alloc::{borrow::Cow, vec::Vec};
+use alloc::{borrow::Cow, string::String, vec::Vec};
 
-use crate::filesystem::{FileMetadata, Result, backends::tar::TarBackend, path::CanonPathString};
+use crate::filesystem::{
+    FileMetadata, Result,
+    backends::{memory::MemoryBackend, tar::TarBackend},
+    path::CanonPathString,
+};
 
 pub enum FsBackendImpl {
     Tar(tar::TarBackend),
+    Memory(memory::MemoryBackend),
 }
 
 impl FsBackendImpl {
@@ -14,6 +20,10 @@ impl FsBackendImpl {
         Ok(FsBackendImpl::Tar(tar_backend))
     }
 
+    pub fn memory() -> Self {
+        FsBackendImpl::Memory(MemoryBackend::new())
+    }
+
     pub fn read_into(
         &mut self,
         path: &CanonPathString,
@@ -22,18 +32,51 @@ impl FsBackendImpl {
     ) -> Result<usize> {
         match self {
             FsBackendImpl::Tar(b) => b.read_into(path, position, buffer),
+            FsBackendImpl::Memory(b) => b.read_into(path, position, buffer),
         }
     }
 
     pub fn file_metadata(&self, path: &CanonPathString) -> Result<FileMetadata> {
         match self {
             FsBackendImpl::Tar(b) => b.file_metadata(path),
+            FsBackendImpl::Memory(b) => b.file_metadata(path),
         }
     }
 
     pub fn read_dir(&self, path: &CanonPathString) -> Result<Vec<FileMetadata>> {
         match self {
             FsBackendImpl::Tar(b) => b.read_dir(path),
+            FsBackendImpl::Memory(b) => b.read_dir(path),
+        }
+    }
+
+    /// The target of the symbolic link at `path`.
+    pub fn read_link(&self, path: &CanonPathString) -> Result<String> {
+        match self {
+            FsBackendImpl::Tar(b) => b.read_link(path),
+            FsBackendImpl::Memory(b) => b.read_link(path),
         }
     }
+
+    pub fn write(&mut self, path: &CanonPathString, content: &[u8]) -> Result<()> {
+        match self {
+            FsBackendImpl::Tar(b) => b.write(path, content),
+            FsBackendImpl::Memory(b) => b.write(path, content),
+        }
+    }
+}
+
+/// Whether `path` is directly inside `dir`, both canonical.
+fn is_immediate_child(dir: &str, path: &str) -> bool {
+    let path = path.trim_end_matches('/');
+    if dir.is_empty() {
+        return !path.is_empty() && !path.contains('/');
+    }
+
+    if let Some(rest) = path.strip_prefix(dir).and_then(|r| r.strip_prefix('/')) {
+        let rest = rest.trim_end_matches('/');
+        !rest.is_empty() && !rest.contains('/')
+    } else {
+        false
+    }
 }
@@ -4,24 +4,46 @@ use tarfs::{Entity, TarFS, Type};
 
 use crate::filesystem::{Error, FileMetadata, FileType, Result, path::CanonPathString};
 
+use super::is_immediate_child;
+
+/// Offset and length of the link target in a tar header.
+const LINK_TARGET: core::ops::Range<usize> = 157..257;
+
 pub struct TarBackend {
     tarfs: TarFS,
     entries: BTreeMap<CanonPathString, Entity>,
+    /// Targets of the symbolic links, which `Entity` does not keep.
+    links: BTreeMap<CanonPathString, String>,
 }
 
 impl TarBackend {
     pub fn new(buffer: Cow<'static, [u8]>) -> Result<Self> {
+        // the ramdisk is borrowed, so the copy only clones the reference
+        let headers = buffer.clone();
         let mut tarfs = TarFS::from_device(TarFsDevice {
             cursor: Cursor::new(buffer),
         })
         .ok_or(Error::MountFailed)?;
         let mut entries: BTreeMap<CanonPathString, Entity> = BTreeMap::new();
+        let mut links: BTreeMap<CanonPathString, String> = BTreeMap::new();
         for entity in tarfs.list() {
             let entity = entity?;
-            let canonicalized_name: CanonPathString = entity.name.as_str().try_into()?;
+            let canonicalized_name = CanonPathString::from(entity.name.as_str());
+            if entity._type == Type::SymbLink {
+                let header = headers.get(entity.position..).unwrap_or_default();
+                let target = header.get(LINK_TARGET).ok_or(Error::InvalidData)?;
+                let length = target.iter().position(|&b| b == 0).unwrap_or(target.len());
+                let target =
+                    core::str::from_utf8(&target[..length]).map_err(|_| Error::NotUtf8Encoded)?;
+                links.insert(canonicalized_name.clone(), String::from(target));
+            }
             entries.insert(canonicalized_name, entity);
         }
-        Ok(TarBackend { tarfs, entries })
+        Ok(TarBackend {
+            tarfs,
+            entries,
+            links,
+        })
     }
 
     pub fn read_into(
@@ -31,9 +53,11 @@ impl TarBackend {
         buffer: &mut [u8],
     ) -> Result<usize> {
         let file_entity = self.entries.get(path).ok_or(Error::NotFound)?;
-        let bytes_read = self
-            .tarfs
-            .read_file_by_entity(&file_entity, position, buffer)?;
+        // the archive goes on after the file, stop at its end
+        let length = file_entity.size.saturating_sub(position).min(buffer.len());
+        let bytes_read =
+            self.tarfs
+                .read_file_by_entity(&file_entity, position, &mut buffer[..length])?;
         Ok(bytes_read)
     }
 
@@ -53,19 +77,18 @@ impl TarBackend {
 
         Ok(entries)
     }
-}
 
-fn is_immediate_child(dir: &str, path: &str) -> bool {
-    let path = path.trim_end_matches('/');
-    if dir.is_empty() {
-        return !path.is_empty() && !path.contains('/');
+    pub fn read_link(&self, path: &CanonPathString) -> Result<String> {
+        match self.links.get(path) {
+            Some(target) => Ok(target.clone()),
+            None if self.entries.contains_key(path) => Err(Error::InvalidInput),
+            None => Err(Error::NotFound),
+        }
     }
 
-    if let Some(rest) = path.strip_prefix(dir).and_then(|r| r.strip_prefix('/')) {
-        let rest = rest.trim_end_matches('/');
-        !rest.is_empty() && !rest.contains('/')
-    } else {
-        false
+    /// The ramdisk is read-only.
+    pub fn write(&mut self, _path: &CanonPathString, _content: &[u8]) -> Result<()> {
+        Err(Error::PermissionDenied)
     }
 }
 
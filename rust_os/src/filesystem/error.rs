@@ -19,7 +19,7 @@ pub enum Error {
     WouldBlock,
     InvalidInput,
     InvalidData,
-    InvalidPathTraversal,
+    FilesystemLoop,
     TimedOut,
     WriteZero,
     Interrupted,
@@ -47,7 +47,7 @@ impl Error {
             Error::WouldBlock => "operation would block",
             Error::InvalidInput => "invalid input parameter",
             Error::InvalidData => "invalid data",
-            Error::InvalidPathTraversal => "invalid path traversal",
+            Error::FilesystemLoop => "too many levels of symbolic links",
             Error::TimedOut => "timed out",
             Error::WriteZero => "write zero",
             Error::Interrupted => "operation interrupted",
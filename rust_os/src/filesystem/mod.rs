@@ -11,6 +11,8 @@ use alloc::vec;
 
 pub struct FileSystem {
     backend: FsBackendImpl,
+    /// Writable layer on top of `backend`, its files hide those below.
+    overlay: Option<FsBackendImpl>,
 }
 
 unsafe impl Send for FileSystem {}
@@ -39,12 +41,25 @@ impl FileMetadata {
 impl FileSystem {
     pub fn from_tar(buffer: Cow<'static, [u8]>) -> Result<FileSystem> {
         let backend = FsBackendImpl::from_tar(buffer)?;
-        Ok(FileSystem { backend })
+        Ok(FileSystem {
+            backend,
+            overlay: None,
+        })
+    }
+
+    /// Makes the filesystem writable by keeping written files in memory.
+    pub fn with_memory_overlay(mut self) -> Self {
+        self.overlay = Some(FsBackendImpl::memory());
+        self
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.overlay.is_some()
     }
 
     pub fn read(&mut self, path: &str) -> Result<Vec<u8>> {
-        let canonicalized_path: CanonPathString = path.try_into()?;
-        let metadata = self.backend.file_metadata(&canonicalized_path)?;
+        let canonicalized_path = self.resolve(path)?;
+        let metadata = self.file_metadata(&canonicalized_path)?;
         let mut buffer = vec![0u8; metadata.size];
         let bytes_read = self.read_into_buffer(&canonicalized_path, 0, &mut buffer)?;
         buffer.truncate(bytes_read);
@@ -58,25 +73,119 @@ impl FileSystem {
     }
 
     pub fn read_into(&mut self, path: &str, position: usize, buffer: &mut [u8]) -> Result<usize> {
-        let canonicalized_path: CanonPathString = path.try_into()?;
+        let canonicalized_path = self.resolve(path)?;
         self.read_into_buffer(&canonicalized_path, position, buffer)
     }
 
     pub fn read_dir(&self, path: &str) -> Result<Vec<FileMetadata>> {
-        let canonicalized_path: CanonPathString = path.try_into()?;
-        let entries = self.backend.read_dir(&canonicalized_path)?;
+        let canonicalized_path = self.resolve(path)?;
+        let mut entries = self.backend.read_dir(&canonicalized_path)?;
+        if let Some(overlay) = &self.overlay {
+            for entry in overlay.read_dir(&canonicalized_path)? {
+                entries.retain(|e| e.name() != entry.name());
+                entries.push(entry);
+            }
+        }
         Ok(entries)
     }
 
+    pub fn metadata(&self, path: &str) -> Result<FileMetadata> {
+        let canonicalized_path = self.resolve(path)?;
+        self.file_metadata(&canonicalized_path)
+    }
+
+    /// Metadata of the entry at `path` itself, not of what a symbolic link
+    /// there points to.
+    pub fn symlink_metadata(&self, path: &str) -> Result<FileMetadata> {
+        let canonicalized_path = self.resolve_parent(path)?;
+        self.file_metadata(&canonicalized_path)
+    }
+
+    /// The target of the symbolic link at `path`, as stored in the link.
+    pub fn read_link(&self, path: &str) -> Result<String> {
+        let canonicalized_path = self.resolve_parent(path)?;
+        match self.file_metadata(&canonicalized_path)?.file_type {
+            FileType::SymLink => self.backend.read_link(&canonicalized_path),
+            _ => Err(Error::InvalidInput),
+        }
+    }
+
+    /// Replaces the content of the file at `path`, creating it if needed. The
+    /// directory it is in has to exist.
+    pub fn write(&mut self, path: &str, content: &[u8]) -> Result<()> {
+        let canonicalized_path = self.resolve(path)?;
+        let Some(overlay) = self.overlay.as_mut() else {
+            return self.backend.write(&canonicalized_path, content);
+        };
+        // the root always exists, even if the ramdisk has no entry for it
+        let parent = canonicalized_path
+            .parent()
+            .filter(|p| !p.as_str().is_empty());
+        if let Some(parent) = parent {
+            let parent = overlay
+                .file_metadata(&parent)
+                .or_else(|_| self.backend.file_metadata(&parent))?;
+            if parent.file_type != FileType::Dir {
+                return Err(Error::NotFound);
+            }
+        }
+        match self.backend.file_metadata(&canonicalized_path) {
+            Ok(metadata) if metadata.file_type == FileType::Dir => Err(Error::UnexpectedFileType),
+            _ => overlay.write(&canonicalized_path, content),
+        }
+    }
+
+    /// Adds `content` to the end of the file at `path`, creating it if needed.
+    pub fn append(&mut self, path: &str, content: &[u8]) -> Result<()> {
+        let mut existing = match self.read(path) {
+            Ok(existing) => existing,
+            Err(Error::NotFound) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        existing.extend_from_slice(content);
+        self.write(path, &existing)
+    }
+
+    /// Normalizes `path` and follows the symbolic links in it.
+    fn resolve(&self, path: &str) -> Result<CanonPathString> {
+        path::resolve(path, |path| match self.file_metadata(path) {
+            Ok(metadata) if metadata.file_type == FileType::SymLink => {
+                self.backend.read_link(path).ok()
+            }
+            _ => None,
+        })
+    }
+
+    /// Like `resolve`, but a link in the last component is not followed.
+    fn resolve_parent(&self, path: &str) -> Result<CanonPathString> {
+        let path = path.trim_end_matches('/');
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        if let "" | "." | ".." = name {
+            return self.resolve(path);
+        }
+        Ok(self.resolve(parent)?.join(name))
+    }
+
+    fn file_metadata(&self, canonicalized_path: &CanonPathString) -> Result<FileMetadata> {
+        match &self.overlay {
+            Some(overlay) => overlay
+                .file_metadata(canonicalized_path)
+                .or_else(|_| self.backend.file_metadata(canonicalized_path)),
+            None => self.backend.file_metadata(canonicalized_path),
+        }
+    }
+
     fn read_into_buffer(
         &mut self,
         canonicalized_path: &CanonPathString,
         position: usize,
         buffer: &mut [u8],
     ) -> Result<usize> {
-        let bytes_read = self
-            .backend
-            .read_into(&canonicalized_path, position, buffer)?;
+        let backend = match self.overlay.as_mut() {
+            Some(overlay) if overlay.file_metadata(canonicalized_path).is_ok() => overlay,
+            _ => &mut self.backend,
+        };
+        let bytes_read = backend.read_into(canonicalized_path, position, buffer)?;
         Ok(bytes_read)
     }
 }
@@ -87,7 +196,7 @@ use spin::Mutex;
 static FILE_SYSTEM: OnceCell<Mutex<FileSystem>> = OnceCell::uninit();
 
 pub fn init_filesystem(ramdisk: &'static [u8]) -> Result<()> {
-    let fs = FileSystem::from_tar(ramdisk.into())?;
+    let fs = FileSystem::from_tar(ramdisk.into())?.with_memory_overlay();
     FILE_SYSTEM.init_once(|| Mutex::new(fs));
     Ok(())
 }
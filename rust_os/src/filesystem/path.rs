@@ -1,7 +1,11 @@
-use alloc::string::String;
+use alloc::{collections::VecDeque, format, string::String, vec::Vec};
 
 use crate::filesystem::{Error, Result};
 
+/// How many symbolic links are followed while resolving a path before it is
+/// taken for a loop.
+const MAX_SYMLINKS: usize = 40;
+
 #[repr(transparent)]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct CanonPathString(String);
@@ -10,6 +14,24 @@ impl CanonPathString {
     pub fn as_str(&self) -> &str {
         &self.0
     }
+
+    /// The directory containing the path, `None` for the root.
+    pub fn parent(&self) -> Option<CanonPathString> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let parent = self.0.rsplit_once('/').map_or("", |(parent, _)| parent);
+        Some(CanonPathString(String::from(parent)))
+    }
+
+    /// The path of the entry `name` in this directory. `name` is a single
+    /// component other than `.` or `..`.
+    pub fn join(&self, name: &str) -> CanonPathString {
+        match self.0.is_empty() {
+            true => CanonPathString(String::from(name)),
+            false => CanonPathString(format!("{}/{}", self.0, name)),
+        }
+    }
 }
 
 impl AsRef<str> for CanonPathString {
@@ -18,47 +40,102 @@ impl AsRef<str> for CanonPathString {
     }
 }
 
-impl TryFrom<&str> for CanonPathString {
-    type Error = Error;
-
-    fn try_from(value: &str) -> Result<Self> {
-        let canonicalized_str = canonicalize(value)?;
-        Ok(CanonPathString(canonicalized_str))
+impl From<&str> for CanonPathString {
+    fn from(value: &str) -> Self {
+        CanonPathString(canonicalize(value))
     }
 }
 
-fn canonicalize(input: &str) -> Result<String> {
-    if input.is_empty() || input == "." || input == "/" || input == "./" {
-        return Ok(String::new());
-    }
-
-    let mut s = input;
-    while let Some(rest) = s.strip_prefix("./") {
-        s = rest;
-    }
-
-    while let Some(rest) = s.strip_prefix('/') {
-        s = rest;
+/// Normalizes a path without looking at the filesystem. `.` and empty
+/// components are dropped and `..` removes the component before it, or
+/// nothing at the root.
+fn canonicalize(input: &str) -> String {
+    let mut components: Vec<&str> = Vec::new();
+    for comp in input.split('/') {
+        match comp {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            comp => components.push(comp),
+        }
     }
+    components.join("/")
+}
 
-    let mut out = String::with_capacity(s.len());
-    let mut first = true;
+/// Resolves `input` like [`canonicalize`], but follows the symbolic links
+/// on the way. `read_link` returns the target of a path if it is a link.
+///
+/// Relative targets are resolved from the directory of the link, so `..`
+/// after a link leaves the directory the link points to. Fails with
+/// [`Error::FilesystemLoop`] after [`MAX_SYMLINKS`] links.
+pub fn resolve(
+    input: &str,
+    mut read_link: impl FnMut(&CanonPathString) -> Option<String>,
+) -> Result<CanonPathString> {
+    let mut resolved: Vec<String> = Vec::new();
+    let mut pending: VecDeque<String> = input.split('/').map(String::from).collect();
+    let mut links_followed = 0;
 
-    for comp in s.split('/') {
-        if comp.is_empty() || comp == "." {
+    while let Some(comp) = pending.pop_front() {
+        match comp.as_str() {
+            "" | "." => continue,
+            ".." => {
+                resolved.pop();
+                continue;
+            }
+            _ => resolved.push(comp),
+        }
+        let Some(target) = read_link(&CanonPathString(resolved.join("/"))) else {
             continue;
+        };
+        links_followed += 1;
+        if links_followed > MAX_SYMLINKS {
+            return Err(Error::FilesystemLoop);
         }
-        if comp == ".." {
-            return Err(Error::InvalidPathTraversal);
+        resolved.pop();
+        if target.starts_with('/') {
+            resolved.clear();
         }
-
-        if !first {
-            out.push('/');
+        for comp in target.split('/').rev() {
+            pending.push_front(String::from(comp));
         }
-
-        first = false;
-        out.push_str(comp);
     }
+    Ok(CanonPathString(resolved.join("/")))
+}
+
+#[test_case]
+fn test_canonicalize() {
+    let canonical = canonicalize;
+    assert_eq!(canonical(""), "");
+    assert_eq!(canonical("/"), "");
+    assert_eq!(canonical("./dir//file.txt"), "dir/file.txt");
+    assert_eq!(canonical("/dir/../dir/./file.txt"), "dir/file.txt");
+    assert_eq!(canonical("dir/sub/../.."), "");
+    assert_eq!(canonical("../../etc/config.txt"), "etc/config.txt");
+}
+
+#[test_case]
+fn test_resolve_symlinks() {
+    let read_link = |path: &CanonPathString| match path.as_str() {
+        "dir/up" => Some(String::from("../other")),
+        "dir/absolute" => Some(String::from("/etc/config.txt")),
+        "dir/chain" => Some(String::from("up/file.txt")),
+        "loop" => Some(String::from("./loop")),
+        _ => None,
+    };
+    let resolved = |path: &str| resolve(path, read_link).map(|path| path.0);
 
-    Ok(out)
+    assert_eq!(
+        resolved("dir/../dir/file.txt"),
+        Ok(String::from("dir/file.txt"))
+    );
+    assert_eq!(
+        resolved("dir/up/file.txt"),
+        Ok(String::from("other/file.txt"))
+    );
+    assert_eq!(resolved("/dir/up/.."), Ok(String::new()));
+    assert_eq!(resolved("dir/absolute"), Ok(String::from("etc/config.txt")));
+    assert_eq!(resolved("dir/chain"), Ok(String::from("other/file.txt")));
+    assert_eq!(resolved("loop/file.txt"), Err(Error::FilesystemLoop));
 }
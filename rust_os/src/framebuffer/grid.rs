@@ -0,0 +1,424 @@
+use alloc::{collections::VecDeque, string::String, vec, vec::Vec};
+
+use super::Rgb;
+
+/// How many lines that scrolled off the top of the screen are kept. Lines
+/// are kept as runs of text, so a console's history takes up to about 80 KiB
+/// of the heap for typical shell output, instead of the 1.4 MiB 500 lines of
+/// cells would.
+const SCROLLBACK_LINES: usize = 500;
+const DEFAULT_FOREGROUND: Rgb = Rgb::WHITE;
+const DEFAULT_BACKGROUND: Rgb = Rgb::BLACK;
+
+/// A color set by a control sequence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Color {
+    Default,
+    /// An index into the 256 colors of [`palette`].
+    Indexed(u8),
+    Rgb(Rgb),
+}
+
+impl Color {
+    /// The color to draw with. Bold text uses the bright variants of the
+    /// first 8 colors.
+    fn resolve(self, default: Rgb, bold: bool) -> Rgb {
+        match self {
+            Color::Default => default,
+            Color::Indexed(index) if bold && index < 8 => palette(index + 8),
+            Color::Indexed(index) => palette(index),
+            Color::Rgb(rgb) => rgb,
+        }
+    }
+}
+
+/// How text is drawn, changed with `ESC [ ... m`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct Attributes {
+    pub(super) foreground: Color,
+    pub(super) background: Color,
+    pub(super) bold: bool,
+    pub(super) reverse: bool,
+}
+
+impl Attributes {
+    pub(super) const DEFAULT: Self = Attributes {
+        foreground: Color::Default,
+        background: Color::Default,
+        bold: false,
+        reverse: false,
+    };
+
+    /// The foreground and background colors to draw with.
+    pub(super) fn colors(&self) -> (Rgb, Rgb) {
+        let foreground = self.foreground.resolve(DEFAULT_FOREGROUND, self.bold);
+        let background = self.background.resolve(DEFAULT_BACKGROUND, false);
+        match self.reverse {
+            true => (background, foreground),
+            false => (foreground, background),
+        }
+    }
+}
+
+impl Default for Attributes {
+    fn default() -> Self {
+        Attributes::DEFAULT
+    }
+}
+
+/// The xterm color for an index of `ESC [ 38 ; 5 ; <index> m`: 16 basic
+/// colors, a 6x6x6 color cube and 24 shades of gray.
+fn palette(index: u8) -> Rgb {
+    const BASIC: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (205, 0, 0),
+        (0, 205, 0),
+        (205, 205, 0),
+        (0, 0, 238),
+        (205, 0, 205),
+        (0, 205, 205),
+        (229, 229, 229),
+        (127, 127, 127),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (92, 92, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+    match index {
+        0..=15 => {
+            let (r, g, b) = BASIC[index as usize];
+            Rgb { r, g, b }
+        }
+        16..=231 => {
+            let level = |value: u8| if value == 0 { 0 } else { 55 + value * 40 };
+            let index = index - 16;
+            Rgb {
+                r: level(index / 36),
+                g: level(index / 6 % 6),
+                b: level(index % 6),
+            }
+        }
+        _ => {
+            let value = 8 + (index - 232) * 10;
+            Rgb {
+                r: value,
+                g: value,
+                b: value,
+            }
+        }
+    }
+}
+
+/// A character on the screen with the attributes it was written with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct Cell {
+    pub(super) c: char,
+    pub(super) attributes: Attributes,
+}
+
+impl Cell {
+    pub(super) const BLANK: Self = Cell {
+        c: ' ',
+        attributes: Attributes::DEFAULT,
+    };
+}
+
+/// A line in the scrollback history, as runs of text with the same
+/// attributes. Trailing blanks are dropped.
+type Line = Vec<(Attributes, String)>;
+
+/// The character cells as they were last drawn on the framebuffer. Only one
+/// console is shown at a time, so they share it.
+pub(super) struct Drawn {
+    cells: Vec<Vec<Cell>>,
+    cursor: Option<(usize, usize)>,
+}
+
+impl Drawn {
+    pub(super) fn new(columns: usize, rows: usize) -> Self {
+        Drawn {
+            cells: vec![vec![Cell::BLANK; columns]; rows],
+            cursor: None,
+        }
+    }
+
+    /// Takes the framebuffer for blank, after it was cleared without a grid.
+    pub(super) fn forget(&mut self) {
+        self.cells
+            .iter_mut()
+            .for_each(|line| line.fill(Cell::BLANK));
+        self.cursor = None;
+    }
+
+    /// Follows the framebuffer being shifted up by a line.
+    pub(super) fn scroll(&mut self) {
+        let mut line = self.cells.remove(0);
+        line.fill(Cell::BLANK);
+        self.cells.push(line);
+        self.cursor = self
+            .cursor
+            .and_then(|(column, row)| Some((column, row.checked_sub(1)?)));
+    }
+}
+
+/// The character cells on the screen and the lines that scrolled off it.
+///
+/// Writing only changes the cells. Redrawing compares them with what was
+/// last drawn and only touches the cells that look different now.
+pub(super) struct TextGrid {
+    columns: usize,
+    screen: Vec<Vec<Cell>>,
+    /// Rows that may look different from what was drawn.
+    dirty: Vec<bool>,
+    /// Lines that scrolled off the top, the oldest first.
+    history: VecDeque<Line>,
+    /// How many lines the view is scrolled back into the history.
+    offset: usize,
+}
+
+impl TextGrid {
+    pub(super) fn new(columns: usize, rows: usize) -> Self {
+        TextGrid {
+            columns,
+            screen: vec![vec![Cell::BLANK; columns]; rows],
+            dirty: vec![false; rows],
+            history: VecDeque::new(),
+            offset: 0,
+        }
+    }
+
+    pub(super) fn set(&mut self, column: usize, row: usize, cell: Cell) {
+        if let Some(current) = self
+            .screen
+            .get_mut(row)
+            .and_then(|line| line.get_mut(column))
+            && *current != cell
+        {
+            *current = cell;
+            self.dirty[row] = true;
+        }
+    }
+
+    pub(super) fn clear(&mut self) {
+        for (line, dirty) in self.screen.iter_mut().zip(&mut self.dirty) {
+            if line.iter().any(|cell| *cell != Cell::BLANK) {
+                line.fill(Cell::BLANK);
+                *dirty = true;
+            }
+        }
+    }
+
+    /// Compares every row with what was drawn on the next redraw.
+    pub(super) fn invalidate(&mut self) {
+        self.dirty.fill(true);
+    }
+
+    /// Moves the top line of the screen into the history.
+    pub(super) fn scroll(&mut self) {
+        let mut line = self.screen.remove(0);
+        self.history.push_back(to_runs(&line));
+        if self.history.len() > SCROLLBACK_LINES {
+            self.history.pop_front();
+        }
+        line.fill(Cell::BLANK);
+        self.screen.push(line);
+        self.dirty.remove(0);
+        self.dirty.push(false);
+    }
+
+    pub(super) fn clear_history(&mut self) {
+        self.set_offset(0);
+        self.history.clear();
+    }
+
+    pub(super) fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Scrolls the view back into the history, 0 shows the screen.
+    pub(super) fn set_offset(&mut self, offset: usize) {
+        let offset = offset.min(self.history.len());
+        if offset != self.offset {
+            self.offset = offset;
+            self.dirty.fill(true);
+        }
+    }
+
+    /// Calls `draw` for every cell that looks different from what was drawn,
+    /// with whether the cursor is on it. `cursor` is where the cursor is
+    /// shown, `None` while it is hidden.
+    pub(super) fn redraw(
+        &mut self,
+        drawn: &mut Drawn,
+        cursor: Option<(usize, usize)>,
+        mut draw: impl FnMut(usize, usize, Cell, bool),
+    ) {
+        let moved = cursor != drawn.cursor;
+        if moved {
+            for (_, row) in [cursor, drawn.cursor].into_iter().flatten() {
+                if let Some(dirty) = self.dirty.get_mut(row) {
+                    *dirty = true;
+                }
+            }
+        }
+        for row in 0..self.screen.len() {
+            if !core::mem::take(&mut self.dirty[row]) {
+                continue;
+            }
+            let index = self.history.len() + row - self.offset;
+            let history_line;
+            let line = match self.history.get(index) {
+                Some(runs) => {
+                    history_line = from_runs(runs, self.columns);
+                    &history_line
+                }
+                None => &self.screen[index - self.history.len()],
+            };
+            for (column, &cell) in line.iter().enumerate() {
+                let here = Some((column, row));
+                let cursor_changed = moved && (cursor == here || drawn.cursor == here);
+                if drawn.cells[row][column] != cell || cursor_changed {
+                    drawn.cells[row][column] = cell;
+                    draw(column, row, cell, cursor == here);
+                }
+            }
+        }
+        drawn.cursor = cursor;
+    }
+}
+
+fn to_runs(cells: &[Cell]) -> Line {
+    let end = cells
+        .iter()
+        .rposition(|cell| *cell != Cell::BLANK)
+        .map_or(0, |last| last + 1);
+    let mut runs: Line = Vec::new();
+    for cell in &cells[..end] {
+        match runs.last_mut() {
+            Some((attributes, text)) if *attributes == cell.attributes => text.push(cell.c),
+            _ => runs.push((cell.attributes, String::from(cell.c))),
+        }
+    }
+    // the lines are kept for long, without the room that pushing left
+    runs.iter_mut().for_each(|(_, text)| text.shrink_to_fit());
+    runs.shrink_to_fit();
+    runs
+}
+
+fn from_runs(runs: &Line, columns: usize) -> Vec<Cell> {
+    let mut cells: Vec<Cell> = runs
+        .iter()
+        .flat_map(|(attributes, text)| {
+            text.chars().map(|c| Cell {
+                c,
+                attributes: *attributes,
+            })
+        })
+        .collect();
+    cells.resize(columns, Cell::BLANK);
+    cells
+}
+
+#[test_case]
+fn test_palette() {
+    assert_eq!(palette(1), Rgb { r: 205, g: 0, b: 0 });
+    assert_eq!(Color::Indexed(1).resolve(Rgb::WHITE, true), Rgb::RED);
+    assert_eq!(palette(16), Rgb::BLACK);
+    assert_eq!(palette(196), Rgb::RED);
+    assert_eq!(palette(231), Rgb::WHITE);
+    assert_eq!(
+        palette(244),
+        Rgb {
+            r: 128,
+            g: 128,
+            b: 128
+        }
+    );
+}
+
+#[test_case]
+fn test_text_grid() {
+    let red = Attributes {
+        foreground: Color::Indexed(1),
+        ..Attributes::DEFAULT
+    };
+    let cell = |c: char| Cell {
+        c,
+        attributes: Attributes::DEFAULT,
+    };
+    fn redrawn(
+        grid: &mut TextGrid,
+        drawn: &mut Drawn,
+        cursor: Option<(usize, usize)>,
+    ) -> Vec<(usize, usize, char)> {
+        let mut cells = Vec::new();
+        grid.redraw(drawn, cursor, |column, row, cell, _| {
+            cells.push((column, row, cell.c))
+        });
+        cells
+    }
+
+    let mut grid = TextGrid::new(3, 2);
+    let drawn = &mut Drawn::new(3, 2);
+    grid.set(0, 0, cell('a'));
+    grid.set(
+        1,
+        0,
+        Cell {
+            c: 'b',
+            ..Cell::BLANK
+        },
+    );
+    grid.set(
+        1,
+        0,
+        Cell {
+            c: 'b',
+            attributes: red,
+        },
+    );
+    assert_eq!(redrawn(&mut grid, drawn, None), [(0, 0, 'a'), (1, 0, 'b')]);
+    assert_eq!(redrawn(&mut grid, drawn, None), []);
+
+    // clearing and writing the same text again draws nothing
+    grid.clear();
+    grid.set(0, 0, cell('a'));
+    grid.set(
+        1,
+        0,
+        Cell {
+            c: 'b',
+            attributes: red,
+        },
+    );
+    assert_eq!(redrawn(&mut grid, drawn, None), []);
+
+    // the cursor cell is drawn again when the cursor comes and goes
+    assert_eq!(redrawn(&mut grid, drawn, Some((2, 1))), [(2, 1, ' ')]);
+    assert_eq!(redrawn(&mut grid, drawn, None), [(2, 1, ' ')]);
+
+    grid.scroll();
+    drawn.scroll();
+    assert_eq!(
+        grid.history,
+        [alloc::vec![
+            (Attributes::DEFAULT, String::from("a")),
+            (red, String::from("b"))
+        ]]
+    );
+    assert_eq!(redrawn(&mut grid, drawn, None), []);
+    grid.set_offset(5);
+    assert_eq!(grid.offset(), 1);
+    assert_eq!(redrawn(&mut grid, drawn, None), [(0, 0, 'a'), (1, 0, 'b')]);
+    grid.set_offset(0);
+    assert_eq!(redrawn(&mut grid, drawn, None), [(0, 0, ' '), (1, 0, ' ')]);
+
+    // another console shown in its place only draws the cells that differ
+    let mut other = TextGrid::new(3, 2);
+    other.set(1, 1, cell('c'));
+    other.invalidate();
+    assert_eq!(redrawn(&mut other, drawn, None), [(1, 1, 'c')]);
+}
@@ -0,0 +1,1267 @@
+use core::{
+    fmt::{self, Write},
+    ptr,
+    time::Duration,
+};
+
+use bootloader_api::info::{FrameBuffer, FrameBufferInfo, PixelFormat};
+use conquer_once::spin::OnceCell;
+use noto_sans_mono_bitmap::{
+    FontWeight, RasterHeight, RasterizedChar, get_raster, get_raster_width,
+};
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use alloc::{vec, vec::Vec};
+
+use crate::console::{self, CONSOLES};
+use crate::{config, time};
+use grid::{Attributes, Cell, Color, Drawn, TextGrid};
+
+mod grid;
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+const LINE_SPACING: usize = 2;
+const LETTER_SPACING: usize = 0;
+const BORDER_PADDING: usize = 1;
+const CHAR_RASTER_HEIGHT: RasterHeight = RasterHeight::Size16;
+const CHAR_RASTER_WIDTH: usize = get_raster_width(FontWeight::Regular, CHAR_RASTER_HEIGHT);
+const FALLBACK_CHAR: char = '?';
+const FONT_WEIGHT: FontWeight = FontWeight::Regular;
+const ESCAPE: char = '\x1b';
+/// Control sequences with more parameters ignore the rest.
+const MAX_PARAMETERS: usize = 16;
+/// How long a blinking cursor stays on and off.
+const CURSOR_BLINK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Mouse pointer sprite, `#` is the outline, `o` the fill and `.` transparent.
+const POINTER_SPRITE: [&[u8; POINTER_WIDTH]; POINTER_HEIGHT] = [
+    b"#...........",
+    b"##..........",
+    b"#o#.........",
+    b"#oo#........",
+    b"#ooo#.......",
+    b"#oooo#......",
+    b"#ooooo#.....",
+    b"#oooooo#....",
+    b"#ooooooo#...",
+    b"#oooooooo#..",
+    b"#ooooooooo#.",
+    b"#oooooo#####",
+    b"#ooo#oo#....",
+    b"#oo#.#oo#...",
+    b"#o#..#oo#...",
+    b"##....#oo#..",
+    b"#.....#oo#..",
+    b".......##...",
+];
+const POINTER_WIDTH: usize = 12;
+const POINTER_HEIGHT: usize = 18;
+
+// ============================================================================
+// Global State
+// ============================================================================
+
+static WRITER: OnceCell<Mutex<FrameBufferWriter>> = OnceCell::uninit();
+static CELL_SIZE: OnceCell<usize> = OnceCell::uninit();
+
+// ============================================================================
+// RGB Color Type
+// ============================================================================
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const BLACK: Self = Self { r: 0, g: 0, b: 0 };
+    pub const WHITE: Self = Self {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+    pub const RED: Self = Self { r: 255, g: 0, b: 0 };
+    pub const GREEN: Self = Self { r: 0, g: 160, b: 0 };
+    pub const BRIGHT_GREEN: Self = Self { r: 0, g: 255, b: 0 };
+    pub const DARK_GRAY: Self = Self {
+        r: 32,
+        g: 32,
+        b: 32,
+    };
+}
+
+/// Reads the color after a 38 or 48 in `ESC [ ... m`, `5;<index>` or
+/// `2;<r>;<g>;<b>`.
+fn extended_color(values: &mut impl Iterator<Item = usize>) -> Option<Color> {
+    let mut component = || values.next().map(|value| value.min(255) as u8);
+    match component()? {
+        5 => Some(Color::Indexed(component()?)),
+        2 => {
+            let (r, g, b) = (component()?, component()?, component()?);
+            Some(Color::Rgb(Rgb { r, g, b }))
+        }
+        _ => None,
+    }
+}
+
+// ============================================================================
+// Public API Functions
+// ============================================================================
+
+/// Initialize the global framebuffer writer
+pub fn init_framebuffer_writer(framebuffer: &'static mut FrameBuffer) {
+    let info = framebuffer.info();
+    let buffer = framebuffer.buffer_mut();
+    WRITER.init_once(|| Mutex::new(FrameBufferWriter::new(buffer, info)));
+}
+
+pub fn clear_color(color: Rgb) {
+    with_framebuffer_writer(|writer| writer.clear_color(color));
+}
+
+pub fn reset_cursor() {
+    with_framebuffer_writer(|writer| {
+        writer.console.x_pos = BORDER_PADDING;
+        writer.console.y_pos = BORDER_PADDING;
+    });
+}
+
+/// Starts keeping the text on the screen in a grid of character cells,
+/// with the lines scrolled off it. Needs the heap, so it is called after the
+/// heap is initialized. Until then characters are drawn right away.
+pub fn init_text_grid() {
+    with_framebuffer_writer(|writer| {
+        let (columns, rows) = writer.text_dimensions();
+        writer.drawn = Some(Drawn::new(columns, rows));
+        writer.select(console::current());
+        // whatever was drawn before is not in the grid
+        writer.console.graphics = true;
+        writer.clear();
+    });
+}
+
+/// Applies the `CURSOR_STYLE` and `CURSOR_BLINK` settings of the
+/// configuration.
+pub fn load_config() {
+    let style = match config::get("CURSOR_STYLE") {
+        Some("block") => Some(CursorStyle::Block),
+        Some("underline") => Some(CursorStyle::Underline),
+        Some(other) => {
+            log::warn!("invalid CURSOR_STYLE '{}'", other);
+            None
+        }
+        None => None,
+    };
+    let blink = config::get_parsed("CURSOR_BLINK");
+    with_framebuffer_writer(|writer| {
+        let consoles = core::iter::once(&mut writer.console).chain(&mut writer.consoles);
+        for console in consoles {
+            console.cursor.style = style.unwrap_or(console.cursor.style);
+            console.cursor.blink = blink.unwrap_or(console.cursor.blink);
+        }
+    });
+}
+
+/// Draws the text that changed and blinks the cursor. Called by the timer
+/// interrupt handler, so it skips a turn if the writer is in use.
+pub fn refresh() {
+    if let Some(writer) = WRITER.get()
+        && let Some(mut writer) = writer.try_lock()
+    {
+        let active = writer.active;
+        writer.select(active);
+        writer.flush();
+    }
+}
+
+/// Shows the virtual console `console` instead of the active one. Called by
+/// [`console::run`], use [`console::request_switch`] to switch.
+pub fn switch_console(console: usize) {
+    with_framebuffer_writer(|writer| writer.switch(console));
+}
+
+/// Scrolls the view the given number of pages back into the scrollback
+/// history, or forward for negative numbers. Output returns to the bottom.
+pub fn scroll_back(pages: isize) {
+    with_framebuffer_writer(|writer| {
+        let active = writer.active;
+        writer.select(active);
+        let (_, rows) = writer.text_dimensions();
+        writer.scroll_view(pages * rows as isize);
+    });
+}
+
+/// Draws the mouse pointer with its tip at the given pixel
+pub fn move_pointer(x: usize, y: usize) {
+    with_framebuffer_writer(|writer| writer.move_pointer(x, y));
+}
+
+/// Set the logical cell size used for grid calculations
+pub fn init_cell_size(cell_size: usize) {
+    CELL_SIZE.get_or_init(|| cell_size);
+}
+
+/// Execute a function with access to the framebuffer writer, writing to the
+/// virtual console of the caller
+pub fn with_framebuffer_writer<R>(f: impl FnOnce(&mut FrameBufferWriter) -> R) -> R {
+    without_interrupts(|| {
+        let mut writer = WRITER
+            .get()
+            .expect("FrameBufferWriter has not been initialized")
+            .lock();
+        writer.select(console::current());
+        f(&mut writer)
+    })
+}
+
+/// Get the framebuffer pixel dimensions
+pub fn framebuffer_size() -> (usize, usize) {
+    with_framebuffer_writer(|writer| writer.dimensions())
+}
+
+/// Get the number of text columns and rows that fit on the screen
+pub fn text_size() -> (usize, usize) {
+    with_framebuffer_writer(|writer| writer.text_dimensions())
+}
+
+/// Get the grid size based on the configured cell size
+pub fn grid_size() -> Option<(usize, usize)> {
+    CELL_SIZE
+        .get()
+        .map(|cell| with_framebuffer_writer(|writer| writer.grid_dimensions(*cell)))
+}
+
+/// Get the framebuffer dimensions and grid dimensions for a given cell size
+pub fn framebuffer_dimensions(cell_size: usize) -> ((usize, usize), (usize, usize)) {
+    with_framebuffer_writer(|writer| (writer.dimensions(), writer.grid_dimensions(cell_size)))
+}
+
+/// Write a single pixel with the provided RGB color
+pub fn put_pixel(x: usize, y: usize, color: Rgb) {
+    with_framebuffer_writer(|writer| writer.put_pixel_rgb(x, y, color));
+}
+
+/// Draw a filled cell at grid coordinates
+pub fn draw_cell(cx: usize, cy: usize, cell_size: usize, color: Rgb) {
+    with_framebuffer_writer(|writer| writer.draw_cell(cx, cy, cell_size, color));
+}
+
+// ============================================================================
+// Macros for Printing
+// ============================================================================
+
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => ($crate::framebuffer::_print(format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! println {
+    () => ($crate::print!("\n"));
+    ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    with_framebuffer_writer(|writer| {
+        writer
+            .write_fmt(args)
+            .expect("Writing to framebuffer failed")
+    });
+    // the serial terminal belongs to the first shell, the other shells would
+    // mix into it
+    if matches!(
+        console::current(),
+        console::LOG_CONSOLE | console::SHELL_CONSOLE
+    ) {
+        crate::serial::mirror_console(args);
+    }
+}
+
+fn get_rasterized_char(c: char) -> RasterizedChar {
+    fn _get(c: char) -> Option<RasterizedChar> {
+        get_raster(c, FONT_WEIGHT, CHAR_RASTER_HEIGHT)
+    }
+
+    _get(c).unwrap_or_else(|| {
+        _get(FALLBACK_CHAR).expect("Failed to get rasterized version of backup char")
+    })
+}
+
+// ============================================================================
+// FrameBufferWriter Implementation
+// ============================================================================
+
+pub struct FrameBufferWriter {
+    framebuffer: &'static mut [u8],
+    info: FrameBufferInfo,
+    pointer: Option<Pointer>,
+    /// The virtual console written to.
+    console: Console,
+    selected: usize,
+    /// The other consoles, the slot of the selected one is left empty.
+    consoles: [Console; CONSOLES],
+    /// The console on the screen, the others are only drawn when switched to.
+    active: usize,
+    /// What the screen shows of the text grid of the active console. The
+    /// consoles get a text grid, which needs the heap, once this is set.
+    drawn: Option<Drawn>,
+}
+
+/// A virtual console, with its own text and state of the output.
+#[derive(Default)]
+struct Console {
+    x_pos: usize,
+    y_pos: usize,
+    escape: EscapeState,
+    cursor: Cursor,
+    text: Option<TextGrid>,
+    /// Set when pixels were drawn other than through the text grid.
+    graphics: bool,
+    /// The graphics drawn with [`FrameBufferWriter::clear_color`] and
+    /// [`FrameBufferWriter::draw_cell`], painted again when the console is
+    /// switched to. Single pixels are not kept.
+    painting: Option<Painting>,
+    attributes: Attributes,
+    /// Position and attributes saved by `ESC 7` or `ESC [ s`.
+    saved: Option<(usize, usize, Attributes)>,
+}
+
+/// A background color with square cells of one size on it.
+struct Painting {
+    background: Rgb,
+    cell_size: usize,
+    columns: usize,
+    cells: Vec<Option<Rgb>>,
+}
+
+impl Painting {
+    fn new(background: Rgb) -> Self {
+        Self {
+            background,
+            cell_size: 0,
+            columns: 0,
+            cells: Vec::new(),
+        }
+    }
+
+    /// Records a cell on a screen of `width` by `height` pixels. Cells of
+    /// another size replace the ones recorded so far.
+    fn set(
+        &mut self,
+        cx: usize,
+        cy: usize,
+        cell_size: usize,
+        color: Rgb,
+        (width, height): (usize, usize),
+    ) {
+        if cell_size == 0 {
+            return;
+        }
+        if cell_size != self.cell_size {
+            self.cell_size = cell_size;
+            self.columns = width.div_ceil(cell_size);
+            self.cells = vec![None; self.columns * height.div_ceil(cell_size)];
+        }
+        if cx < self.columns
+            && let Some(cell) = self.cells.get_mut(cy * self.columns + cx)
+        {
+            *cell = Some(color);
+        }
+    }
+}
+
+/// The text cursor, drawn on the cell at the current position.
+struct Cursor {
+    style: CursorStyle,
+    blink: bool,
+    /// Cleared by `ESC [ ? 25 l` for programs that draw the whole screen.
+    visible: bool,
+    /// Blinking starts over from here on output, so the cursor shows while
+    /// typing.
+    blink_start: Duration,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Cursor {
+            style: CursorStyle::Underline,
+            blink: true,
+            visible: true,
+            blink_start: Duration::ZERO,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CursorStyle {
+    Block,
+    Underline,
+}
+
+/// Progress through an escape sequence in the text output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum EscapeState {
+    #[default]
+    Ground,
+    Escape,
+    /// Inside a control sequence `ESC [ <parameters> <final byte>`.
+    ControlSequence(Parameters),
+}
+
+/// The numeric parameters of a control sequence, separated by `;`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Parameters {
+    /// `None` for parameters left empty.
+    values: [Option<u16>; MAX_PARAMETERS],
+    len: usize,
+    /// A `?`, `<`, `=` or `>` prefix, which marks private sequences.
+    private: Option<char>,
+    /// The last byte between the parameters and the final byte, like the
+    /// space in `ESC [ 2 SP q`.
+    intermediate: Option<char>,
+}
+
+impl Parameters {
+    const EMPTY: Self = Parameters {
+        values: [None; MAX_PARAMETERS],
+        len: 0,
+        private: None,
+        intermediate: None,
+    };
+
+    fn push_digit(&mut self, digit: u16) {
+        if self.len == 0 {
+            self.len = 1;
+        }
+        if let Some(value) = self.values.get_mut(self.len - 1) {
+            *value = Some(value.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+        }
+    }
+
+    /// Starts the next parameter after a `;`.
+    fn separator(&mut self) {
+        self.len = self.len.max(1) + 1;
+    }
+
+    fn get(&self, index: usize) -> Option<usize> {
+        self.values.get(index).copied().flatten().map(usize::from)
+    }
+
+    /// The parameter at `index`, treating a missing one or 0 as 1.
+    fn count(&self, index: usize) -> usize {
+        self.get(index).unwrap_or(1).max(1)
+    }
+
+    /// All parameters, empty ones are 0.
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len.min(MAX_PARAMETERS)).map(|index| self.get(index).unwrap_or(0))
+    }
+}
+
+/// The mouse pointer drawn on top of everything else, together with the
+/// pixels it covers. Drawing below the pointer only changes the saved pixels.
+struct Pointer {
+    x: usize,
+    y: usize,
+    background: [[u8; 4]; POINTER_WIDTH * POINTER_HEIGHT],
+}
+
+impl Pointer {
+    /// Index into the sprite of the opaque pointer pixel at `(x, y)`.
+    fn covered_index(&self, x: usize, y: usize) -> Option<usize> {
+        let (column, row) = (x.checked_sub(self.x)?, y.checked_sub(self.y)?);
+        if column >= POINTER_WIDTH || row >= POINTER_HEIGHT {
+            return None;
+        }
+        (POINTER_SPRITE[row][column] != b'.').then_some(row * POINTER_WIDTH + column)
+    }
+}
+
+impl FrameBufferWriter {
+    pub fn new(framebuffer: &'static mut [u8], info: FrameBufferInfo) -> Self {
+        let mut writer = Self {
+            framebuffer,
+            info,
+            pointer: None,
+            console: Console::default(),
+            selected: console::LOG_CONSOLE,
+            consoles: Default::default(),
+            active: console::LOG_CONSOLE,
+            drawn: None,
+        };
+        writer.clear();
+        writer
+    }
+
+    /// Whether the selected console is on the screen.
+    fn shown(&self) -> bool {
+        self.selected == self.active
+    }
+
+    /// Makes `console` the one written to. It gets its text grid the first
+    /// time it is written to once there is a heap.
+    fn select(&mut self, console: usize) {
+        let console = console.min(CONSOLES - 1);
+        if console != self.selected {
+            let parked = core::mem::take(&mut self.consoles[console]);
+            self.consoles[self.selected] = core::mem::replace(&mut self.console, parked);
+            self.selected = console;
+        }
+        if self.drawn.is_some() && self.console.text.is_none() {
+            let (columns, rows) = self.text_dimensions();
+            self.console.text = Some(TextGrid::new(columns, rows));
+        }
+    }
+
+    /// Shows `console` instead of the active one, drawn again from its
+    /// text grid and painting.
+    fn switch(&mut self, console: usize) {
+        if console == self.active || console >= CONSOLES {
+            return;
+        }
+        self.active = console;
+        self.select(console);
+        let pointer = self.pointer.as_ref().map(|pointer| (pointer.x, pointer.y));
+        self.hide_pointer();
+        self.framebuffer.fill(0);
+        self.forget_drawn();
+        self.repaint();
+        if let Some((x, y)) = pointer {
+            self.move_pointer(x, y);
+        }
+        self.flush();
+    }
+
+    /// Paints the recorded graphics of the selected console.
+    fn repaint(&mut self) {
+        let Some(painting) = self.console.painting.take() else {
+            return;
+        };
+        let (width, height) = self.dimensions();
+        self.fill_pixels(0, 0, width, height, painting.background);
+        let size = painting.cell_size;
+        for (index, color) in painting.cells.iter().enumerate() {
+            if let Some(color) = *color {
+                let (cx, cy) = (index % painting.columns, index / painting.columns);
+                self.fill_pixels(cx * size, cy * size, size, size, color);
+            }
+        }
+        self.console.painting = Some(painting);
+    }
+
+    /// Makes the text grid of the selected console be drawn again in full.
+    fn forget_drawn(&mut self) {
+        if let Some(drawn) = self.drawn.as_mut() {
+            drawn.forget();
+        }
+        if let Some(text) = self.console.text.as_mut() {
+            text.invalidate();
+        }
+    }
+
+    fn newline(&mut self) {
+        let line_height = CHAR_RASTER_HEIGHT.val() + LINE_SPACING;
+        if self.console.y_pos + line_height + CHAR_RASTER_HEIGHT.val() + BORDER_PADDING
+            >= self.height()
+        {
+            self.scroll();
+        } else {
+            self.console.y_pos += line_height;
+        }
+        self.carriage_return()
+    }
+
+    /// Shifts the screen up by one line and clears the bottom line.
+    fn scroll(&mut self) {
+        if let Some(text) = self.console.text.as_mut() {
+            text.scroll();
+        }
+        if !self.shown() {
+            return;
+        }
+        if let Some(drawn) = self.drawn.as_mut() {
+            drawn.scroll();
+        }
+        let line_height = CHAR_RASTER_HEIGHT.val() + LINE_SPACING;
+        let pointer = self.pointer.as_ref().map(|pointer| (pointer.x, pointer.y));
+        self.hide_pointer();
+        let line_bytes = line_height * self.info.stride * self.info.bytes_per_pixel;
+        self.framebuffer.copy_within(line_bytes.., 0);
+        let (y, width) = (self.console.y_pos, self.width());
+        let height = self.height() - y;
+        self.fill_pixels(0, y, width, height, Rgb::BLACK);
+        if let Some((x, y)) = pointer {
+            self.move_pointer(x, y);
+        }
+    }
+
+    fn backspace(&mut self) {
+        let width = CHAR_RASTER_WIDTH + LETTER_SPACING;
+        if self.console.x_pos >= BORDER_PADDING + width {
+            self.console.x_pos -= width;
+            let start_x = self.console.x_pos;
+            let start_y = self.console.y_pos;
+
+            // Overwrite the character with a space
+            // write_char will advance x_pos, so we need to reset it
+            // afterwards
+            self.write_char(' ');
+
+            // Restore position to the cleared spot
+            self.console.x_pos = start_x;
+            self.console.y_pos = start_y;
+        }
+    }
+
+    fn carriage_return(&mut self) {
+        self.console.x_pos = BORDER_PADDING
+    }
+    // ------------------------------------------------------------------------
+    // Dimension Queries
+    // ------------------------------------------------------------------------
+
+    /// Clears the screen. Text is only cleared in the grid, so text written
+    /// again right away is not drawn twice. Anything else drawn is erased.
+    pub fn clear(&mut self) {
+        self.console.x_pos = BORDER_PADDING;
+        self.console.y_pos = BORDER_PADDING;
+        match self.console.text.as_mut() {
+            Some(text) if !self.console.graphics => text.clear(),
+            _ => {
+                if self.shown() {
+                    self.clear_pixels();
+                }
+                self.console.painting = None;
+                if let Some(text) = self.console.text.as_mut() {
+                    text.clear();
+                }
+                if self.shown() {
+                    self.forget_drawn();
+                }
+            }
+        }
+        self.console.graphics = false;
+    }
+
+    fn clear_pixels(&mut self) {
+        self.framebuffer.fill(0);
+        if let Some(pointer) = self.pointer.take() {
+            self.move_pointer(pointer.x, pointer.y);
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.info.width
+    }
+
+    fn height(&self) -> usize {
+        self.info.height
+    }
+
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.info.width, self.info.height)
+    }
+
+    /// Columns and rows of characters that fit without wrapping or clearing
+    /// the screen.
+    pub fn text_dimensions(&self) -> (usize, usize) {
+        let width = CHAR_RASTER_WIDTH + LETTER_SPACING;
+        let line_height = CHAR_RASTER_HEIGHT.val() + LINE_SPACING;
+        let columns = self
+            .width()
+            .saturating_sub(BORDER_PADDING + CHAR_RASTER_WIDTH + 1)
+            / width
+            + 1;
+        let rows = self
+            .height()
+            .saturating_sub(2 * BORDER_PADDING + CHAR_RASTER_HEIGHT.val() + 1)
+            / line_height
+            + 1;
+        (columns, rows)
+    }
+
+    pub fn grid_dimensions(&self, cell_size: usize) -> (usize, usize) {
+        (self.info.width / cell_size, self.info.height / cell_size)
+    }
+
+    // ------------------------------------------------------------------------
+    // Text Rendering
+    // ------------------------------------------------------------------------
+
+    fn write_char(&mut self, c: char) {
+        match self.console.escape {
+            EscapeState::Ground => {}
+            EscapeState::Escape => {
+                self.console.escape = EscapeState::Ground;
+                match c {
+                    '[' => self.console.escape = EscapeState::ControlSequence(Parameters::EMPTY),
+                    '7' => self.save_cursor(),
+                    '8' => self.restore_cursor(),
+                    'c' => {
+                        self.console.attributes = Attributes::DEFAULT;
+                        self.clear();
+                    }
+                    _ => {}
+                }
+                return;
+            }
+            EscapeState::ControlSequence(mut parameters) => {
+                self.console.escape = EscapeState::Ground;
+                match c {
+                    '0'..='9' => parameters.push_digit(c as u16 - '0' as u16),
+                    ';' | ':' => parameters.separator(),
+                    '<'..='?' => parameters.private = Some(c),
+                    ' '..='/' => parameters.intermediate = Some(c),
+                    '\x40'..='\x7e' => {
+                        self.control_sequence(c, &parameters);
+                        return;
+                    }
+                    // anything else cancels the sequence
+                    _ => return,
+                }
+                self.console.escape = EscapeState::ControlSequence(parameters);
+                return;
+            }
+        }
+        match c {
+            ESCAPE => self.console.escape = EscapeState::Escape,
+            '\n' => self.newline(),
+            '\r' => self.carriage_return(),
+            '\x08' => self.backspace(),
+            c => {
+                let new_xpos = self.console.x_pos + CHAR_RASTER_WIDTH;
+
+                if new_xpos >= self.width() {
+                    self.newline();
+                }
+
+                let (column, row) = self.text_position();
+                let attributes = self.console.attributes;
+                self.set_cell(column, row, Cell { c, attributes });
+                self.console.x_pos += CHAR_RASTER_WIDTH + LETTER_SPACING;
+            }
+        }
+    }
+
+    /// Executes the control sequences for colors, cursor movement, erasing
+    /// and the cursor style, other sequences are ignored.
+    fn control_sequence(&mut self, action: char, parameters: &Parameters) {
+        match (parameters.private, parameters.intermediate, action) {
+            (None, None, _) => {}
+            // show or hide the cursor
+            (Some('?'), None, 'h' | 'l') => {
+                if parameters.get(0) == Some(25) {
+                    self.console.cursor.visible = action == 'h';
+                }
+                return;
+            }
+            // cursor style, bars are shown as underlines
+            (None, Some(' '), 'q') => {
+                let style = parameters.get(0).unwrap_or(0);
+                self.console.cursor.style = match style {
+                    0..=2 => CursorStyle::Block,
+                    _ => CursorStyle::Underline,
+                };
+                self.console.cursor.blink = style == 0 || style % 2 == 1;
+                return;
+            }
+            _ => return,
+        }
+        let (column, row) = self.text_position();
+        let count = parameters.count(0);
+        match action {
+            'A' => self.move_to(column, row.saturating_sub(count)),
+            'B' => self.move_to(column, row + count),
+            'C' => self.move_to(column + count, row),
+            'D' => self.move_to(column.saturating_sub(count), row),
+            'E' => self.move_to(0, row + count),
+            'F' => self.move_to(0, row.saturating_sub(count)),
+            'G' | '`' => self.move_to(count - 1, row),
+            'd' => self.move_to(column, count - 1),
+            'H' | 'f' => self.move_to(parameters.count(1) - 1, count - 1),
+            'J' => self.erase_display(parameters.get(0).unwrap_or(0)),
+            'K' => self.erase_line(parameters.get(0).unwrap_or(0)),
+            'm' => self.select_graphic_rendition(parameters),
+            's' => self.save_cursor(),
+            'u' => self.restore_cursor(),
+            _ => {}
+        }
+    }
+
+    /// Moves to a character cell, limited to the screen.
+    fn move_to(&mut self, column: usize, row: usize) {
+        let (columns, rows) = self.text_dimensions();
+        let width = CHAR_RASTER_WIDTH + LETTER_SPACING;
+        let line_height = CHAR_RASTER_HEIGHT.val() + LINE_SPACING;
+        self.console.x_pos = BORDER_PADDING + column.min(columns - 1) * width;
+        self.console.y_pos = BORDER_PADDING + row.min(rows - 1) * line_height;
+    }
+
+    /// `ESC [ n K` erases to the end of the line for 0, to the start for 1
+    /// and the whole line for 2.
+    fn erase_line(&mut self, mode: usize) {
+        let (column, row) = self.text_position();
+        match mode {
+            0 => self.erase_cells(row, column, usize::MAX),
+            1 => self.erase_cells(row, 0, column + 1),
+            2 => self.erase_cells(row, 0, usize::MAX),
+            _ => {}
+        }
+    }
+
+    /// `ESC [ n J` erases to the end of the screen for 0, to the start for
+    /// 1, the whole screen for 2 and also the scrollback history for 3.
+    fn erase_display(&mut self, mode: usize) {
+        let (_, row) = self.text_position();
+        let rows = self.text_dimensions().1;
+        let lines = match mode {
+            0 => row + 1..rows,
+            1 => 0..row,
+            2 | 3 => 0..rows,
+            _ => return,
+        };
+        if mode < 2 {
+            self.erase_line(mode);
+        }
+        for line in lines {
+            self.erase_cells(line, 0, usize::MAX);
+        }
+        if mode == 3
+            && let Some(text) = self.console.text.as_mut()
+        {
+            text.clear_history();
+        }
+    }
+
+    /// Clears the cells from `start` up to `end` of a row, they keep the
+    /// current background color.
+    fn erase_cells(&mut self, row: usize, start: usize, end: usize) {
+        let blank = Cell {
+            c: ' ',
+            attributes: self.console.attributes,
+        };
+        for column in start..end.min(self.text_dimensions().0) {
+            self.set_cell(column, row, blank);
+        }
+    }
+
+    /// `ESC [ ... m` sets the colors: 30-37, 90-97 and `38;5;n` or
+    /// `38;2;r;g;b` for the foreground and 40-47, 100-107 and 48 for the
+    /// background. 1 is bold, 7 reverse and 0 resets everything.
+    fn select_graphic_rendition(&mut self, parameters: &Parameters) {
+        if parameters.len == 0 {
+            self.console.attributes = Attributes::DEFAULT;
+        }
+        let mut values = parameters.iter();
+        while let Some(value) = values.next() {
+            let attributes = &mut self.console.attributes;
+            match value {
+                0 => *attributes = Attributes::DEFAULT,
+                1 => attributes.bold = true,
+                22 => attributes.bold = false,
+                7 => attributes.reverse = true,
+                27 => attributes.reverse = false,
+                30..=37 => attributes.foreground = Color::Indexed(value as u8 - 30),
+                38 => {
+                    if let Some(color) = extended_color(&mut values) {
+                        attributes.foreground = color;
+                    }
+                }
+                39 => attributes.foreground = Color::Default,
+                40..=47 => attributes.background = Color::Indexed(value as u8 - 40),
+                48 => {
+                    if let Some(color) = extended_color(&mut values) {
+                        attributes.background = color;
+                    }
+                }
+                49 => attributes.background = Color::Default,
+                90..=97 => attributes.foreground = Color::Indexed(value as u8 - 90 + 8),
+                100..=107 => attributes.background = Color::Indexed(value as u8 - 100 + 8),
+                _ => {}
+            }
+        }
+    }
+
+    fn save_cursor(&mut self) {
+        self.console.saved = Some((
+            self.console.x_pos,
+            self.console.y_pos,
+            self.console.attributes,
+        ));
+    }
+
+    fn restore_cursor(&mut self) {
+        if let Some((x, y, attributes)) = self.console.saved {
+            (
+                self.console.x_pos,
+                self.console.y_pos,
+                self.console.attributes,
+            ) = (x, y, attributes);
+        }
+    }
+
+    /// Column and row of the character cell at the current position.
+    fn text_position(&self) -> (usize, usize) {
+        let width = CHAR_RASTER_WIDTH + LETTER_SPACING;
+        let line_height = CHAR_RASTER_HEIGHT.val() + LINE_SPACING;
+        (
+            (self.console.x_pos - BORDER_PADDING) / width,
+            (self.console.y_pos - BORDER_PADDING) / line_height,
+        )
+    }
+
+    /// Puts a character into the grid, or draws it right away while there
+    /// is no grid yet.
+    fn set_cell(&mut self, column: usize, row: usize, cell: Cell) {
+        match self.console.text.as_mut() {
+            Some(text) => text.set(column, row, cell),
+            None => self.draw_text_cell(column, row, cell, false),
+        }
+    }
+
+    /// Moves the view `lines` back into the scrollback history, or forward
+    /// for negative numbers.
+    fn scroll_view(&mut self, lines: isize) {
+        if let Some(text) = self.console.text.as_mut() {
+            text.set_offset(text.offset().saturating_add_signed(lines));
+        }
+        self.flush();
+    }
+
+    /// Where the cursor is shown right now, `None` while it is hidden or
+    /// blinked off.
+    fn cursor_cell(&self) -> Option<(usize, usize)> {
+        let cursor = &self.console.cursor;
+        let scrolled_back = self
+            .console
+            .text
+            .as_ref()
+            .is_some_and(|text| text.offset() > 0);
+        let phase = (time::uptime().saturating_sub(cursor.blink_start)).as_millis()
+            / CURSOR_BLINK_INTERVAL.as_millis();
+        if !cursor.visible || scrolled_back || (cursor.blink && phase % 2 == 1) {
+            return None;
+        }
+        let (columns, _) = self.text_dimensions();
+        let (column, row) = self.text_position();
+        Some((column.min(columns - 1), row))
+    }
+
+    /// Draws the cells of the grid that changed since they were last drawn.
+    fn flush(&mut self) {
+        if !self.shown() {
+            return;
+        }
+        let cursor = self.cursor_cell();
+        let (Some(mut drawn), Some(mut text)) = (self.drawn.take(), self.console.text.take())
+        else {
+            return;
+        };
+        text.redraw(&mut drawn, cursor, |column, row, cell, cursor| {
+            self.draw_text_cell(column, row, cell, cursor)
+        });
+        self.console.text = Some(text);
+        self.drawn = Some(drawn);
+    }
+
+    /// Draws a character cell including the line spacing below it, with the
+    /// cursor on it if `cursor` is set.
+    fn draw_text_cell(&mut self, column: usize, row: usize, cell: Cell, cursor: bool) {
+        let width = CHAR_RASTER_WIDTH + LETTER_SPACING;
+        let line_height = CHAR_RASTER_HEIGHT.val() + LINE_SPACING;
+        let (x_pos, y_pos) = (self.console.x_pos, self.console.y_pos);
+        self.console.x_pos = BORDER_PADDING + column * width;
+        self.console.y_pos = BORDER_PADDING + row * line_height;
+
+        let (mut foreground, mut background) = cell.attributes.colors();
+        if cursor && self.console.cursor.style == CursorStyle::Block {
+            core::mem::swap(&mut foreground, &mut background);
+        }
+        self.write_rendered_char(get_rasterized_char(cell.c), (foreground, background));
+        if cursor && self.console.cursor.style == CursorStyle::Underline {
+            let y = self.console.y_pos + CHAR_RASTER_HEIGHT.val();
+            let x = BORDER_PADDING + column * width;
+            self.fill_pixels(x, y, CHAR_RASTER_WIDTH, LINE_SPACING, foreground);
+        }
+        (self.console.x_pos, self.console.y_pos) = (x_pos, y_pos);
+    }
+
+    fn write_rendered_char(&mut self, rendered_char: RasterizedChar, colors: (Rgb, Rgb)) {
+        for (y, row) in rendered_char.raster().iter().enumerate() {
+            for (x, byte) in row.iter().enumerate() {
+                self.write_pixel(
+                    self.console.x_pos + x,
+                    self.console.y_pos + y,
+                    *byte,
+                    colors,
+                );
+            }
+        }
+        // the line spacing below gets the background too
+        let (x, y) = (
+            self.console.x_pos,
+            self.console.y_pos + rendered_char.height(),
+        );
+        self.fill_pixels(x, y, rendered_char.width(), LINE_SPACING, colors.1);
+        self.console.x_pos += rendered_char.width() + LETTER_SPACING;
+    }
+
+    // ------------------------------------------------------------------------
+    // Low-Level Pixel Operations
+    // ------------------------------------------------------------------------
+
+    /// Writes a pixel of a glyph, blending the foreground into the
+    /// background by the glyph's intensity.
+    fn write_pixel(&mut self, x: usize, y: usize, intensity: u8, colors: (Rgb, Rgb)) {
+        let format = self.info.pixel_format;
+        if !matches!(
+            format,
+            PixelFormat::Rgb | PixelFormat::Bgr | PixelFormat::U8
+        ) {
+            // the panic message is printed here too
+            self.info.pixel_format = PixelFormat::Rgb;
+            panic!("pixel format {format:?} not supported in FrameBufferWriter")
+        }
+        let (foreground, background) = colors;
+        let blend = |foreground: u8, background: u8| {
+            let intensity = intensity as u16;
+            ((foreground as u16 * intensity + background as u16 * (255 - intensity)) / 255) as u8
+        };
+        let color = Rgb {
+            r: blend(foreground.r, background.r),
+            g: blend(foreground.g, background.g),
+            b: blend(foreground.b, background.b),
+        };
+        let color = self.encode(color);
+        self.write_color(x, y, color);
+    }
+
+    pub fn put_pixel_rgb(&mut self, x: usize, y: usize, c: Rgb) {
+        if x >= self.info.width || y >= self.info.height {
+            return;
+        }
+        self.console.graphics = true;
+        let color = self.encode(c);
+        self.write_color(x, y, color);
+    }
+
+    fn encode(&self, c: Rgb) -> [u8; 4] {
+        match self.info.pixel_format {
+            PixelFormat::Rgb => [c.r, c.g, c.b, 0],
+            PixelFormat::Bgr => [c.b, c.g, c.r, 0],
+            PixelFormat::U8 => {
+                let v = if (c.r as u16 + c.g as u16 + c.b as u16) > 0 {
+                    0xF
+                } else {
+                    0x0
+                };
+                [v, 0, 0, 0]
+            }
+            other => panic!("pixel format {other:?} not supported"),
+        }
+    }
+
+    /// Writes an encoded pixel, or remembers it if the pointer covers it.
+    /// Nothing is drawn for a console that is not shown.
+    fn write_color(&mut self, x: usize, y: usize, color: [u8; 4]) {
+        if !self.shown() {
+            return;
+        }
+        if let Some(pointer) = self.pointer.as_mut()
+            && let Some(index) = pointer.covered_index(x, y)
+        {
+            pointer.background[index] = color;
+            return;
+        }
+        self.write_framebuffer(x, y, color);
+    }
+
+    fn write_framebuffer(&mut self, x: usize, y: usize, color: [u8; 4]) {
+        let bytes_per_pixel = self.info.bytes_per_pixel;
+        let byte_offset = (y * self.info.stride + x) * bytes_per_pixel;
+        self.framebuffer[byte_offset..(byte_offset + bytes_per_pixel)]
+            .copy_from_slice(&color[..bytes_per_pixel]);
+
+        let _ = unsafe { ptr::read_volatile(&self.framebuffer[byte_offset]) };
+    }
+
+    fn read_framebuffer(&self, x: usize, y: usize) -> [u8; 4] {
+        let bytes_per_pixel = self.info.bytes_per_pixel;
+        let byte_offset = (y * self.info.stride + x) * bytes_per_pixel;
+        let mut color = [0; 4];
+        color[..bytes_per_pixel]
+            .copy_from_slice(&self.framebuffer[byte_offset..(byte_offset + bytes_per_pixel)]);
+        color
+    }
+
+    // ------------------------------------------------------------------------
+    // Mouse Pointer
+    // ------------------------------------------------------------------------
+
+    /// Draws the mouse pointer with its tip at `(x, y)`, restoring the pixels
+    /// it covered before.
+    pub fn move_pointer(&mut self, x: usize, y: usize) {
+        self.hide_pointer();
+        let mut pointer = Pointer {
+            x,
+            y,
+            background: [[0; 4]; POINTER_WIDTH * POINTER_HEIGHT],
+        };
+        let (outline, fill) = (self.encode(Rgb::BLACK), self.encode(Rgb::WHITE));
+        for (px, py, index) in pointer_pixels(x, y, self.dimensions()) {
+            pointer.background[index] = self.read_framebuffer(px, py);
+            let color = match POINTER_SPRITE[py - y][px - x] {
+                b'#' => outline,
+                _ => fill,
+            };
+            self.write_framebuffer(px, py, color);
+        }
+        self.pointer = Some(pointer);
+    }
+
+    /// Removes the mouse pointer from the screen.
+    pub fn hide_pointer(&mut self) {
+        if let Some(pointer) = self.pointer.take() {
+            for (px, py, index) in pointer_pixels(pointer.x, pointer.y, self.dimensions()) {
+                self.write_framebuffer(px, py, pointer.background[index]);
+            }
+        }
+    }
+
+    // ------------------------------------------------------------------------
+    // Graphics Operations
+    // ------------------------------------------------------------------------
+
+    pub fn clear_color(&mut self, c: Rgb) {
+        let (width, height) = self.dimensions();
+        self.fill_rect(0, 0, width, height, c);
+        self.console.x_pos = BORDER_PADDING;
+        self.console.y_pos = BORDER_PADDING;
+        if let Some(text) = self.console.text.as_mut() {
+            text.clear();
+        }
+        // text drawn later gets a black background on top of the color
+        if self.shown() {
+            self.forget_drawn();
+        }
+        self.console.painting = Some(Painting::new(c));
+    }
+
+    pub fn fill_rect(&mut self, x: usize, y: usize, w: usize, h: usize, c: Rgb) {
+        self.console.graphics = true;
+        self.fill_pixels(x, y, w, h, c);
+    }
+
+    /// Fills a rectangle that belongs to the text, unlike [`Self::fill_rect`].
+    fn fill_pixels(&mut self, x: usize, y: usize, w: usize, h: usize, c: Rgb) {
+        let x_end = (x + w).min(self.info.width);
+        let y_end = (y + h).min(self.info.height);
+        let color = self.encode(c);
+
+        for yy in y..y_end {
+            for xx in x..x_end {
+                self.write_color(xx, yy, color);
+            }
+        }
+    }
+
+    pub fn draw_cell(&mut self, cx: usize, cy: usize, cell_size: usize, color: Rgb) {
+        let px = cx * cell_size;
+        let py = cy * cell_size;
+        self.fill_rect(px, py, cell_size, cell_size, color);
+        let dimensions = self.dimensions();
+        self.console
+            .painting
+            .get_or_insert_with(|| Painting::new(Rgb::BLACK))
+            .set(cx, cy, cell_size, color, dimensions);
+    }
+
+    pub fn draw_cell_inset(
+        &mut self,
+        cx: usize,
+        cy: usize,
+        cell_size: usize,
+        inset: usize,
+        color: Rgb,
+    ) {
+        let px = cx * cell_size + inset;
+        let py = cy * cell_size + inset;
+        let size = cell_size.saturating_sub(inset * 2);
+        self.fill_rect(px, py, size, size, color);
+    }
+}
+
+/// Opaque pixels of the pointer at `(x, y)` that are on the screen, with their
+/// index into the sprite.
+fn pointer_pixels(
+    x: usize,
+    y: usize,
+    (width, height): (usize, usize),
+) -> impl Iterator<Item = (usize, usize, usize)> {
+    (y..(y + POINTER_HEIGHT).min(height))
+        .flat_map(move |py| (x..(x + POINTER_WIDTH).min(width)).map(move |px| (px, py)))
+        .filter(move |&(px, py)| POINTER_SPRITE[py - y][px - x] != b'.')
+        .map(move |(px, py)| (px, py, (py - y) * POINTER_WIDTH + (px - x)))
+}
+
+// ============================================================================
+// Trait Implementations
+// ============================================================================
+
+unsafe impl Sync for FrameBufferWriter {}
+
+impl fmt::Write for FrameBufferWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        // new output brings the view back from the scrollback history
+        if let Some(text) = self.console.text.as_mut() {
+            text.set_offset(0);
+        }
+        for c in s.chars() {
+            self.write_char(c);
+        }
+        self.console.cursor.blink_start = time::uptime();
+        self.flush();
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+#[test_case]
+fn test_println_many() {
+    without_interrupts(|| {
+        for _ in 0..200 {
+            println!("test_println_many output");
+        }
+    })
+}
+
+#[test_case]
+fn test_control_sequence_parameters() {
+    let mut parameters = Parameters::EMPTY;
+    for c in "38;2;;255;0".chars() {
+        match c {
+            ';' => parameters.separator(),
+            c => parameters.push_digit(c.to_digit(10).unwrap() as u16),
+        }
+    }
+    assert_eq!(parameters.get(2), None);
+    assert_eq!(parameters.count(2), 1);
+    let mut values = parameters.iter();
+    assert_eq!(values.next(), Some(38));
+    assert_eq!(
+        extended_color(&mut values),
+        Some(Color::Rgb(Rgb { r: 0, g: 255, b: 0 }))
+    );
+    assert_eq!(values.next(), None);
+    assert_eq!(Parameters::EMPTY.iter().count(), 0);
+}
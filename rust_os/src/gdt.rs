@@ -1,3 +1,4 @@
+use alloc::boxed::Box;
 use lazy_static::lazy_static;
 use x86_64::{
     VirtAddr,
@@ -11,6 +12,9 @@ use x86_64::{
 
 pub const DOUBLE_FAULT_IST_INDEX: u16 = 0;
 
+/// Pages of the double fault stack of application processors.
+const DOUBLE_FAULT_STACK_PAGES: u64 = 5;
+
 struct GlobalDescriptorContext {
     gdt: GlobalDescriptorTable,
     kernel_code: SegmentSelector,
@@ -39,10 +43,15 @@ lazy_static! {
 
 // GDT is needed to actually load the TSS
 lazy_static! {
-    static ref GLOBAL_DESCRIPTOR_CONTEXT: GlobalDescriptorContext = {
+    static ref GLOBAL_DESCRIPTOR_CONTEXT: GlobalDescriptorContext =
+        GlobalDescriptorContext::new(&TASK_STATE_SEGMENT);
+}
+
+impl GlobalDescriptorContext {
+    fn new(task_state_segment: &'static TaskStateSegment) -> Self {
         let mut gdt = GlobalDescriptorTable::new();
         let kernel_code = gdt.append(Descriptor::kernel_code_segment());
-        let task_state = gdt.append(Descriptor::tss_segment(&TASK_STATE_SEGMENT));
+        let task_state = gdt.append(Descriptor::tss_segment(task_state_segment));
         let kernel_data = gdt.append(Descriptor::kernel_data_segment());
 
         GlobalDescriptorContext {
@@ -51,16 +60,33 @@ lazy_static! {
             task_state,
             kernel_data,
         }
-    };
+    }
+
+    fn load(&'static self) {
+        self.gdt.load();
+        unsafe {
+            CS::set_reg(self.kernel_code);
+            SS::set_reg(self.kernel_data);
+            DS::set_reg(self.kernel_data);
+            ES::set_reg(self.kernel_data);
+            load_tss(self.task_state);
+        }
+    }
 }
 
 pub fn initialize_global_descriptor_table() {
-    GLOBAL_DESCRIPTOR_CONTEXT.gdt.load();
-    unsafe {
-        CS::set_reg(GLOBAL_DESCRIPTOR_CONTEXT.kernel_code);
-        SS::set_reg(GLOBAL_DESCRIPTOR_CONTEXT.kernel_data);
-        DS::set_reg(GLOBAL_DESCRIPTOR_CONTEXT.kernel_data);
-        ES::set_reg(GLOBAL_DESCRIPTOR_CONTEXT.kernel_data);
-        load_tss(GLOBAL_DESCRIPTOR_CONTEXT.task_state);
-    }
+    GLOBAL_DESCRIPTOR_CONTEXT.load();
+}
+
+/// Loads a GDT and TSS of its own on an application processor. A TSS can only
+/// be in use by one CPU, and every CPU needs its own double fault stack.
+pub fn initialize_for_application_processor() {
+    let stack_top =
+        crate::memory::with_kernel_memory(|memory| memory.allocate_stack(DOUBLE_FAULT_STACK_PAGES))
+            .expect("could not allocate a double fault stack");
+
+    let mut task_state_segment = TaskStateSegment::new();
+    task_state_segment.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = stack_top;
+    let task_state_segment = Box::leak(Box::new(task_state_segment));
+    Box::leak(Box::new(GlobalDescriptorContext::new(task_state_segment))).load();
 }
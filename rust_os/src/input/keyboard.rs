@@ -0,0 +1,303 @@
+use alloc::{sync::Arc, vec::Vec};
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures_util::Stream;
+use pc_keyboard::{DecodedKey, HandleControl, KeyCode, KeyState, Keyboard, layouts::AnyLayout};
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use super::EventQueue;
+use super::layout::{AnyScancodeSet, Layout, ScancodeSetKind};
+use super::ps2::{self, Typematic};
+use crate::console::{self, CONSOLES};
+use crate::{config, framebuffer};
+
+/// A key going down or up, decoded once for all subscribers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub code: KeyCode,
+    pub state: KeyState,
+    /// The key translated with the keyboard layout, only set when a key goes down.
+    pub key: Option<DecodedKey>,
+    /// Modifiers after this event was applied.
+    pub modifiers: Modifiers,
+}
+
+impl KeyEvent {
+    /// Returns the decoded key if this event is a key press.
+    pub fn pressed(&self) -> Option<DecodedKey> {
+        match self.state {
+            KeyState::Down => self.key,
+            _ => None,
+        }
+    }
+}
+
+/// State of the modifier keys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub alt_gr: bool,
+    pub caps_lock: bool,
+    pub num_lock: bool,
+}
+
+impl From<&pc_keyboard::Modifiers> for Modifiers {
+    fn from(modifiers: &pc_keyboard::Modifiers) -> Self {
+        Modifiers {
+            shift: modifiers.is_shifted(),
+            ctrl: modifiers.is_ctrl(),
+            alt: modifiers.lalt,
+            alt_gr: modifiers.ralt,
+            caps_lock: modifiers.capslock,
+            num_lock: modifiers.numlock,
+        }
+    }
+}
+
+static DECODER: Mutex<Decoder> = Mutex::new(Decoder::new(Layout::Us, ScancodeSetKind::Set1));
+
+struct Decoder {
+    keyboard: Keyboard<AnyLayout, AnyScancodeSet>,
+    /// The keyboard does not expose its layout and scancode set, so they
+    /// are kept next to it.
+    layout: Layout,
+    scancode_set: ScancodeSetKind,
+}
+
+impl Decoder {
+    const fn new(layout: Layout, scancode_set: ScancodeSetKind) -> Self {
+        Decoder {
+            keyboard: Keyboard::new(
+                AnyScancodeSet::new(scancode_set),
+                layout.to_any(),
+                // Ctrl+letter gives the same control characters a terminal sends
+                HandleControl::MapLettersToUnicode,
+            ),
+            layout,
+            scancode_set,
+        }
+    }
+}
+
+/// Applies the `KEYMAP`, `SCANCODE_SET` and `KEYBOARD_REPEAT_*` settings of
+/// the configuration.
+pub fn load_config() {
+    if let Some(name) = config::get("KEYMAP") {
+        match name.parse() {
+            Ok(layout) => set_layout(layout),
+            Err(_) => log::warn!("unknown keyboard layout '{}'", name),
+        }
+    }
+    if let Some(name) = config::get("SCANCODE_SET") {
+        match name.parse() {
+            Ok(set) => set_scancode_set(set),
+            Err(_) => log::warn!("unknown scancode set '{}'", name),
+        }
+    }
+    let delay = config::get_parsed("KEYBOARD_REPEAT_DELAY_MS");
+    let rate = config::get_parsed("KEYBOARD_REPEAT_RATE");
+    if delay.is_some() || rate.is_some() {
+        let default = Typematic::default();
+        ps2::set_typematic(Typematic {
+            delay: delay.map_or(default.delay, Duration::from_millis),
+            rate: rate.unwrap_or(default.rate),
+        });
+    }
+}
+
+/// The layout used to translate keys.
+pub fn layout() -> Layout {
+    without_interrupts(|| DECODER.lock().layout)
+}
+
+/// Switches the layout used to translate keys. Modifier state is reset.
+pub fn set_layout(layout: Layout) {
+    without_interrupts(|| {
+        let mut decoder = DECODER.lock();
+        let scancode_set = decoder.scancode_set;
+        *decoder = Decoder::new(layout, scancode_set);
+    });
+}
+
+/// The scancode set the decoder expects from the keyboard.
+pub fn scancode_set() -> ScancodeSetKind {
+    without_interrupts(|| DECODER.lock().scancode_set)
+}
+
+/// Switches the scancode set the decoder expects. Modifier state is reset.
+pub fn set_scancode_set(scancode_set: ScancodeSetKind) {
+    without_interrupts(|| {
+        let mut decoder = DECODER.lock();
+        let layout = decoder.layout;
+        *decoder = Decoder::new(layout, scancode_set);
+    });
+}
+
+static SUBSCRIBERS: Mutex<Subscribers> = Mutex::new(Subscribers {
+    monitors: Vec::new(),
+    focus_stacks: [const { Vec::new() }; CONSOLES],
+});
+
+/// Everyone interested in key events.
+struct Subscribers {
+    /// Receive every event.
+    monitors: Vec<Arc<Subscriber>>,
+    /// A focus stack for each virtual console. Only the top of the stack of
+    /// the console on the screen receives events.
+    focus_stacks: [Vec<Arc<Subscriber>>; CONSOLES],
+}
+
+type Subscriber = EventQueue<KeyEvent>;
+
+/// Decodes a scancode of the keyboard and hands the resulting event to the
+/// subscribers. Called by the PS/2 driver for every byte that is not a reply.
+pub fn handle_scancode(scancode: u8) -> Option<KeyEvent> {
+    let event = without_interrupts(|| {
+        let decoder = &mut DECODER.lock().keyboard;
+        let event = decoder.add_byte(scancode).ok()??;
+        let (code, state) = (event.code, event.state);
+        let key = decoder.process_keyevent(event);
+        Some(KeyEvent {
+            code,
+            state,
+            key,
+            modifiers: decoder.get_modifiers().into(),
+        })
+    });
+    if let Some(event) = event
+        && !scrollback_key(&event)
+        && !console_key(&event)
+    {
+        dispatch(event);
+    }
+    event
+}
+
+/// Scrolls the console for Shift+PageUp and Shift+PageDown. Returns whether
+/// the event was such a key, which is not passed on.
+fn scrollback_key(event: &KeyEvent) -> bool {
+    let pages = match event.code {
+        KeyCode::PageUp => 1,
+        KeyCode::PageDown => -1,
+        _ => return false,
+    };
+    if !event.modifiers.shift {
+        return false;
+    }
+    if event.state == KeyState::Down {
+        framebuffer::scroll_back(pages);
+    }
+    true
+}
+
+/// Switches the virtual console for Alt+F1 to Alt+F6. Returns whether the
+/// event was such a key, which is not passed on.
+fn console_key(event: &KeyEvent) -> bool {
+    let console = match event.code {
+        KeyCode::F1 => 0,
+        KeyCode::F2 => 1,
+        KeyCode::F3 => 2,
+        KeyCode::F4 => 3,
+        KeyCode::F5 => 4,
+        KeyCode::F6 => 5,
+        _ => return false,
+    };
+    if !event.modifiers.alt {
+        return false;
+    }
+    if event.state == KeyState::Down {
+        console::request_switch(console);
+    }
+    true
+}
+
+/// Delivers `event` to all monitors and to the subscriber that has the focus
+/// of the console on the screen.
+pub fn dispatch(event: KeyEvent) {
+    without_interrupts(|| {
+        let subscribers = SUBSCRIBERS.lock();
+        for monitor in &subscribers.monitors {
+            monitor.push(event);
+        }
+        if let Some(focused) = subscribers.focus_stacks[console::active()].last() {
+            focused.push(event);
+        }
+    });
+}
+
+/// Stream of key events for one subscriber.
+pub struct KeyEvents {
+    subscriber: Arc<Subscriber>,
+    /// The console of the focus stack.
+    console: usize,
+}
+
+impl KeyEvents {
+    fn new(register: impl FnOnce(&mut Subscribers, Arc<Subscriber>, usize)) -> Self {
+        let subscriber = Arc::new(Subscriber::new());
+        let console = console::current();
+        without_interrupts(|| register(&mut SUBSCRIBERS.lock(), subscriber.clone(), console));
+        KeyEvents {
+            subscriber,
+            console,
+        }
+    }
+
+    /// Receives every key event, no matter who has the focus.
+    pub fn monitor() -> Self {
+        Self::new(|subscribers, subscriber, _| subscribers.monitors.push(subscriber))
+    }
+
+    /// Takes the keyboard focus of the console the caller runs on. Events
+    /// only reach the most recent focus until it is dropped, then the
+    /// previous one gets them again. Nothing arrives while the console is
+    /// not on the screen.
+    pub fn focus() -> Self {
+        Self::new(|subscribers, subscriber, console| {
+            subscribers.focus_stacks[console].push(subscriber)
+        })
+    }
+
+    /// Whether this stream is currently on top of the focus stack of its
+    /// console.
+    pub fn has_focus(&self) -> bool {
+        without_interrupts(|| {
+            let subscribers = SUBSCRIBERS.lock();
+            subscribers.focus_stacks[self.console]
+                .last()
+                .is_some_and(|focused| Arc::ptr_eq(focused, &self.subscriber))
+        })
+    }
+
+    /// Returns the next buffered event without waiting.
+    pub fn try_next(&mut self) -> Option<KeyEvent> {
+        self.subscriber.pop()
+    }
+}
+
+impl Stream for KeyEvents {
+    type Item = KeyEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<KeyEvent>> {
+        self.subscriber.poll_next(cx)
+    }
+}
+
+impl Drop for KeyEvents {
+    fn drop(&mut self) {
+        without_interrupts(|| {
+            let mut subscribers = SUBSCRIBERS.lock();
+            let is_other =
+                |subscriber: &Arc<Subscriber>| !Arc::ptr_eq(subscriber, &self.subscriber);
+            subscribers.monitors.retain(is_other);
+            subscribers.focus_stacks[self.console].retain(is_other);
+        });
+    }
+}
@@ -0,0 +1,142 @@
+use core::{fmt, str::FromStr};
+
+use pc_keyboard::{
+    Error, KeyEvent, ScancodeSet, ScancodeSet1, ScancodeSet2,
+    layouts::{AnyLayout, Azerty, De105Key, Dvorak104Key, Uk105Key, Us104Key},
+};
+
+/// Keyboard layouts that can be selected at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    Us,
+    Uk,
+    German,
+    French,
+    Dvorak,
+}
+
+impl Layout {
+    pub const ALL: [Layout; 5] = [
+        Layout::Us,
+        Layout::Uk,
+        Layout::German,
+        Layout::French,
+        Layout::Dvorak,
+    ];
+
+    /// Name used by `loadkeys` and the `KEYMAP` configuration key.
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::Us => "us",
+            Layout::Uk => "uk",
+            Layout::German => "de",
+            Layout::French => "fr",
+            Layout::Dvorak => "dvorak",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Layout::Us => "US 104-key",
+            Layout::Uk => "UK 105-key",
+            Layout::German => "German 105-key (QWERTZ)",
+            Layout::French => "French (AZERTY)",
+            Layout::Dvorak => "Dvorak 104-key",
+        }
+    }
+
+    pub(super) const fn to_any(self) -> AnyLayout {
+        match self {
+            Layout::Us => AnyLayout::Us104Key(Us104Key),
+            Layout::Uk => AnyLayout::Uk105Key(Uk105Key),
+            Layout::German => AnyLayout::De105Key(De105Key),
+            Layout::French => AnyLayout::Azerty(Azerty),
+            Layout::Dvorak => AnyLayout::Dvorak104Key(Dvorak104Key),
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownLayout;
+
+impl FromStr for Layout {
+    type Err = UnknownLayout;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Layout::ALL
+            .into_iter()
+            .find(|layout| layout.name().eq_ignore_ascii_case(name))
+            .ok_or(UnknownLayout)
+    }
+}
+
+/// Scancode set the keyboard sends. Controllers usually translate set 2 into
+/// set 1, set 2 is needed if translation is turned off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScancodeSetKind {
+    Set1,
+    Set2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownScancodeSet;
+
+impl fmt::Display for ScancodeSetKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScancodeSetKind::Set1 => f.write_str("1"),
+            ScancodeSetKind::Set2 => f.write_str("2"),
+        }
+    }
+}
+
+impl FromStr for ScancodeSetKind {
+    type Err = UnknownScancodeSet;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "1" => Ok(ScancodeSetKind::Set1),
+            "2" => Ok(ScancodeSetKind::Set2),
+            _ => Err(UnknownScancodeSet),
+        }
+    }
+}
+
+/// Decoder for either scancode set.
+pub(super) enum AnyScancodeSet {
+    Set1(ScancodeSet1),
+    Set2(ScancodeSet2),
+}
+
+impl AnyScancodeSet {
+    pub(super) const fn new(kind: ScancodeSetKind) -> Self {
+        match kind {
+            ScancodeSetKind::Set1 => AnyScancodeSet::Set1(ScancodeSet1::new()),
+            ScancodeSetKind::Set2 => AnyScancodeSet::Set2(ScancodeSet2::new()),
+        }
+    }
+}
+
+impl ScancodeSet for AnyScancodeSet {
+    fn advance_state(&mut self, code: u8) -> Result<Option<KeyEvent>, Error> {
+        match self {
+            AnyScancodeSet::Set1(set) => set.advance_state(code),
+            AnyScancodeSet::Set2(set) => set.advance_state(code),
+        }
+    }
+}
+
+#[test_case]
+fn test_layout_names_round_trip() {
+    for layout in Layout::ALL {
+        assert_eq!(layout.name().parse(), Ok(layout));
+    }
+    assert_eq!("DE".parse(), Ok(Layout::German));
+    assert_eq!("klingon".parse::<Layout>(), Err(UnknownLayout));
+}
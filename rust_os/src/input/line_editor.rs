@@ -0,0 +1,223 @@
+use alloc::{string::String, vec::Vec};
+
+use pc_keyboard::{DecodedKey, KeyCode};
+
+use crate::print;
+
+// control characters of Ctrl+letter key combinations
+const CTRL_A: char = '\x01';
+const CTRL_B: char = '\x02';
+const CTRL_E: char = '\x05';
+const CTRL_F: char = '\x06';
+const CTRL_K: char = '\x0b';
+const CTRL_U: char = '\x15';
+const CTRL_W: char = '\x17';
+const BACKSPACE: char = '\x08';
+
+/// Editable line of text on the console.
+///
+/// The editor draws the line after whatever was printed before, usually a
+/// prompt, and keeps the screen up to date while keys are handled. Keys it
+/// does not handle, like Enter or Tab, are given back to the caller.
+///
+/// Lines are expected to fit into one row of the screen.
+pub struct LineEditor {
+    line: Vec<char>,
+    cursor: usize,
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        LineEditor {
+            line: Vec::new(),
+            cursor: 0,
+        }
+    }
+
+    /// Applies an editing key. Returns the key if it is not an editing key.
+    ///
+    /// Supports Left/Right/Home/End, Backspace and Delete, and the Ctrl-A,
+    /// Ctrl-E, Ctrl-B, Ctrl-F, Ctrl-K, Ctrl-U and Ctrl-W bindings of Emacs.
+    pub fn handle_key(&mut self, key: DecodedKey) -> Option<DecodedKey> {
+        match key {
+            DecodedKey::Unicode(BACKSPACE) => {
+                if self.cursor > 0 {
+                    self.delete(self.cursor - 1..self.cursor);
+                }
+            }
+            DecodedKey::RawKey(KeyCode::Delete) | DecodedKey::Unicode('\x7f') => {
+                if self.cursor < self.line.len() {
+                    self.delete(self.cursor..self.cursor + 1);
+                }
+            }
+            DecodedKey::RawKey(KeyCode::ArrowLeft) | DecodedKey::Unicode(CTRL_B) => {
+                self.move_cursor(self.cursor.saturating_sub(1))
+            }
+            DecodedKey::RawKey(KeyCode::ArrowRight) | DecodedKey::Unicode(CTRL_F) => {
+                self.move_cursor((self.cursor + 1).min(self.line.len()))
+            }
+            DecodedKey::RawKey(KeyCode::Home) | DecodedKey::Unicode(CTRL_A) => self.move_cursor(0),
+            DecodedKey::RawKey(KeyCode::End) | DecodedKey::Unicode(CTRL_E) => {
+                self.move_cursor(self.line.len())
+            }
+            DecodedKey::Unicode(CTRL_K) => self.delete(self.cursor..self.line.len()),
+            DecodedKey::Unicode(CTRL_U) => self.delete(0..self.cursor),
+            DecodedKey::Unicode(CTRL_W) => self.delete(self.word_start()..self.cursor),
+            DecodedKey::Unicode(c) if !c.is_control() => self.insert(c),
+            key => return Some(key),
+        }
+        None
+    }
+
+    /// The whole line.
+    pub fn text(&self) -> String {
+        self.line.iter().collect()
+    }
+
+    /// The part of the line before the cursor.
+    pub fn text_before_cursor(&self) -> String {
+        self.line[..self.cursor].iter().collect()
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.line.insert(self.cursor, c);
+        self.cursor += 1;
+        self.redraw(self.cursor - 1, self.cursor);
+    }
+
+    pub fn insert_str(&mut self, text: &str) {
+        let start = self.cursor;
+        for c in text.chars() {
+            self.line.insert(self.cursor, c);
+            self.cursor += 1;
+        }
+        self.redraw(start, self.cursor);
+    }
+
+    /// Replaces the line with `text` and moves the cursor to its end.
+    pub fn set_text(&mut self, text: &str) {
+        self.move_cursor(0);
+        self.line = text.chars().collect();
+        self.redraw(0, self.line.len());
+    }
+
+    /// Prints the line again, after the screen was written to by someone else.
+    pub fn draw(&self) {
+        let line: String = self.line.iter().collect();
+        print!("{}", line);
+        cursor_back(self.line.len() - self.cursor);
+    }
+
+    /// Moves the cursor behind the line and returns the line, leaving the
+    /// editor empty.
+    pub fn finish(&mut self) -> String {
+        self.move_cursor(self.line.len());
+        let line = self.text();
+        self.line.clear();
+        self.cursor = 0;
+        line
+    }
+
+    /// Start of the word before the cursor, with the spaces following it.
+    fn word_start(&self) -> usize {
+        let before = &self.line[..self.cursor];
+        let end = before
+            .iter()
+            .rposition(|c| !c.is_whitespace())
+            .map_or(0, |i| i + 1);
+        before[..end]
+            .iter()
+            .rposition(|c| c.is_whitespace())
+            .map_or(0, |i| i + 1)
+    }
+
+    fn delete(&mut self, range: core::ops::Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        let start = range.start;
+        let cursor = if self.cursor >= range.end {
+            self.cursor - range.len()
+        } else {
+            self.cursor.min(start)
+        };
+        self.move_cursor(start.min(self.cursor));
+        self.line.drain(range);
+        self.redraw(start, cursor);
+    }
+
+    fn move_cursor(&mut self, position: usize) {
+        if position < self.cursor {
+            cursor_back(self.cursor - position);
+        } else if position > self.cursor {
+            let moved: String = self.line[self.cursor..position].iter().collect();
+            print!("{}", moved);
+        }
+        self.cursor = position;
+    }
+
+    /// Redraws the line from `start`, where the cursor is on the screen, and
+    /// moves the cursor to `cursor`.
+    fn redraw(&mut self, start: usize, cursor: usize) {
+        let rest: String = self.line[start..].iter().collect();
+        print!("{}\x1b[K", rest);
+        cursor_back(self.line.len() - cursor);
+        self.cursor = cursor;
+    }
+}
+
+impl Default for LineEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn cursor_back(count: usize) {
+    if count > 0 {
+        print!("\x1b[{}D", count);
+    }
+}
+
+#[test_case]
+fn test_line_editor() {
+    fn type_keys(editor: &mut LineEditor, keys: &[DecodedKey]) {
+        for &key in keys {
+            assert_eq!(editor.handle_key(key), None);
+        }
+    }
+    let text = |text: &str| text.chars().map(DecodedKey::Unicode).collect::<Vec<_>>();
+    let key = DecodedKey::RawKey;
+    let mut editor = LineEditor::new();
+
+    type_keys(&mut editor, &text("cat fle"));
+    type_keys(
+        &mut editor,
+        &[key(KeyCode::ArrowLeft), key(KeyCode::ArrowLeft)],
+    );
+    type_keys(&mut editor, &text("i"));
+    type_keys(&mut editor, &[key(KeyCode::Home), key(KeyCode::Delete)]);
+    type_keys(&mut editor, &text("b"));
+    assert_eq!(editor.text(), "bat file");
+    assert_eq!(editor.cursor(), 1);
+
+    type_keys(&mut editor, &[DecodedKey::Unicode(CTRL_E)]);
+    type_keys(&mut editor, &text("  "));
+    type_keys(&mut editor, &[DecodedKey::Unicode(CTRL_W)]);
+    assert_eq!(editor.text(), "bat ");
+    type_keys(
+        &mut editor,
+        &[key(KeyCode::ArrowLeft), DecodedKey::Unicode(CTRL_U)],
+    );
+    assert_eq!(editor.text(), " ");
+    assert_eq!(editor.cursor(), 0);
+    type_keys(&mut editor, &[DecodedKey::Unicode(CTRL_K)]);
+    assert_eq!(
+        editor.handle_key(DecodedKey::Unicode('\n')),
+        Some(DecodedKey::Unicode('\n'))
+    );
+    assert_eq!(editor.finish(), "");
+}
@@ -0,0 +1,56 @@
+use core::task::{Context, Poll};
+
+use crossbeam_queue::ArrayQueue;
+use futures_util::task::AtomicWaker;
+
+pub mod keyboard;
+pub mod layout;
+pub mod line_editor;
+pub mod mouse;
+pub mod ps2;
+pub mod terminal;
+
+/// Number of events a subscriber can fall behind before events are dropped.
+const SUBSCRIBER_QUEUE_CAPACITY: usize = 100;
+
+/// Events buffered for one subscriber of an input device.
+struct EventQueue<T> {
+    queue: ArrayQueue<T>,
+    waker: AtomicWaker,
+}
+
+impl<T> EventQueue<T> {
+    fn new() -> Self {
+        EventQueue {
+            queue: ArrayQueue::new(SUBSCRIBER_QUEUE_CAPACITY),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    fn push(&self, event: T) {
+        if self.queue.push(event).is_err() {
+            log::warn!("input event queue full, dropping input");
+        } else {
+            self.waker.wake();
+        }
+    }
+
+    fn pop(&self) -> Option<T> {
+        self.queue.pop()
+    }
+
+    fn poll_next(&self, cx: &mut Context) -> Poll<Option<T>> {
+        if let Some(event) = self.queue.pop() {
+            return Poll::Ready(Some(event));
+        }
+
+        self.waker.register(cx.waker());
+        match self.queue.pop() {
+            Some(event) => {
+                self.waker.take();
+                Poll::Ready(Some(event))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use alloc::{sync::Arc, vec::Vec};
+use core::{
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll},
+};
+
+use futures_util::Stream;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use super::EventQueue;
+use crate::framebuffer;
+
+// bits of the first byte of a packet
+const BUTTON_LEFT: u8 = 1 << 0;
+const BUTTON_RIGHT: u8 = 1 << 1;
+const BUTTON_MIDDLE: u8 = 1 << 2;
+const ALWAYS_ONE: u8 = 1 << 3;
+const X_SIGN: u8 = 1 << 4;
+const Y_SIGN: u8 = 1 << 5;
+const X_OVERFLOW: u8 = 1 << 6;
+const Y_OVERFLOW: u8 = 1 << 7;
+
+/// Whether the mouse sends a fourth byte with the wheel movement.
+static WHEEL: AtomicBool = AtomicBool::new(false);
+
+static DECODER: Mutex<PacketDecoder> = Mutex::new(PacketDecoder {
+    bytes: [0; 4],
+    received: 0,
+});
+
+static POINTER: Mutex<Pointer> = Mutex::new(Pointer {
+    x: 0,
+    y: 0,
+    buttons: Buttons {
+        left: false,
+        right: false,
+        middle: false,
+    },
+});
+
+static SUBSCRIBERS: Mutex<Vec<Arc<EventQueue<MouseEvent>>>> = Mutex::new(Vec::new());
+
+/// State of the mouse buttons.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Buttons {
+    pub left: bool,
+    pub right: bool,
+    pub middle: bool,
+}
+
+impl Buttons {
+    /// The buttons as a bit mask, left is bit 0, right bit 1 and middle bit 2.
+    pub fn bits(self) -> u8 {
+        self.left as u8 | (self.right as u8) << 1 | (self.middle as u8) << 2
+    }
+}
+
+/// Movement and button state reported by one packet of the mouse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseEvent {
+    pub dx: i16,
+    /// Movement in screen direction, positive values move down.
+    pub dy: i16,
+    /// Positive values scroll down.
+    pub wheel: i8,
+    pub buttons: Buttons,
+    /// Pointer position on the framebuffer after this event.
+    pub x: usize,
+    pub y: usize,
+}
+
+struct PacketDecoder {
+    bytes: [u8; 4],
+    received: usize,
+}
+
+impl PacketDecoder {
+    fn add_byte(&mut self, byte: u8) -> Option<[u8; 4]> {
+        // the first byte always has bit 3 set, skip bytes until the stream is in sync
+        if self.received == 0 && byte & ALWAYS_ONE == 0 {
+            return None;
+        }
+        self.bytes[self.received] = byte;
+        self.received += 1;
+
+        let packet_size = if has_wheel() { 4 } else { 3 };
+        if self.received < packet_size {
+            return None;
+        }
+        self.received = 0;
+        Some(core::mem::take(&mut self.bytes))
+    }
+}
+
+struct Pointer {
+    x: usize,
+    y: usize,
+    buttons: Buttons,
+}
+
+/// Called by the PS/2 driver once it knows the packet format of the mouse.
+pub(super) fn set_wheel(wheel: bool) {
+    WHEEL.store(wheel, Ordering::SeqCst);
+}
+
+/// Whether the mouse has a scroll wheel.
+pub fn has_wheel() -> bool {
+    WHEEL.load(Ordering::SeqCst)
+}
+
+/// Decodes a byte received from the mouse and hands complete packets to the
+/// subscribers. Called by the mouse interrupt handler.
+pub fn handle_byte(byte: u8) -> Option<MouseEvent> {
+    let packet = without_interrupts(|| DECODER.lock().add_byte(byte))?;
+    let event = apply_packet(packet);
+    framebuffer::move_pointer(event.x, event.y);
+    without_interrupts(|| {
+        for subscriber in SUBSCRIBERS.lock().iter() {
+            subscriber.push(event);
+        }
+    });
+    Some(event)
+}
+
+fn apply_packet([flags, x, y, wheel]: [u8; 4]) -> MouseEvent {
+    let movement = |value: u8, sign: u8, overflow: u8| match flags {
+        flags if flags & overflow != 0 => 0,
+        flags if flags & sign != 0 => value as i16 - 0x100,
+        _ => value as i16,
+    };
+    let dx = movement(x, X_SIGN, X_OVERFLOW);
+    // the mouse counts upwards, the framebuffer downwards
+    let dy = -movement(y, Y_SIGN, Y_OVERFLOW);
+    // the wheel movement is a signed 4-bit value
+    let wheel = (wheel << 4) as i8 >> 4;
+    let buttons = Buttons {
+        left: flags & BUTTON_LEFT != 0,
+        right: flags & BUTTON_RIGHT != 0,
+        middle: flags & BUTTON_MIDDLE != 0,
+    };
+
+    let (width, height) = framebuffer::framebuffer_size();
+    without_interrupts(|| {
+        let mut pointer = POINTER.lock();
+        pointer.x = pointer
+            .x
+            .saturating_add_signed(dx as isize)
+            .min(width.saturating_sub(1));
+        pointer.y = pointer
+            .y
+            .saturating_add_signed(dy as isize)
+            .min(height.saturating_sub(1));
+        pointer.buttons = buttons;
+        MouseEvent {
+            dx,
+            dy,
+            wheel,
+            buttons,
+            x: pointer.x,
+            y: pointer.y,
+        }
+    })
+}
+
+/// Current pointer position on the framebuffer.
+pub fn position() -> (usize, usize) {
+    without_interrupts(|| {
+        let pointer = POINTER.lock();
+        (pointer.x, pointer.y)
+    })
+}
+
+/// Buttons that are currently held down.
+pub fn buttons() -> Buttons {
+    without_interrupts(|| POINTER.lock().buttons)
+}
+
+/// Stream of mouse events for one subscriber.
+pub struct MouseEvents {
+    subscriber: Arc<EventQueue<MouseEvent>>,
+}
+
+impl MouseEvents {
+    pub fn new() -> Self {
+        let subscriber = Arc::new(EventQueue::new());
+        without_interrupts(|| SUBSCRIBERS.lock().push(subscriber.clone()));
+        MouseEvents { subscriber }
+    }
+
+    /// Returns the next buffered event without waiting.
+    pub fn try_next(&mut self) -> Option<MouseEvent> {
+        self.subscriber.pop()
+    }
+}
+
+impl Default for MouseEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Stream for MouseEvents {
+    type Item = MouseEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<MouseEvent>> {
+        self.subscriber.poll_next(cx)
+    }
+}
+
+impl Drop for MouseEvents {
+    fn drop(&mut self) {
+        without_interrupts(|| {
+            SUBSCRIBERS
+                .lock()
+                .retain(|subscriber| !Arc::ptr_eq(subscriber, &self.subscriber))
+        });
+    }
+}
@@ -0,0 +1,407 @@
+use core::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use pc_keyboard::{KeyCode, KeyState};
+use spin::Mutex;
+use x86_64::instructions::{interrupts::without_interrupts, port::Port};
+
+use super::{keyboard, layout::ScancodeSetKind, mouse};
+use crate::time;
+
+const DATA_PORT: u16 = 0x60;
+/// Reading gives the status register, writing sends a controller command.
+const COMMAND_PORT: u16 = 0x64;
+
+const STATUS_OUTPUT_FULL: u8 = 1 << 0;
+const STATUS_INPUT_FULL: u8 = 1 << 1;
+
+// controller commands
+const READ_CONFIGURATION: u8 = 0x20;
+const WRITE_CONFIGURATION: u8 = 0x60;
+const DISABLE_SECOND_PORT: u8 = 0xa7;
+const ENABLE_SECOND_PORT: u8 = 0xa8;
+const TEST_SECOND_PORT: u8 = 0xa9;
+const TEST_CONTROLLER: u8 = 0xaa;
+const TEST_FIRST_PORT: u8 = 0xab;
+const DISABLE_FIRST_PORT: u8 = 0xad;
+const ENABLE_FIRST_PORT: u8 = 0xae;
+const WRITE_SECOND_PORT: u8 = 0xd4;
+
+const CONTROLLER_TEST_PASSED: u8 = 0x55;
+const PORT_TEST_PASSED: u8 = 0x00;
+
+// bits of the configuration byte
+const CONFIG_FIRST_PORT_INTERRUPT: u8 = 1 << 0;
+const CONFIG_SECOND_PORT_INTERRUPT: u8 = 1 << 1;
+const CONFIG_SECOND_PORT_CLOCK_DISABLED: u8 = 1 << 5;
+const CONFIG_TRANSLATION: u8 = 1 << 6;
+
+// keyboard commands and replies
+const SET_LEDS: u8 = 0xed;
+const SET_TYPEMATIC: u8 = 0xf3;
+const ENABLE_SCANNING: u8 = 0xf4;
+const ACK: u8 = 0xfa;
+const RESEND: u8 = 0xfe;
+
+// mouse commands
+const GET_DEVICE_ID: u8 = 0xf2;
+const SET_SAMPLE_RATE: u8 = 0xf3;
+const ENABLE_DATA_REPORTING: u8 = 0xf4;
+const SET_DEFAULTS: u8 = 0xf6;
+/// Device ID of a mouse that sends wheel movement in a fourth packet byte.
+const INTELLIMOUSE_ID: u8 = 3;
+
+/// How long to wait for the controller or a device before giving up.
+const TIMEOUT: Duration = Duration::from_millis(20);
+/// How often a command is repeated when the keyboard asks for it.
+const MAX_RETRIES: u8 = 3;
+
+const LED_SCROLL_LOCK: u8 = 1 << 0;
+const LED_NUM_LOCK: u8 = 1 << 1;
+const LED_CAPS_LOCK: u8 = 1 << 2;
+
+static SECOND_PORT: AtomicBool = AtomicBool::new(false);
+/// pc-keyboard does not track Scroll Lock, so it is toggled here.
+static SCROLL_LOCK: AtomicBool = AtomicBool::new(false);
+
+static KEYBOARD_COMMANDS: Mutex<CommandQueue> = Mutex::new(CommandQueue::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    Timeout,
+    ControllerSelfTestFailed(u8),
+    PortTestFailed(u8),
+    NotAcknowledged(u8),
+}
+
+/// Delay and rate of repeated key presses while a key is held down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Typematic {
+    pub delay: Duration,
+    /// Repeats per second.
+    pub rate: u32,
+}
+
+impl Default for Typematic {
+    fn default() -> Self {
+        Typematic {
+            delay: Duration::from_millis(500),
+            rate: 10,
+        }
+    }
+}
+
+impl Typematic {
+    /// Encodes the settings as the argument of the set typematic command,
+    /// using the closest supported values.
+    fn encode(self) -> u8 {
+        let delay = (self.delay.as_millis() as u64).clamp(250, 1000);
+        let delay_bits = ((delay + 125) / 250 - 1) as u8;
+
+        // the repeat period is (8 + A) * 2^B * 4.17 ms for a rate value of 0bBBAAA
+        let target_period_us = 1_000_000 / u64::from(self.rate.clamp(2, 30));
+        let period_us = |value: u8| (8 + u64::from(value & 0b111)) * (1 << (value >> 3)) * 4170;
+        let rate_bits = (0..32u8)
+            .min_by_key(|&value| period_us(value).abs_diff(target_period_us))
+            .unwrap_or(0);
+
+        delay_bits << 5 | rate_bits
+    }
+}
+
+/// The devices connected to the two ports of the controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Device {
+    Keyboard,
+    Mouse,
+}
+
+/// Initializes the i8042 controller, the keyboard on its first port and the
+/// mouse on its second port.
+///
+/// Has to run before interrupts are enabled, it polls the controller.
+pub fn init() -> Result<(), Error> {
+    send_controller_command(DISABLE_FIRST_PORT)?;
+    send_controller_command(DISABLE_SECOND_PORT)?;
+    flush_output();
+
+    let mut config = read_configuration()?;
+    config &= !(CONFIG_FIRST_PORT_INTERRUPT | CONFIG_SECOND_PORT_INTERRUPT);
+    write_configuration(config)?;
+
+    send_controller_command(TEST_CONTROLLER)?;
+    let result = read_data()?;
+    if result != CONTROLLER_TEST_PASSED {
+        return Err(Error::ControllerSelfTestFailed(result));
+    }
+    // some controllers reset themselves during the self test
+    write_configuration(config)?;
+
+    // the clock of a second port only follows the enable command if it exists
+    send_controller_command(ENABLE_SECOND_PORT)?;
+    let second_port = read_configuration()? & CONFIG_SECOND_PORT_CLOCK_DISABLED == 0;
+    send_controller_command(DISABLE_SECOND_PORT)?;
+    let second_port = second_port && test_port(TEST_SECOND_PORT).is_ok();
+    SECOND_PORT.store(second_port, Ordering::SeqCst);
+
+    test_port(TEST_FIRST_PORT)?;
+    send_controller_command(ENABLE_FIRST_PORT)?;
+    let mut interrupts = CONFIG_FIRST_PORT_INTERRUPT;
+    if second_port {
+        send_controller_command(ENABLE_SECOND_PORT)?;
+        match init_mouse() {
+            Ok(()) => interrupts |= CONFIG_SECOND_PORT_INTERRUPT,
+            Err(error) => {
+                log::warn!("PS/2 mouse initialization failed: {:?}", error);
+                send_controller_command(DISABLE_SECOND_PORT)?;
+            }
+        }
+    }
+    write_configuration(config | interrupts)?;
+
+    // without translation the keyboard's own set 2 reaches the decoder
+    if config & CONFIG_TRANSLATION == 0 {
+        keyboard::set_scancode_set(ScancodeSetKind::Set2);
+    }
+
+    send_device_command(
+        Device::Keyboard,
+        &[SET_TYPEMATIC, Typematic::default().encode()],
+    )?;
+    send_device_command(Device::Keyboard, &[SET_LEDS, LED_NUM_LOCK])?;
+    send_device_command(Device::Keyboard, &[ENABLE_SCANNING])
+}
+
+fn init_mouse() -> Result<(), Error> {
+    send_device_command(Device::Mouse, &[SET_DEFAULTS])?;
+    // an IntelliMouse turns on the wheel after this sequence of sample rates
+    for rate in [200, 100, 80] {
+        send_device_command(Device::Mouse, &[SET_SAMPLE_RATE, rate])?;
+    }
+    send_device_command(Device::Mouse, &[GET_DEVICE_ID])?;
+    mouse::set_wheel(read_data()? == INTELLIMOUSE_ID);
+    send_device_command(Device::Mouse, &[ENABLE_DATA_REPORTING])
+}
+
+/// Whether the controller has a second port, which usually has a mouse.
+pub fn has_second_port() -> bool {
+    SECOND_PORT.load(Ordering::SeqCst)
+}
+
+/// Handles a byte received from the keyboard. Called by the keyboard
+/// interrupt handler.
+pub fn handle_keyboard_byte(byte: u8) {
+    if without_interrupts(|| KEYBOARD_COMMANDS.lock().handle_reply(byte)) {
+        return;
+    }
+    let Some(event) = keyboard::handle_scancode(byte) else {
+        return;
+    };
+    if event.state != KeyState::Down {
+        return;
+    }
+    let scroll_lock = match event.code {
+        KeyCode::ScrollLock => !SCROLL_LOCK.fetch_xor(true, Ordering::SeqCst),
+        KeyCode::CapsLock | KeyCode::NumpadLock => SCROLL_LOCK.load(Ordering::SeqCst),
+        _ => return,
+    };
+    let mut leds = 0;
+    if scroll_lock {
+        leds |= LED_SCROLL_LOCK;
+    }
+    if event.modifiers.num_lock {
+        leds |= LED_NUM_LOCK;
+    }
+    if event.modifiers.caps_lock {
+        leds |= LED_CAPS_LOCK;
+    }
+    without_interrupts(|| KEYBOARD_COMMANDS.lock().set_leds(leds));
+}
+
+/// Changes the key repeat delay and rate of the keyboard.
+pub fn set_typematic(typematic: Typematic) {
+    without_interrupts(|| KEYBOARD_COMMANDS.lock().set_typematic(typematic.encode()));
+}
+
+/// Commands sent to the keyboard while interrupts are enabled. The keyboard
+/// acknowledges every byte, the replies arrive through the interrupt handler.
+struct CommandQueue {
+    sending: [u8; 2],
+    length: usize,
+    position: usize,
+    retries: u8,
+    pending_leds: Option<u8>,
+    pending_typematic: Option<u8>,
+}
+
+impl CommandQueue {
+    const fn new() -> Self {
+        CommandQueue {
+            sending: [0; 2],
+            length: 0,
+            position: 0,
+            retries: 0,
+            pending_leds: None,
+            pending_typematic: None,
+        }
+    }
+
+    fn is_busy(&self) -> bool {
+        self.position < self.length
+    }
+
+    fn set_leds(&mut self, leds: u8) {
+        self.pending_leds = Some(leds);
+        self.send_next();
+    }
+
+    fn set_typematic(&mut self, value: u8) {
+        self.pending_typematic = Some(value);
+        self.send_next();
+    }
+
+    fn send_next(&mut self) {
+        if self.is_busy() {
+            return;
+        }
+        let command = if let Some(value) = self.pending_typematic.take() {
+            [SET_TYPEMATIC, value]
+        } else if let Some(leds) = self.pending_leds.take() {
+            [SET_LEDS, leds]
+        } else {
+            return;
+        };
+        self.sending = command;
+        self.length = command.len();
+        self.position = 0;
+        self.retries = 0;
+        self.write_current();
+    }
+
+    fn write_current(&self) {
+        if write_data(self.sending[self.position]).is_err() {
+            log::warn!("PS/2 keyboard does not accept commands");
+        }
+    }
+
+    /// Returns whether `byte` was a reply to a command rather than a scancode.
+    fn handle_reply(&mut self, byte: u8) -> bool {
+        if !self.is_busy() {
+            return false;
+        }
+        match byte {
+            ACK => {
+                self.position += 1;
+                self.retries = 0;
+                if self.is_busy() {
+                    self.write_current();
+                } else {
+                    self.send_next();
+                }
+            }
+            RESEND if self.retries < MAX_RETRIES => {
+                self.retries += 1;
+                self.write_current();
+            }
+            RESEND => {
+                self.position = self.length;
+                self.send_next();
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+fn status() -> u8 {
+    unsafe { Port::<u8>::new(COMMAND_PORT).read() }
+}
+
+fn wait_for(condition: impl Fn(u8) -> bool) -> Result<(), Error> {
+    let deadline = time::uptime() + TIMEOUT;
+    while !condition(status()) {
+        if time::uptime() > deadline {
+            return Err(Error::Timeout);
+        }
+        core::hint::spin_loop();
+    }
+    Ok(())
+}
+
+fn send_controller_command(command: u8) -> Result<(), Error> {
+    wait_for(|status| status & STATUS_INPUT_FULL == 0)?;
+    unsafe { Port::new(COMMAND_PORT).write(command) };
+    Ok(())
+}
+
+fn write_data(byte: u8) -> Result<(), Error> {
+    wait_for(|status| status & STATUS_INPUT_FULL == 0)?;
+    unsafe { Port::new(DATA_PORT).write(byte) };
+    Ok(())
+}
+
+fn read_data() -> Result<u8, Error> {
+    wait_for(|status| status & STATUS_OUTPUT_FULL != 0)?;
+    Ok(unsafe { Port::new(DATA_PORT).read() })
+}
+
+fn flush_output() {
+    while status() & STATUS_OUTPUT_FULL != 0 {
+        unsafe { Port::<u8>::new(DATA_PORT).read() };
+    }
+}
+
+fn read_configuration() -> Result<u8, Error> {
+    send_controller_command(READ_CONFIGURATION)?;
+    read_data()
+}
+
+fn write_configuration(config: u8) -> Result<(), Error> {
+    send_controller_command(WRITE_CONFIGURATION)?;
+    write_data(config)
+}
+
+fn test_port(command: u8) -> Result<(), Error> {
+    send_controller_command(command)?;
+    match read_data()? {
+        PORT_TEST_PASSED => Ok(()),
+        result => Err(Error::PortTestFailed(result)),
+    }
+}
+
+/// Sends a command to a device by polling, waiting for each byte to be
+/// acknowledged.
+fn send_device_command(device: Device, bytes: &[u8]) -> Result<(), Error> {
+    for &byte in bytes {
+        let mut retries = 0;
+        loop {
+            if device == Device::Mouse {
+                send_controller_command(WRITE_SECOND_PORT)?;
+            }
+            write_data(byte)?;
+            match read_data()? {
+                ACK => break,
+                RESEND if retries < MAX_RETRIES => retries += 1,
+                reply => return Err(Error::NotAcknowledged(reply)),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test_case]
+fn test_typematic_encoding() {
+    let fastest = Typematic {
+        delay: Duration::from_millis(250),
+        rate: 30,
+    };
+    assert_eq!(fastest.encode(), 0x00);
+    let slowest = Typematic {
+        delay: Duration::from_secs(1),
+        rate: 2,
+    };
+    assert_eq!(slowest.encode(), 0x7f);
+    assert_eq!(Typematic::default().encode(), 0b01_01100);
+}
@@ -0,0 +1,176 @@
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_util::{Stream, StreamExt};
+use pc_keyboard::{DecodedKey, KeyCode};
+
+use crate::serial::SerialInput;
+
+const ESCAPE: u8 = 0x1b;
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7f;
+
+/// Keys typed on a terminal connected to the serial port.
+///
+/// Translates the bytes a terminal sends into the keys the PS/2 keyboard
+/// would produce, including the escape sequences of cursor keys. A single
+/// Escape key press is only reported once the next byte arrives.
+pub struct TerminalKeys {
+    bytes: SerialInput,
+    decoder: TerminalDecoder,
+}
+
+impl TerminalKeys {
+    pub fn new() -> Self {
+        TerminalKeys {
+            bytes: SerialInput::new(),
+            decoder: TerminalDecoder::new(),
+        }
+    }
+}
+
+impl Default for TerminalKeys {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Stream for TerminalKeys {
+    type Item = DecodedKey;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<DecodedKey>> {
+        loop {
+            if let Some(key) = self.decoder.pending.take() {
+                return Poll::Ready(Some(key));
+            }
+            let Some(byte) = core::task::ready!(self.bytes.poll_next_unpin(cx)) else {
+                return Poll::Ready(None);
+            };
+            if let Some(key) = self.decoder.add_byte(byte) {
+                return Poll::Ready(Some(key));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    /// After an escape byte.
+    Escape,
+    /// Inside a control sequence `ESC [ <parameter> <final byte>`.
+    ControlSequence {
+        parameter: u8,
+    },
+}
+
+/// Turns terminal bytes into keys.
+struct TerminalDecoder {
+    state: State,
+    after_carriage_return: bool,
+    /// A second key produced by the last byte.
+    pending: Option<DecodedKey>,
+}
+
+impl TerminalDecoder {
+    const fn new() -> Self {
+        TerminalDecoder {
+            state: State::Ground,
+            after_carriage_return: false,
+            pending: None,
+        }
+    }
+
+    /// Returns the key finished by `byte`, if any. A second key is left in
+    /// `pending` and has to be taken before the next byte is added.
+    fn add_byte(&mut self, byte: u8) -> Option<DecodedKey> {
+        let after_carriage_return = core::mem::replace(&mut self.after_carriage_return, false);
+        match self.state {
+            State::Ground => match byte {
+                ESCAPE => {
+                    self.state = State::Escape;
+                    None
+                }
+                b'\r' => {
+                    self.after_carriage_return = true;
+                    Some(DecodedKey::Unicode('\n'))
+                }
+                // terminals send either \r, \n or \r\n for Enter
+                b'\n' if after_carriage_return => None,
+                BACKSPACE | DELETE => Some(DecodedKey::Unicode('\x08')),
+                byte if byte.is_ascii() => Some(DecodedKey::Unicode(byte as char)),
+                // non-ASCII characters are not supported
+                _ => None,
+            },
+            State::Escape => {
+                if byte == b'[' {
+                    self.state = State::ControlSequence { parameter: 0 };
+                    return None;
+                }
+                self.state = State::Ground;
+                self.pending = self.add_byte(byte);
+                Some(DecodedKey::RawKey(KeyCode::Escape))
+            }
+            State::ControlSequence { parameter } => {
+                if byte.is_ascii_digit() {
+                    let parameter = parameter.saturating_mul(10).saturating_add(byte - b'0');
+                    self.state = State::ControlSequence { parameter };
+                    return None;
+                }
+                if !(0x40..=0x7e).contains(&byte) {
+                    // separators and modifier parameters are ignored
+                    return None;
+                }
+                self.state = State::Ground;
+                let code = match (byte, parameter) {
+                    (b'A', _) => KeyCode::ArrowUp,
+                    (b'B', _) => KeyCode::ArrowDown,
+                    (b'C', _) => KeyCode::ArrowRight,
+                    (b'D', _) => KeyCode::ArrowLeft,
+                    (b'H', _) | (b'~', 1 | 7) => KeyCode::Home,
+                    (b'F', _) | (b'~', 4 | 8) => KeyCode::End,
+                    (b'~', 2) => KeyCode::Insert,
+                    (b'~', 3) => KeyCode::Delete,
+                    (b'~', 5) => KeyCode::PageUp,
+                    (b'~', 6) => KeyCode::PageDown,
+                    _ => return None,
+                };
+                Some(DecodedKey::RawKey(code))
+            }
+        }
+    }
+}
+
+#[test_case]
+fn test_terminal_decoder() {
+    let mut decoder = TerminalDecoder::new();
+    let mut decode = |bytes: &[u8]| {
+        let mut keys = alloc::vec::Vec::new();
+        for &byte in bytes {
+            keys.extend(decoder.add_byte(byte));
+            keys.extend(decoder.pending.take());
+        }
+        keys
+    };
+    assert_eq!(
+        decode(b"a\r\n"),
+        [DecodedKey::Unicode('a'), DecodedKey::Unicode('\n')]
+    );
+    assert_eq!(
+        decode(b"\x1b[A\x1b[3~\x7f"),
+        [
+            DecodedKey::RawKey(KeyCode::ArrowUp),
+            DecodedKey::RawKey(KeyCode::Delete),
+            DecodedKey::Unicode('\x08'),
+        ]
+    );
+    assert_eq!(
+        decode(b"\x1bx"),
+        [
+            DecodedKey::RawKey(KeyCode::Escape),
+            DecodedKey::Unicode('x')
+        ]
+    );
+}
@@ -1,13 +1,14 @@
+use alloc::boxed::Box;
 use lazy_static::lazy_static;
-use pc_keyboard::{Keyboard, ScancodeSet1, layouts};
 use pic8259::ChainedPics;
 use spin::Mutex;
 use x86_64::registers::control::Cr2;
 use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode};
 
-use crate::task::keyboard::add_scancode;
+use crate::input::{mouse, ps2};
+use crate::smp::apic;
 use crate::wasm_game;
-use crate::{gdt, hlt_loop, println};
+use crate::{console, framebuffer, gdt, hlt_loop, println, serial, time};
 use x86_64::instructions::port::Port;
 
 extern "x86-interrupt" fn page_fault_handler(
@@ -22,35 +23,35 @@ extern "x86-interrupt" fn page_fault_handler(
 }
 
 lazy_static! {
-    static ref INTERRUPT_DESCRIPTOR_TABLE: InterruptDescriptorTable = {
-        let mut interrupt_descriptor_table = InterruptDescriptorTable::new();
-        interrupt_descriptor_table
-            .breakpoint
-            .set_handler_fn(breakpoint_handler);
-        unsafe {
-            interrupt_descriptor_table
-                .double_fault
-                .set_handler_fn(double_fault_handler)
-                .set_stack_index(gdt::DOUBLE_FAULT_IST_INDEX);
-        }
-        interrupt_descriptor_table[u8::from(InterruptIndex::Timer)]
-            .set_handler_fn(timer_interrupt_handler);
-        interrupt_descriptor_table[u8::from(InterruptIndex::Keyboard)]
-            .set_handler_fn(keyboard_interrupt_handler);
-        interrupt_descriptor_table
-            .page_fault
-            .set_handler_fn(page_fault_handler);
-        interrupt_descriptor_table
-    };
+    static ref INTERRUPT_DESCRIPTOR_TABLE: InterruptDescriptorTable =
+        new_interrupt_descriptor_table();
 }
 
-lazy_static! {
-    static ref KEYBOARD: Mutex<Keyboard<layouts::Us104Key, ScancodeSet1>> =
-        Mutex::new(Keyboard::new(
-            ScancodeSet1::new(),
-            layouts::Us104Key,
-            pc_keyboard::HandleControl::Ignore
-        ));
+fn new_interrupt_descriptor_table() -> InterruptDescriptorTable {
+    let mut interrupt_descriptor_table = InterruptDescriptorTable::new();
+    interrupt_descriptor_table
+        .breakpoint
+        .set_handler_fn(breakpoint_handler);
+    unsafe {
+        interrupt_descriptor_table
+            .double_fault
+            .set_handler_fn(double_fault_handler)
+            .set_stack_index(gdt::DOUBLE_FAULT_IST_INDEX);
+    }
+    interrupt_descriptor_table[u8::from(InterruptIndex::Timer)]
+        .set_handler_fn(timer_interrupt_handler);
+    interrupt_descriptor_table[u8::from(InterruptIndex::Keyboard)]
+        .set_handler_fn(keyboard_interrupt_handler);
+    interrupt_descriptor_table[u8::from(InterruptIndex::Serial)]
+        .set_handler_fn(serial_interrupt_handler);
+    interrupt_descriptor_table[u8::from(InterruptIndex::Mouse)]
+        .set_handler_fn(mouse_interrupt_handler);
+    interrupt_descriptor_table
+        .page_fault
+        .set_handler_fn(page_fault_handler);
+    interrupt_descriptor_table[apic::WAKEUP_VECTOR].set_handler_fn(wakeup_interrupt_handler);
+    interrupt_descriptor_table[apic::SPURIOUS_VECTOR].set_handler_fn(spurious_interrupt_handler);
+    interrupt_descriptor_table
 }
 
 const PIC_1_OFFSET: u8 = 32;
@@ -64,8 +65,14 @@ static PICS: spin::Mutex<ChainedPics> =
 enum InterruptIndex {
     Timer = PIC_1_OFFSET,
     Keyboard,
+    /// COM1
+    Serial = PIC_1_OFFSET + 4,
+    Mouse = PIC_2_OFFSET + 4,
 }
 
+/// IRQ line of the second PIC on the first one.
+const CASCADE_IRQ: u8 = 2;
+
 impl From<InterruptIndex> for u8 {
     fn from(value: InterruptIndex) -> Self {
         value as u8
@@ -75,11 +82,25 @@ impl From<InterruptIndex> for u8 {
 pub fn initialize_interrupt_handling() {
     INTERRUPT_DESCRIPTOR_TABLE.load();
     unsafe {
-        PICS.lock().initialize();
+        let mut pics = PICS.lock();
+        pics.initialize();
+        let [primary_mask, secondary_mask] = pics.read_masks();
+        let serial_irq = u8::from(InterruptIndex::Serial) - PIC_1_OFFSET;
+        let mouse_irq = u8::from(InterruptIndex::Mouse) - PIC_2_OFFSET;
+        pics.write_masks(
+            primary_mask & !(1 << CASCADE_IRQ | 1 << serial_irq),
+            secondary_mask & !(1 << mouse_irq),
+        );
     }
     x86_64::instructions::interrupts::enable();
 }
 
+/// Loads an IDT of its own on an application processor. Only the bootstrap
+/// processor receives interrupts from the PIC.
+pub fn initialize_for_application_processor() {
+    Box::leak(Box::new(new_interrupt_descriptor_table())).load();
+}
+
 extern "x86-interrupt" fn breakpoint_handler(frame: InterruptStackFrame) {
     println!("EXCEPTION: BREAKPOINT HIT\n{:#?}", frame)
 }
@@ -94,10 +115,15 @@ extern "x86-interrupt" fn double_fault_handler(frame: InterruptStackFrame, _: u6
 }
 
 extern "x86-interrupt" fn timer_interrupt_handler(_: InterruptStackFrame) {
-    if wasm_game::is_game_running() {
-        wasm_game::process_pending_keys();
-        wasm_game::update_game();
-        wasm_game::render_game();
+    time::tick();
+    framebuffer::refresh();
+
+    if wasm_game::is_game_shown() {
+        console::with_console(wasm_game::game_console(), || {
+            wasm_game::process_pending_keys();
+            wasm_game::update_game();
+            wasm_game::render_game();
+        });
     }
 
     unsafe {
@@ -107,11 +133,10 @@ extern "x86-interrupt" fn timer_interrupt_handler(_: InterruptStackFrame) {
 }
 
 extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStackFrame) {
-    //let keyboard = KEYBOARD.lock();
     let mut ps2_port: Port<u8> = Port::new(0x60);
-    let scancode = unsafe { ps2_port.read() };
+    let byte = unsafe { ps2_port.read() };
 
-    add_scancode(scancode);
+    ps2::handle_keyboard_byte(byte);
 
     unsafe {
         PICS.lock()
@@ -119,6 +144,35 @@ extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStac
     }
 }
 
+extern "x86-interrupt" fn serial_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    serial::handle_interrupt();
+
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(u8::from(InterruptIndex::Serial));
+    }
+}
+
+extern "x86-interrupt" fn mouse_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    let mut ps2_port: Port<u8> = Port::new(0x60);
+    let byte = unsafe { ps2_port.read() };
+
+    mouse::handle_byte(byte);
+
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(u8::from(InterruptIndex::Mouse));
+    }
+}
+
+// only interrupts the halt instruction of an idle executor, see `RunQueue::unpark`
+extern "x86-interrupt" fn wakeup_interrupt_handler(_: InterruptStackFrame) {
+    apic::end_of_interrupt();
+}
+
+// spurious interrupts must not be acknowledged
+extern "x86-interrupt" fn spurious_interrupt_handler(_: InterruptStackFrame) {}
+
 #[cfg(test)]
 mod tests {
     #[test_case]
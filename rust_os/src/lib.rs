@@ -7,29 +7,41 @@
 
 use bootloader_api::BootInfo;
 use core::panic::PanicInfo;
-use x86_64::{VirtAddr, instructions::hlt};
+use x86_64::{PhysAddr, VirtAddr, instructions::hlt};
 
 pub mod allocator;
+pub mod config;
+pub mod console;
 pub mod entry_point;
 pub mod filesystem;
 pub mod framebuffer;
 pub mod gdt;
+pub mod input;
 pub mod interrupts;
+pub mod logger;
 pub mod memory;
 pub mod qemu;
 pub mod serial;
+pub mod smp;
 pub mod task;
+pub mod time;
 pub mod wasm_game;
 
 extern crate alloc;
 
 pub fn init_kernel(boot_info: &'static mut BootInfo) {
+    smp::percpu::init_bootstrap_processor();
     gdt::initialize_global_descriptor_table();
     let framebuffer = boot_info
         .framebuffer
         .as_mut()
         .expect("Could not get framebuffer from boot info");
     framebuffer::init_framebuffer_writer(framebuffer);
+    time::init();
+    logger::init();
+    if let Err(error) = input::ps2::init() {
+        log::warn!("PS/2 controller initialization failed: {:?}", error);
+    }
     interrupts::initialize_interrupt_handling();
 
     let phys_mem_offset = VirtAddr::new(
@@ -43,6 +55,11 @@ pub fn init_kernel(boot_info: &'static mut BootInfo) {
         unsafe { memory::BootInfoFrameAllocator::init(&boot_info.memory_regions) };
 
     allocator::init_heap(&mut mapper, &mut frame_allocator).expect("heap initialization failed");
+    framebuffer::init_text_grid();
+    serial::init();
+    memory::install(mapper, frame_allocator);
+
+    smp::init(boot_info.rsdp_addr.into_option().map(PhysAddr::new));
 }
 
 pub trait Testable {
@@ -0,0 +1,284 @@
+use alloc::{string::String, vec::Vec};
+use core::{
+    fmt::{self, Write},
+    str::FromStr,
+    sync::atomic::{AtomicU8, Ordering},
+    time::Duration,
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use crate::{config, console, framebuffer::with_framebuffer_writer, serial, time};
+
+/// Size of the in-memory log that `dmesg` shows.
+const RING_BUFFER_SIZE: usize = 16 * 1024;
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+
+static LOGGER: KernelLogger = KernelLogger;
+static RING_BUFFER: Mutex<RingBuffer> = Mutex::new(RingBuffer::new());
+static FILTERS: Mutex<Filters> = Mutex::new(Filters {
+    default: DEFAULT_LEVEL,
+    modules: Vec::new(),
+});
+static SINK: AtomicU8 = AtomicU8::new(Sink::Serial as u8);
+
+/// Where log records are written to, besides the ring buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Sink {
+    None,
+    Serial,
+    Framebuffer,
+    Both,
+}
+
+impl Sink {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Sink::Serial,
+            2 => Sink::Framebuffer,
+            3 => Sink::Both,
+            _ => Sink::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownSink;
+
+impl FromStr for Sink {
+    type Err = UnknownSink;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "none" => Ok(Sink::None),
+            "serial" => Ok(Sink::Serial),
+            "framebuffer" => Ok(Sink::Framebuffer),
+            "both" => Ok(Sink::Both),
+            _ => Err(UnknownSink),
+        }
+    }
+}
+
+/// Maximum levels for log targets.
+#[derive(Debug, PartialEq, Eq)]
+struct Filters {
+    default: LevelFilter,
+    /// Module paths with their level, a module also covers its submodules.
+    modules: Vec<(String, LevelFilter)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct InvalidFilter;
+
+impl Filters {
+    /// Parses a comma separated list of `level` and `module=level` entries,
+    /// e.g. `info,rust_os::input=debug`.
+    fn parse(spec: &str) -> Result<Filters, InvalidFilter> {
+        let mut filters = Filters {
+            default: DEFAULT_LEVEL,
+            modules: Vec::new(),
+        };
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            match entry.split_once('=') {
+                Some((module, level)) => {
+                    let level = level.trim().parse().map_err(|_| InvalidFilter)?;
+                    filters.modules.push((String::from(module.trim()), level));
+                }
+                None => filters.default = entry.parse().map_err(|_| InvalidFilter)?,
+            }
+        }
+        Ok(filters)
+    }
+
+    fn level(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                target
+                    .strip_prefix(module.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+/// The last [`RING_BUFFER_SIZE`] bytes of log output.
+struct RingBuffer {
+    bytes: [u8; RING_BUFFER_SIZE],
+    start: usize,
+    length: usize,
+}
+
+impl RingBuffer {
+    const fn new() -> Self {
+        RingBuffer {
+            bytes: [0; RING_BUFFER_SIZE],
+            start: 0,
+            length: 0,
+        }
+    }
+
+    fn push(&mut self, byte: u8) {
+        let end = (self.start + self.length) % RING_BUFFER_SIZE;
+        self.bytes[end] = byte;
+        if self.length == RING_BUFFER_SIZE {
+            self.start = (self.start + 1) % RING_BUFFER_SIZE;
+        } else {
+            self.length += 1;
+        }
+    }
+
+    fn contents(&self) -> String {
+        let bytes: Vec<u8> = (0..self.length)
+            .map(|i| self.bytes[(self.start + i) % RING_BUFFER_SIZE])
+            .collect();
+        let mut text = String::from_utf8_lossy(&bytes).into_owned();
+        if self.length == RING_BUFFER_SIZE {
+            // the oldest line was partially overwritten
+            let first_line = text.find('\n').map_or(text.len(), |end| end + 1);
+            text.drain(..first_line);
+        }
+        text
+    }
+}
+
+impl Write for RingBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        s.bytes().for_each(|byte| self.push(byte));
+        Ok(())
+    }
+}
+
+/// A record formatted as a line with the time since boot.
+struct Line<'a> {
+    record: &'a Record<'a>,
+    uptime: Duration,
+}
+
+impl fmt::Display for Line<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "[{:>5}.{:06}] {:<5} {}: {}",
+            self.uptime.as_secs(),
+            self.uptime.subsec_micros(),
+            self.record.level(),
+            self.record.target(),
+            self.record.args()
+        )
+    }
+}
+
+struct KernelLogger;
+
+impl Log for KernelLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= without_interrupts(|| FILTERS.lock().level(metadata.target()))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = Line {
+            record,
+            uptime: time::uptime(),
+        };
+
+        without_interrupts(|| {
+            let _ = write!(RING_BUFFER.lock(), "{}", line);
+        });
+        let sink = Sink::from_u8(SINK.load(Ordering::Relaxed));
+        if matches!(sink, Sink::Serial | Sink::Both) {
+            serial::_serial_print(format_args!("{}", line));
+        }
+        if matches!(sink, Sink::Framebuffer | Sink::Both) {
+            console::with_console(console::LOG_CONSOLE, || {
+                with_framebuffer_writer(|writer| {
+                    let _ = write!(writer, "{}", line);
+                })
+            });
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installs the kernel logger. Records go to the ring buffer and the serial
+/// port until [`load_config`] is called.
+pub fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(DEFAULT_LEVEL);
+    }
+}
+
+/// Applies the `LOG_LEVEL` and `LOG_SINK` settings of the configuration.
+pub fn load_config() {
+    if let Some(spec) = config::get("LOG_LEVEL") {
+        match Filters::parse(spec) {
+            Ok(filters) => set_filters(filters),
+            Err(InvalidFilter) => log::warn!("invalid LOG_LEVEL '{}'", spec),
+        }
+    }
+    if let Some(name) = config::get("LOG_SINK") {
+        match name.parse() {
+            Ok(sink) => set_sink(sink),
+            Err(UnknownSink) => log::warn!("unknown LOG_SINK '{}'", name),
+        }
+    }
+}
+
+fn set_filters(filters: Filters) {
+    log::set_max_level(filters.max_level());
+    without_interrupts(|| *FILTERS.lock() = filters);
+}
+
+/// Changes where log records are written to.
+pub fn set_sink(sink: Sink) {
+    SINK.store(sink as u8, Ordering::Relaxed);
+}
+
+/// The log records that are still in the ring buffer.
+pub fn contents() -> String {
+    without_interrupts(|| RING_BUFFER.lock().contents())
+}
+
+#[test_case]
+fn test_parse_filters() {
+    let filters = Filters::parse("warn, rust_os::input=debug,rust_os::input::ps2=trace").unwrap();
+    assert_eq!(filters.level("rust_os::smp"), LevelFilter::Warn);
+    assert_eq!(
+        filters.level("rust_os::input::keyboard"),
+        LevelFilter::Debug
+    );
+    assert_eq!(filters.level("rust_os::input::ps2"), LevelFilter::Trace);
+    assert_eq!(filters.level("rust_os::inputs"), LevelFilter::Warn);
+    assert_eq!(filters.max_level(), LevelFilter::Trace);
+    assert_eq!(Filters::parse("loud"), Err(InvalidFilter));
+}
+
+#[test_case]
+fn test_ring_buffer_drops_oldest_lines() {
+    let mut buffer = RingBuffer::new();
+    for i in 0..RING_BUFFER_SIZE / 8 + 1 {
+        let _ = writeln!(buffer, "line {:02}", i % 100);
+    }
+    let contents = buffer.contents();
+    assert!(contents.len() < RING_BUFFER_SIZE);
+    assert!(contents.lines().all(|line| line.len() == 7));
+    assert!(contents.ends_with(&alloc::format!(
+        "line {:02}\n",
+        (RING_BUFFER_SIZE / 8) % 100
+    )));
+}
@@ -6,8 +6,9 @@
 use bootloader_api::BootInfo;
 use core::panic::PanicInfo;
 use core::slice;
-use rust_os::task::executor::Executor;
-use rust_os::task::{Task, shell};
+use rust_os::console;
+use rust_os::task::executor::{self, Executor};
+use rust_os::task::shell;
 use rust_os::{default_entry_point, hlt_loop, init_kernel};
 
 extern crate alloc;
@@ -34,8 +35,22 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
     #[cfg(not(test))]
     {
         rust_os::filesystem::init_filesystem(ramdisk).expect("Failed to initialize filesystem");
-        let mut executor = Executor::new();
-        executor.spawn(Task::new(shell::run()));
+        rust_os::config::load().expect("Failed to load configuration");
+        rust_os::logger::load_config();
+        rust_os::input::keyboard::load_config();
+        rust_os::framebuffer::load_config();
+        rust_os::serial::set_console_mirror(
+            rust_os::config::get_parsed("SERIAL_CONSOLE").unwrap_or(true),
+        );
+
+        let queue_capacity = rust_os::config::get_parsed("EXECUTOR_QUEUE_CAPACITY")
+            .unwrap_or(executor::DEFAULT_QUEUE_CAPACITY);
+        let mut executor = Executor::with_capacity(queue_capacity);
+        executor.spawn(console::run());
+        // a shell on every console but the log console
+        for tty in console::SHELL_CONSOLE..console::CONSOLES {
+            executor.spawn(console::on_console(tty, shell::run()));
+        }
         executor.run();
     }
 
@@ -1,10 +1,23 @@
 use bootloader_api::info::{MemoryRegionKind, MemoryRegions};
+use spin::Mutex;
 use x86_64::{
     PhysAddr, VirtAddr,
+    instructions::interrupts::without_interrupts,
     registers::control::Cr3,
-    structures::paging::{FrameAllocator, OffsetPageTable, PageTable, PhysFrame, Size4KiB},
+    structures::paging::{
+        FrameAllocator, Mapper, OffsetPageTable, Page, PageTable, PageTableFlags, PhysFrame,
+        Size4KiB, Translate, mapper::MapToError,
+    },
 };
 
+/// Frames below this address are kept free for code that has to run in real
+/// mode, such as the startup code of application processors.
+const LOW_MEMORY_END: u64 = 0x10_0000;
+
+// random not used addresses, like the heap
+const KERNEL_STACKS_START: u64 = 0x_6969_1000_0000;
+const MMIO_START: u64 = 0x_6969_2000_0000;
+
 pub struct BootInfoFrameAllocator {
     memory_regions: &'static MemoryRegions,
     next: usize,
@@ -19,11 +32,16 @@ pub unsafe fn init(physical_memory_offset: VirtAddr) -> OffsetPageTable<'static>
 
 impl BootInfoFrameAllocator {
     fn usable_frames(&self) -> impl Iterator<Item = PhysFrame> {
+        self.usable_addresses()
+            .filter(|addr| *addr >= LOW_MEMORY_END)
+            .map(|addr| PhysFrame::containing_address(PhysAddr::new(addr)))
+    }
+
+    fn usable_addresses(&self) -> impl Iterator<Item = u64> {
         let regions = self.memory_regions.iter();
         let usable_regions = regions.filter(|r| r.kind == MemoryRegionKind::Usable);
         let addr_ranges = usable_regions.map(|r| r.start..r.end);
-        let frame_addresses = addr_ranges.flat_map(|r| r.step_by(4096));
-        frame_addresses.map(|addr| PhysFrame::containing_address(PhysAddr::new(addr)))
+        addr_ranges.flat_map(|r| r.step_by(4096))
     }
 
     pub unsafe fn init(memory_regions: &'static MemoryRegions) -> Self {
@@ -32,6 +50,15 @@ impl BootInfoFrameAllocator {
             next: 0,
         }
     }
+
+    /// Returns a usable frame below 1 MiB. These frames are never handed out
+    /// by [`FrameAllocator::allocate_frame`].
+    pub fn low_memory_frame(&self) -> Option<PhysFrame> {
+        self.usable_addresses()
+            .filter(|addr| *addr != 0 && *addr < LOW_MEMORY_END)
+            .map(|addr| PhysFrame::containing_address(PhysAddr::new(addr)))
+            .next()
+    }
 }
 
 unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
@@ -51,3 +78,115 @@ unsafe fn active_level_4_table(physical_memory_offset: VirtAddr) -> &'static mut
 
     unsafe { &mut *page_table_ptr }
 }
+
+/// Page tables and frame allocator used after the heap is set up.
+pub struct KernelMemory {
+    pub mapper: OffsetPageTable<'static>,
+    pub frame_allocator: BootInfoFrameAllocator,
+    next_stack: u64,
+    next_mmio: u64,
+}
+
+static KERNEL_MEMORY: Mutex<Option<KernelMemory>> = Mutex::new(None);
+
+/// Makes the page tables and frame allocator available to the rest of the kernel.
+pub fn install(mapper: OffsetPageTable<'static>, frame_allocator: BootInfoFrameAllocator) {
+    let memory = KernelMemory {
+        mapper,
+        frame_allocator,
+        next_stack: KERNEL_STACKS_START,
+        next_mmio: MMIO_START,
+    };
+    without_interrupts(|| *KERNEL_MEMORY.lock() = Some(memory));
+}
+
+/// Runs `f` with the kernel's page tables and frame allocator.
+///
+/// Panics if [`install`] was not called yet.
+pub fn with_kernel_memory<R>(f: impl FnOnce(&mut KernelMemory) -> R) -> R {
+    without_interrupts(|| {
+        let mut memory = KERNEL_MEMORY.lock();
+        f(memory.as_mut().expect("kernel memory not installed"))
+    })
+}
+
+/// Virtual address at which the bootloader mapped all of physical memory.
+pub fn physical_memory_offset() -> VirtAddr {
+    with_kernel_memory(|memory| memory.mapper.phys_offset())
+}
+
+/// Translates a physical address into the bootloader's mapping of physical memory.
+pub fn physical_to_virtual(address: PhysAddr) -> VirtAddr {
+    physical_memory_offset() + address.as_u64()
+}
+
+impl KernelMemory {
+    fn map_pages(
+        &mut self,
+        start: VirtAddr,
+        frames: impl Iterator<Item = Option<PhysFrame>>,
+        flags: PageTableFlags,
+    ) -> Result<(), MapToError<Size4KiB>> {
+        let mut page = Page::<Size4KiB>::containing_address(start);
+        for frame in frames {
+            let frame = frame.ok_or(MapToError::FrameAllocationFailed)?;
+            unsafe {
+                self.mapper
+                    .map_to(page, frame, flags, &mut self.frame_allocator)?
+                    .flush()
+            };
+            page += 1;
+        }
+        Ok(())
+    }
+
+    /// Maps a new stack of `pages` pages with an unmapped guard page below it
+    /// and returns the address of its top.
+    pub fn allocate_stack(&mut self, pages: u64) -> Result<VirtAddr, MapToError<Size4KiB>> {
+        let guard_page = VirtAddr::new(self.next_stack);
+        let bottom = guard_page + 4096u64;
+        self.next_stack = bottom.as_u64() + pages * 4096;
+
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE;
+        let frames: alloc::vec::Vec<_> = (0..pages)
+            .map(|_| self.frame_allocator.allocate_frame())
+            .collect();
+        self.map_pages(bottom, frames.into_iter(), flags)?;
+        Ok(bottom + pages * 4096)
+    }
+
+    /// Maps `size` bytes of device memory starting at `address` as uncached.
+    pub fn map_mmio(
+        &mut self,
+        address: PhysAddr,
+        size: u64,
+    ) -> Result<VirtAddr, MapToError<Size4KiB>> {
+        let first_frame = PhysFrame::<Size4KiB>::containing_address(address);
+        let last_frame = PhysFrame::<Size4KiB>::containing_address(address + (size - 1));
+        let start = VirtAddr::new(self.next_mmio);
+        let frames = PhysFrame::range_inclusive(first_frame, last_frame);
+        self.next_mmio += frames.count() as u64 * 4096;
+
+        let flags = PageTableFlags::PRESENT
+            | PageTableFlags::WRITABLE
+            | PageTableFlags::NO_CACHE
+            | PageTableFlags::WRITE_THROUGH
+            | PageTableFlags::NO_EXECUTE;
+        self.map_pages(
+            start,
+            PhysFrame::range_inclusive(first_frame, last_frame).map(Some),
+            flags,
+        )?;
+        Ok(start + (address - first_frame.start_address()))
+    }
+
+    /// Maps `frame` at the virtual address equal to its physical address.
+    pub fn identity_map(&mut self, frame: PhysFrame) -> Result<(), MapToError<Size4KiB>> {
+        let address = VirtAddr::new(frame.start_address().as_u64());
+        if self.mapper.translate_addr(address) == Some(frame.start_address()) {
+            return Ok(());
+        }
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+        self.map_pages(address, core::iter::once(Some(frame)), flags)
+    }
+}
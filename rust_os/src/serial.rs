@@ -1,11 +1,25 @@
 use core::fmt::Arguments;
 use core::fmt::Write;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll};
 
+use conquer_once::spin::OnceCell;
+use crossbeam_queue::ArrayQueue;
+use futures_util::{Stream, task::AtomicWaker};
 use lazy_static::lazy_static;
 use spin::Mutex;
 use uart_16550::SerialPort;
 use x86_64::instructions::interrupts::without_interrupts;
 
+/// Number of received bytes buffered until they are read.
+const INPUT_QUEUE_CAPACITY: usize = 256;
+
+static INPUT_QUEUE: OnceCell<ArrayQueue<u8>> = OnceCell::uninit();
+static INPUT_WAKER: AtomicWaker = AtomicWaker::new();
+/// Whether the console output of `print!` is copied to the serial port.
+static CONSOLE_MIRROR: AtomicBool = AtomicBool::new(false);
+
 lazy_static! {
     static ref SERIAL_PORT: Mutex<SerialPort> = {
         let mut serial_port = unsafe { SerialPort::new(0x3F8) };
@@ -14,6 +28,82 @@ lazy_static! {
     };
 }
 
+/// Initializes the serial port, which also enables its receive interrupt,
+/// and the queue for received bytes. Has to be called after the heap is set up.
+pub fn init() {
+    lazy_static::initialize(&SERIAL_PORT);
+    INPUT_QUEUE.init_once(|| ArrayQueue::new(INPUT_QUEUE_CAPACITY));
+}
+
+/// Reads the received bytes from the serial port. Called by the COM1
+/// interrupt handler.
+pub(crate) fn handle_interrupt() {
+    let mut dropped = false;
+    let mut serial_port = SERIAL_PORT.lock();
+    while let Ok(byte) = serial_port.try_receive() {
+        // bytes received before `init` are dropped
+        if let Some(queue) = INPUT_QUEUE.get() {
+            dropped |= queue.push(byte).is_err();
+        }
+    }
+    drop(serial_port);
+
+    INPUT_WAKER.wake();
+    if dropped {
+        log::warn!("serial input queue full, dropping input");
+    }
+}
+
+/// Enables or disables copying the console output to the serial port.
+pub fn set_console_mirror(enabled: bool) {
+    CONSOLE_MIRROR.store(enabled, Ordering::SeqCst);
+}
+
+pub(crate) fn mirror_console(args: Arguments) {
+    if CONSOLE_MIRROR.load(Ordering::SeqCst) {
+        _serial_print(args);
+    }
+}
+
+/// Stream of the bytes received on the serial port.
+pub struct SerialInput {
+    _private: (),
+}
+
+impl SerialInput {
+    pub fn new() -> Self {
+        SerialInput { _private: () }
+    }
+}
+
+impl Default for SerialInput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Stream for SerialInput {
+    type Item = u8;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<u8>> {
+        let Some(queue) = INPUT_QUEUE.get() else {
+            return Poll::Ready(None);
+        };
+        if let Some(byte) = queue.pop() {
+            return Poll::Ready(Some(byte));
+        }
+
+        INPUT_WAKER.register(cx.waker());
+        match queue.pop() {
+            Some(byte) => {
+                INPUT_WAKER.take();
+                Poll::Ready(Some(byte))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
 // Prints to qemu host through uart 16550 serial port
 // appends a newline
 #[macro_export]
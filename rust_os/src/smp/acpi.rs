@@ -0,0 +1,124 @@
+use alloc::vec::Vec;
+use core::ptr;
+
+use x86_64::PhysAddr;
+
+use crate::memory::physical_to_virtual;
+
+const RSDP_SIGNATURE: &[u8; 8] = b"RSD PTR ";
+const MADT_SIGNATURE: &[u8; 4] = b"APIC";
+const SDT_HEADER_SIZE: u64 = 36;
+
+// MADT entry types
+const PROCESSOR_LOCAL_APIC: u8 = 0;
+const LOCAL_APIC_ADDRESS_OVERRIDE: u8 = 5;
+const PROCESSOR_LOCAL_X2APIC: u8 = 9;
+
+const PROCESSOR_ENABLED: u32 = 1 << 0;
+const PROCESSOR_ONLINE_CAPABLE: u32 = 1 << 1;
+
+/// Processor entry of the MADT.
+#[derive(Debug, Clone, Copy)]
+pub struct Processor {
+    pub apic_id: u32,
+    /// Whether the firmware started the processor. Only online capable
+    /// processors that are not enabled can be started later.
+    pub enabled: bool,
+    pub online_capable: bool,
+}
+
+/// The parts of the Multiple APIC Description Table the kernel uses.
+#[derive(Debug)]
+pub struct Madt {
+    pub local_apic_address: PhysAddr,
+    pub processors: Vec<Processor>,
+}
+
+fn read<T: Copy>(address: PhysAddr) -> T {
+    unsafe { ptr::read_unaligned(physical_to_virtual(address).as_ptr()) }
+}
+
+/// Finds and parses the MADT, starting from the RSDP the bootloader found.
+pub fn find_madt(rsdp_address: PhysAddr) -> Option<Madt> {
+    // the checksum of the ACPI 1.0 part covers the first 20 bytes
+    let header: [u8; 20] = read(rsdp_address);
+    let checksum = header.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    if &header[..8] != RSDP_SIGNATURE || checksum != 0 {
+        return None;
+    }
+
+    let revision: u8 = read(rsdp_address + 15u64);
+    let tables = if revision >= 2 {
+        let xsdt: u64 = read(rsdp_address + 24u64);
+        root_table_entries(PhysAddr::new(xsdt), 8)
+    } else {
+        let rsdt: u32 = read(rsdp_address + 16u64);
+        root_table_entries(PhysAddr::new(rsdt as u64), 4)
+    };
+
+    tables
+        .into_iter()
+        .find(|table| &read::<[u8; 4]>(*table) == MADT_SIGNATURE)
+        .map(parse_madt)
+}
+
+/// Reads the table addresses of the RSDT (4 byte entries) or XSDT (8 byte entries).
+fn root_table_entries(address: PhysAddr, entry_size: u64) -> Vec<PhysAddr> {
+    let length: u32 = read(address + 4u64);
+    let count = (length as u64).saturating_sub(SDT_HEADER_SIZE) / entry_size;
+    (0..count)
+        .map(|i| {
+            let entry = address + SDT_HEADER_SIZE + i * entry_size;
+            match entry_size {
+                8 => PhysAddr::new(read::<u64>(entry)),
+                _ => PhysAddr::new(read::<u32>(entry) as u64),
+            }
+        })
+        .collect()
+}
+
+fn parse_madt(address: PhysAddr) -> Madt {
+    let length: u32 = read(address + 4u64);
+    let local_apic_address: u32 = read(address + SDT_HEADER_SIZE);
+    let mut madt = Madt {
+        local_apic_address: PhysAddr::new(local_apic_address as u64),
+        processors: Vec::new(),
+    };
+
+    // entries start after the local APIC address and the flags
+    let mut entry = address + SDT_HEADER_SIZE + 8u64;
+    let end = address + length as u64;
+    while entry + 2u64 <= end {
+        let entry_type: u8 = read(entry);
+        let entry_length: u8 = read(entry + 1u64);
+        if entry_length < 2 {
+            break;
+        }
+        match entry_type {
+            PROCESSOR_LOCAL_APIC => {
+                let apic_id: u8 = read(entry + 3u64);
+                let flags: u32 = read(entry + 4u64);
+                madt.processors.push(processor(apic_id as u32, flags));
+            }
+            PROCESSOR_LOCAL_X2APIC => {
+                let apic_id: u32 = read(entry + 4u64);
+                let flags: u32 = read(entry + 8u64);
+                madt.processors.push(processor(apic_id, flags));
+            }
+            LOCAL_APIC_ADDRESS_OVERRIDE => {
+                madt.local_apic_address = PhysAddr::new(read(entry + 4u64));
+            }
+            _ => {}
+        }
+        entry += entry_length as u64;
+    }
+    madt
+}
+
+fn processor(apic_id: u32, flags: u32) -> Processor {
+    Processor {
+        apic_id,
+        enabled: flags & PROCESSOR_ENABLED != 0,
+        online_capable: flags & PROCESSOR_ONLINE_CAPABLE != 0,
+    }
+}
@@ -0,0 +1,122 @@
+use conquer_once::spin::OnceCell;
+use core::ptr;
+use x86_64::{PhysAddr, VirtAddr};
+
+use crate::memory::with_kernel_memory;
+
+/// Vector of the IPI that wakes up a CPU halting in its idle loop.
+pub const WAKEUP_VECTOR: u8 = 0xf0;
+/// Vector the local APIC uses for spurious interrupts.
+pub const SPURIOUS_VECTOR: u8 = 0xff;
+
+// register offsets of the xAPIC
+const ID: usize = 0x020;
+const TASK_PRIORITY: usize = 0x080;
+const END_OF_INTERRUPT: usize = 0x0b0;
+const SPURIOUS_INTERRUPT_VECTOR: usize = 0x0f0;
+const ERROR_STATUS: usize = 0x280;
+const INTERRUPT_COMMAND_LOW: usize = 0x300;
+const INTERRUPT_COMMAND_HIGH: usize = 0x310;
+const LVT_LINT0: usize = 0x350;
+const LVT_LINT1: usize = 0x360;
+
+const APIC_SOFTWARE_ENABLE: u32 = 1 << 8;
+const DELIVERY_MODE_NMI: u32 = 0b100 << 8;
+const DELIVERY_MODE_INIT: u32 = 0b101 << 8;
+const DELIVERY_MODE_STARTUP: u32 = 0b110 << 8;
+const DELIVERY_MODE_EXTINT: u32 = 0b111 << 8;
+const DELIVERY_PENDING: u32 = 1 << 12;
+const LEVEL_ASSERT: u32 = 1 << 14;
+
+/// The local APIC registers are at the same address on every CPU, each CPU
+/// sees its own APIC there.
+static LOCAL_APIC: OnceCell<LocalApic> = OnceCell::uninit();
+
+struct LocalApic {
+    base: VirtAddr,
+}
+
+impl LocalApic {
+    fn read(&self, register: usize) -> u32 {
+        unsafe { ptr::read_volatile((self.base.as_u64() as usize + register) as *const u32) }
+    }
+
+    fn write(&self, register: usize, value: u32) {
+        unsafe { ptr::write_volatile((self.base.as_u64() as usize + register) as *mut u32, value) }
+    }
+
+    fn send(&self, apic_id: u32, command: u32) {
+        while self.read(INTERRUPT_COMMAND_LOW) & DELIVERY_PENDING != 0 {
+            core::hint::spin_loop();
+        }
+        self.write(INTERRUPT_COMMAND_HIGH, apic_id << 24);
+        // writing the low half sends the interrupt
+        self.write(INTERRUPT_COMMAND_LOW, command);
+    }
+}
+
+/// Maps the local APIC registers. Has to be called once before any other
+/// function of this module does something.
+pub fn init(address: PhysAddr) {
+    let base = with_kernel_memory(|memory| memory.map_mmio(address, 4096))
+        .expect("could not map the local APIC");
+    LOCAL_APIC.init_once(|| LocalApic { base });
+}
+
+fn local_apic() -> Option<&'static LocalApic> {
+    LOCAL_APIC.get()
+}
+
+/// Enables the local APIC of the calling CPU.
+///
+/// The bootstrap processor keeps receiving the legacy PIC interrupts through
+/// LINT0, the LINT pins of application processors stay masked.
+pub fn enable(bootstrap_processor: bool) {
+    let Some(apic) = local_apic() else {
+        return;
+    };
+    if bootstrap_processor {
+        apic.write(LVT_LINT0, DELIVERY_MODE_EXTINT);
+        apic.write(LVT_LINT1, DELIVERY_MODE_NMI);
+    }
+    apic.write(TASK_PRIORITY, 0);
+    apic.write(
+        SPURIOUS_INTERRUPT_VECTOR,
+        APIC_SOFTWARE_ENABLE | SPURIOUS_VECTOR as u32,
+    );
+    // the error status register has to be written before it can be read
+    apic.write(ERROR_STATUS, 0);
+}
+
+/// APIC ID of the calling CPU.
+pub fn id() -> Option<u32> {
+    local_apic().map(|apic| apic.read(ID) >> 24)
+}
+
+/// Signals the end of an interrupt that was delivered by the local APIC.
+pub fn end_of_interrupt() {
+    if let Some(apic) = local_apic() {
+        apic.write(END_OF_INTERRUPT, 0);
+    }
+}
+
+/// Sends the interrupt `vector` to the CPU with the given APIC ID.
+pub fn send_ipi(apic_id: u32, vector: u8) {
+    if let Some(apic) = local_apic() {
+        apic.send(apic_id, LEVEL_ASSERT | vector as u32);
+    }
+}
+
+/// Resets the CPU, which then waits for a startup IPI.
+pub(super) fn send_init(apic_id: u32) {
+    if let Some(apic) = local_apic() {
+        apic.send(apic_id, LEVEL_ASSERT | DELIVERY_MODE_INIT);
+    }
+}
+
+/// Starts a CPU in real mode at address `page * 4096`.
+pub(super) fn send_startup(apic_id: u32, page: u8) {
+    if let Some(apic) = local_apic() {
+        apic.send(apic_id, LEVEL_ASSERT | DELIVERY_MODE_STARTUP | page as u32);
+    }
+}
@@ -0,0 +1,134 @@
+use alloc::{boxed::Box, vec::Vec};
+use core::time::Duration;
+
+use spin::Mutex;
+use x86_64::{PhysAddr, instructions::interrupts::without_interrupts};
+
+use crate::{gdt, interrupts, memory::with_kernel_memory, task::executor::Executor, time};
+
+pub mod acpi;
+pub mod apic;
+pub mod percpu;
+mod trampoline;
+
+use percpu::PerCpu;
+use trampoline::Trampoline;
+
+/// Pages of the kernel stack of each application processor.
+const AP_STACK_PAGES: u64 = 16;
+/// How long to wait for an application processor to report that it is running.
+const AP_STARTUP_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// All CPUs that are running, in the order they were started.
+static CPUS: Mutex<Vec<&'static PerCpu>> = Mutex::new(Vec::new());
+
+/// Lists the CPUs that are running.
+pub fn cpus() -> Vec<&'static PerCpu> {
+    without_interrupts(|| CPUS.lock().clone())
+}
+
+pub fn cpu_count() -> usize {
+    without_interrupts(|| CPUS.lock().len()).max(1)
+}
+
+/// Enables the local APIC and starts all application processors listed in
+/// the MADT. Each of them runs its own executor.
+///
+/// Without ACPI tables the kernel keeps running on the bootstrap processor only.
+pub fn init(rsdp_address: Option<PhysAddr>) {
+    let bootstrap_processor = percpu::bootstrap_processor();
+    without_interrupts(|| CPUS.lock().push(bootstrap_processor));
+
+    let Some(madt) = rsdp_address.and_then(acpi::find_madt) else {
+        return;
+    };
+    apic::init(madt.local_apic_address);
+    apic::enable(true);
+    let bootstrap_apic_id = apic::id().expect("local APIC is initialized");
+    bootstrap_processor.set_apic_id(bootstrap_apic_id);
+
+    let application_processors: Vec<u32> = madt
+        .processors
+        .iter()
+        .filter(|p| p.enabled && p.apic_id != bootstrap_apic_id && p.apic_id < 0xff)
+        .map(|p| p.apic_id)
+        .collect();
+    if application_processors.is_empty() {
+        return;
+    }
+
+    let Some(trampoline) = install_trampoline() else {
+        log::warn!("no memory below 1 MiB for the startup code, using one CPU");
+        return;
+    };
+    for (index, apic_id) in (1..).zip(application_processors) {
+        if !start_application_processor(&trampoline, index, apic_id) {
+            // it may still run the trampoline, which can not be reused then
+            log::warn!(
+                "CPU with APIC ID {} did not start, not starting the others",
+                apic_id
+            );
+            break;
+        }
+    }
+    log::info!("{} CPUs online", cpu_count());
+}
+
+fn install_trampoline() -> Option<Trampoline> {
+    let frame = with_kernel_memory(|memory| {
+        let frame = memory.frame_allocator.low_memory_frame()?;
+        memory.identity_map(frame).ok()?;
+        Some(frame)
+    })?;
+    Trampoline::install(frame)
+}
+
+/// Starts one application processor with INIT-SIPI-SIPI and waits until it
+/// left the trampoline and runs.
+fn start_application_processor(trampoline: &Trampoline, index: usize, apic_id: u32) -> bool {
+    let Ok(stack_top) = with_kernel_memory(|memory| memory.allocate_stack(AP_STACK_PAGES)) else {
+        return false;
+    };
+    let cpu: &'static PerCpu = Box::leak(Box::new(PerCpu::new(index, apic_id)));
+
+    let ticket = index as u32;
+    trampoline.prepare(application_processor_main, cpu, stack_top.as_u64(), ticket);
+    let left_trampoline = || trampoline.started() == ticket;
+
+    apic::send_init(apic_id);
+    time::busy_wait(Duration::from_millis(10));
+    // the second startup IPI is only needed if the first one got lost
+    for _ in 0..2 {
+        apic::send_startup(apic_id, trampoline.startup_page());
+        if wait_until(Duration::from_micros(200), left_trampoline) {
+            break;
+        }
+    }
+    wait_until(AP_STARTUP_TIMEOUT, left_trampoline)
+        && wait_until(AP_STARTUP_TIMEOUT, || cpu.is_online())
+}
+
+fn wait_until(timeout: Duration, condition: impl Fn() -> bool) -> bool {
+    let deadline = time::uptime() + timeout;
+    while !condition() {
+        if time::uptime() >= deadline {
+            return false;
+        }
+        core::hint::spin_loop();
+    }
+    true
+}
+
+/// Rust entry point of application processors, called by the trampoline.
+extern "C" fn application_processor_main(cpu: &'static PerCpu) -> ! {
+    cpu.install();
+    gdt::initialize_for_application_processor();
+    interrupts::initialize_for_application_processor();
+    apic::enable(false);
+
+    cpu.set_online();
+    without_interrupts(|| CPUS.lock().push(cpu));
+
+    x86_64::instructions::interrupts::enable();
+    Executor::new().run()
+}
@@ -0,0 +1,82 @@
+use core::{
+    arch::asm,
+    sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+};
+
+use x86_64::{VirtAddr, registers::model_specific::GsBase};
+
+use crate::console;
+
+/// Data that every CPU has its own copy of. The GS base of a CPU points to its copy.
+#[repr(C)]
+pub struct PerCpu {
+    /// Address of this struct, read through `gs:[0]`. Must stay the first field.
+    self_pointer: AtomicU64,
+    index: usize,
+    apic_id: AtomicU32,
+    online: AtomicBool,
+    /// The virtual console output goes to, see `console::current`.
+    pub(crate) console: AtomicUsize,
+}
+
+static BOOTSTRAP_PROCESSOR: PerCpu = PerCpu::new(0, 0);
+
+impl PerCpu {
+    pub(crate) const fn new(index: usize, apic_id: u32) -> Self {
+        PerCpu {
+            self_pointer: AtomicU64::new(0),
+            index,
+            apic_id: AtomicU32::new(apic_id),
+            online: AtomicBool::new(false),
+            console: AtomicUsize::new(console::LOG_CONSOLE),
+        }
+    }
+
+    /// Position of the CPU in the order the CPUs were started, 0 is the bootstrap processor.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn apic_id(&self) -> u32 {
+        self.apic_id.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_apic_id(&self, apic_id: u32) {
+        self.apic_id.store(apic_id, Ordering::Relaxed);
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.online.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn set_online(&self) {
+        self.online.store(true, Ordering::SeqCst);
+    }
+
+    /// Points the GS base of the calling CPU to `self`.
+    pub(crate) fn install(&'static self) {
+        let address = self as *const PerCpu as u64;
+        self.self_pointer.store(address, Ordering::SeqCst);
+        GsBase::write(VirtAddr::new(address));
+    }
+}
+
+/// Installs the per-CPU data of the bootstrap processor. Has to run before
+/// anything calls [`current`].
+pub fn init_bootstrap_processor() {
+    BOOTSTRAP_PROCESSOR.install();
+    BOOTSTRAP_PROCESSOR.set_online();
+}
+
+pub(crate) fn bootstrap_processor() -> &'static PerCpu {
+    &BOOTSTRAP_PROCESSOR
+}
+
+/// Returns the per-CPU data of the calling CPU.
+pub fn current() -> &'static PerCpu {
+    let address: u64;
+    unsafe {
+        asm!("mov {}, gs:[0]", out(reg) address, options(nostack, readonly, preserves_flags));
+        &*(address as *const PerCpu)
+    }
+}
@@ -0,0 +1,195 @@
+use core::{arch::global_asm, mem::offset_of, ptr};
+
+use x86_64::{
+    registers::{
+        control::{Cr0, Cr3, Cr4, Cr4Flags},
+        model_specific::{Efer, EferFlags},
+    },
+    structures::paging::PhysFrame,
+};
+
+use super::percpu::PerCpu;
+
+/// Values the startup code of an application processor needs, written behind
+/// the code in the trampoline page.
+#[repr(C, packed)]
+struct TrampolineData {
+    gdt: [u64; 2],
+    gdt_limit: u16,
+    gdt_base: u32,
+    long_mode_offset: u32,
+    long_mode_selector: u16,
+    cr0: u32,
+    cr3: u32,
+    cr4: u32,
+    efer: u32,
+    stack: u64,
+    entry: u64,
+    argument: u64,
+    /// Number of the processor the trampoline is prepared for.
+    ticket: u32,
+    /// Set to the ticket by the processor once it read everything it needs
+    /// from the trampoline page.
+    started: u32,
+}
+
+/// 64-bit kernel code segment of the temporary GDT.
+const LONG_MODE_CODE_SEGMENT: u64 = 0x00af_9a00_0000_ffff;
+
+// The application processor starts in real mode with CS:IP = (page << 8):0.
+// It switches from real mode directly to long mode by enabling protection and
+// paging at the same time, using the page tables of the bootstrap processor.
+// The trampoline page is identity mapped so that the instructions after
+// enabling paging are still found.
+global_asm!(
+    ".pushsection .text.ap_trampoline, \"ax\"",
+    ".global AP_TRAMPOLINE_START",
+    ".global AP_TRAMPOLINE_LONG_MODE",
+    ".global AP_TRAMPOLINE_DATA",
+    ".code16",
+    "AP_TRAMPOLINE_START:",
+    "    cli",
+    "    cld",
+    "    mov %cs, %ax",
+    "    mov %ax, %ds",
+    "    lgdtl (AP_TRAMPOLINE_DATA - AP_TRAMPOLINE_START + {gdt_pointer})",
+    "    movl (AP_TRAMPOLINE_DATA - AP_TRAMPOLINE_START + {cr4}), %eax",
+    "    mov %eax, %cr4",
+    "    movl (AP_TRAMPOLINE_DATA - AP_TRAMPOLINE_START + {cr3}), %eax",
+    "    mov %eax, %cr3",
+    "    mov $0xc0000080, %ecx",
+    "    movl (AP_TRAMPOLINE_DATA - AP_TRAMPOLINE_START + {efer}), %eax",
+    "    xor %edx, %edx",
+    "    wrmsr",
+    "    movl (AP_TRAMPOLINE_DATA - AP_TRAMPOLINE_START + {cr0}), %eax",
+    "    mov %eax, %cr0",
+    "    ljmpl *(AP_TRAMPOLINE_DATA - AP_TRAMPOLINE_START + {long_mode_pointer})",
+    ".code64",
+    "AP_TRAMPOLINE_LONG_MODE:",
+    "    xor %ax, %ax",
+    "    mov %ax, %ds",
+    "    mov %ax, %es",
+    "    mov %ax, %ss",
+    "    mov %ax, %fs",
+    "    mov %ax, %gs",
+    "    mov AP_TRAMPOLINE_DATA + {stack}(%rip), %rsp",
+    "    mov AP_TRAMPOLINE_DATA + {argument}(%rip), %rdi",
+    "    mov AP_TRAMPOLINE_DATA + {entry}(%rip), %rax",
+    "    movl AP_TRAMPOLINE_DATA + {ticket}(%rip), %ecx",
+    "    movl %ecx, AP_TRAMPOLINE_DATA + {started}(%rip)",
+    "    call *%rax",
+    "    ud2",
+    ".balign 8",
+    "AP_TRAMPOLINE_DATA:",
+    "    .skip {data_size}",
+    ".popsection",
+    gdt_pointer = const offset_of!(TrampolineData, gdt_limit),
+    long_mode_pointer = const offset_of!(TrampolineData, long_mode_offset),
+    cr0 = const offset_of!(TrampolineData, cr0),
+    cr3 = const offset_of!(TrampolineData, cr3),
+    cr4 = const offset_of!(TrampolineData, cr4),
+    efer = const offset_of!(TrampolineData, efer),
+    stack = const offset_of!(TrampolineData, stack),
+    entry = const offset_of!(TrampolineData, entry),
+    argument = const offset_of!(TrampolineData, argument),
+    ticket = const offset_of!(TrampolineData, ticket),
+    started = const offset_of!(TrampolineData, started),
+    data_size = const size_of::<TrampolineData>(),
+    options(att_syntax)
+);
+
+unsafe extern "C" {
+    static AP_TRAMPOLINE_START: u8;
+    static AP_TRAMPOLINE_LONG_MODE: u8;
+    static AP_TRAMPOLINE_DATA: u8;
+}
+
+/// Startup code of application processors, copied into a page below 1 MiB.
+pub(super) struct Trampoline {
+    frame: PhysFrame,
+}
+
+impl Trampoline {
+    /// Copies the startup code into `frame`, which has to be identity mapped
+    /// and writable.
+    pub(super) fn install(frame: PhysFrame) -> Option<Trampoline> {
+        let cr3 = Cr3::read().0.start_address();
+        if frame.start_address().as_u64() >= 0x10_0000 || cr3.as_u64() > u32::MAX as u64 {
+            // unreachable from real mode
+            return None;
+        }
+
+        let start = &raw const AP_TRAMPOLINE_START as usize;
+        let code_size = Self::offset(&raw const AP_TRAMPOLINE_DATA);
+        assert!(code_size + size_of::<TrampolineData>() <= 4096);
+
+        let trampoline = Trampoline { frame };
+        unsafe {
+            ptr::copy_nonoverlapping(start as *const u8, trampoline.address(0), code_size);
+        }
+        Some(trampoline)
+    }
+
+    /// Offset of a symbol of the startup code from its start.
+    fn offset(symbol: *const u8) -> usize {
+        symbol as usize - &raw const AP_TRAMPOLINE_START as usize
+    }
+
+    fn address(&self, offset: usize) -> *mut u8 {
+        (self.frame.start_address().as_u64() as usize + offset) as *mut u8
+    }
+
+    fn physical(&self, offset: usize) -> u32 {
+        (self.frame.start_address() + offset as u64).as_u64() as u32
+    }
+
+    /// The startup IPI vector that makes a CPU run the trampoline.
+    pub(super) fn startup_page(&self) -> u8 {
+        (self.frame.start_address().as_u64() >> 12) as u8
+    }
+
+    /// Prepares the trampoline to call `entry` with `cpu` as the argument, on
+    /// the stack ending at `stack_top`. [`Self::started`] returns `ticket`
+    /// once the processor no longer needs the trampoline.
+    pub(super) fn prepare(
+        &self,
+        entry: extern "C" fn(&'static PerCpu) -> !,
+        cpu: &'static PerCpu,
+        stack_top: u64,
+        ticket: u32,
+    ) {
+        let data_offset = Self::offset(&raw const AP_TRAMPOLINE_DATA);
+        let cr4 = Cr4::read() - Cr4Flags::PCID;
+        let efer = Efer::read()
+            & (EferFlags::SYSTEM_CALL_EXTENSIONS
+                | EferFlags::LONG_MODE_ENABLE
+                | EferFlags::NO_EXECUTE_ENABLE);
+
+        let data = TrampolineData {
+            gdt: [0, LONG_MODE_CODE_SEGMENT],
+            gdt_limit: (size_of::<[u64; 2]>() - 1) as u16,
+            gdt_base: self.physical(data_offset + offset_of!(TrampolineData, gdt)),
+            long_mode_offset: self.physical(Self::offset(&raw const AP_TRAMPOLINE_LONG_MODE)),
+            long_mode_selector: 8,
+            cr0: Cr0::read_raw() as u32,
+            cr3: Cr3::read().0.start_address().as_u64() as u32,
+            cr4: cr4.bits() as u32,
+            efer: efer.bits() as u32,
+            stack: stack_top,
+            entry: entry as usize as u64,
+            argument: cpu as *const PerCpu as u64,
+            ticket,
+            started: 0,
+        };
+        unsafe { ptr::write_unaligned(self.address(data_offset) as *mut TrampolineData, data) }
+    }
+
+    /// The ticket of the last processor that left the trampoline, 0 if none
+    /// did since it was prepared.
+    pub(super) fn started(&self) -> u32 {
+        let offset =
+            Self::offset(&raw const AP_TRAMPOLINE_DATA) + offset_of!(TrampolineData, started);
+        // written by another processor, and aligned as the data is
+        unsafe { ptr::read_volatile(self.address(offset) as *const u32) }
+    }
+}
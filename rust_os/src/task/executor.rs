@@ -1,71 +1,197 @@
-use super::{Task, TaskId};
-use alloc::{collections::BTreeMap, sync::Arc, task::Wake};
-use core::task::{Context, Poll, Waker};
+use super::{JoinHandle, Task, TaskId, TaskStats};
+use crate::smp::{apic, percpu};
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    string::String,
+    sync::Arc,
+    task::Wake,
+    vec::Vec,
+};
+use core::{
+    future::Future,
+    sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+    task::{Context, Poll, Waker},
+};
 use crossbeam_queue::ArrayQueue;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+/// Run queue capacity used by [`Executor::new`].
+pub const DEFAULT_QUEUE_CAPACITY: usize = 100;
+
+/// Spawners of the executors running on each CPU, by CPU index. Used by `task::spawn`.
+static GLOBAL_SPAWNERS: Mutex<BTreeMap<usize, Spawner>> = Mutex::new(BTreeMap::new());
+
+/// Returns the spawner of one of the running executors, taking turns between CPUs.
+pub(super) fn global_spawner() -> Option<Spawner> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    without_interrupts(|| {
+        let spawners = GLOBAL_SPAWNERS.lock();
+        if spawners.is_empty() {
+            return None;
+        }
+        let next = NEXT.fetch_add(1, Ordering::Relaxed) % spawners.len();
+        spawners.values().nth(next).cloned()
+    })
+}
+
+/// Takes the spawner of an executor out of [`GLOBAL_SPAWNERS`] when the
+/// executor stops running, putting back the one it replaced.
+struct GlobalSpawner {
+    cpu: usize,
+    replaced: Option<Spawner>,
+}
+
+impl Drop for GlobalSpawner {
+    fn drop(&mut self) {
+        without_interrupts(|| {
+            let mut spawners = GLOBAL_SPAWNERS.lock();
+            match self.replaced.take() {
+                Some(spawner) => spawners.insert(self.cpu, spawner),
+                None => spawners.remove(&self.cpu),
+            }
+        });
+    }
+}
 
 pub struct Executor {
     tasks: BTreeMap<TaskId, Task>,
-    task_queue: Arc<ArrayQueue<TaskId>>,
-    waker_cache: BTreeMap<TaskId, Waker>,
+    task_queue: Arc<RunQueue>,
+    waker_cache: BTreeMap<TaskId, CachedWaker>,
+    spawn_queue: Arc<SpawnQueue>,
 }
 
 impl Executor {
     pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_QUEUE_CAPACITY)
+    }
+
+    /// Creates an executor whose run queue holds up to `capacity` woken tasks.
+    ///
+    /// The capacity only bounds the fast path: wakeups beyond it are still
+    /// picked up by scanning all tasks, so a full queue never loses a wakeup.
+    pub fn with_capacity(capacity: usize) -> Self {
         Executor {
             tasks: BTreeMap::new(),
-            task_queue: Arc::new(ArrayQueue::new(100)),
+            task_queue: Arc::new(RunQueue::new(capacity.max(1))),
             waker_cache: BTreeMap::new(),
+            spawn_queue: Arc::new(SpawnQueue::new()),
+        }
+    }
+
+    /// Returns a handle that can spawn tasks onto this executor from anywhere,
+    /// including other tasks.
+    pub fn spawner(&self) -> Spawner {
+        Spawner {
+            spawn_queue: self.spawn_queue.clone(),
+            task_queue: self.task_queue.clone(),
         }
     }
 
-    pub fn spawn(&mut self, task: Task) {
+    /// Spawns `future` as a new task and returns a handle to await its output.
+    pub fn spawn<F>(&mut self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let (task, handle) = Task::joinable(future);
+        self.spawn_task(task);
+        handle
+    }
+
+    fn spawn_task(&mut self, task: Task) {
         let task_id = task.id;
+        task.register();
+        let waker = CachedWaker::new(task_id, task.stats.clone(), self.task_queue.clone());
         if self.tasks.insert(task.id, task).is_some() {
             panic!("task with same ID already in tasks");
         }
-        self.task_queue.push(task_id).expect("task queue is full");
+        waker.task_waker.schedule();
+        self.waker_cache.insert(task_id, waker);
+    }
+
+    fn spawn_queued_tasks(&mut self) {
+        while let Some(task) = self.spawn_queue.pop() {
+            self.spawn_task(task);
+        }
     }
 
     fn run_ready_tasks(&mut self) {
+        self.spawn_queued_tasks();
+
+        while let Some(task_id) = self.task_queue.pop() {
+            self.poll_task(task_id);
+        }
+
+        if self.task_queue.take_overflow() {
+            // some wakeups did not fit into the queue, find them by their flag
+            let scheduled: Vec<TaskId> = self
+                .waker_cache
+                .iter()
+                .filter(|(_, waker)| waker.task_waker.is_scheduled())
+                .map(|(task_id, _)| *task_id)
+                .collect();
+            for task_id in scheduled {
+                self.poll_task(task_id);
+            }
+        }
+    }
+
+    fn poll_task(&mut self, task_id: TaskId) {
         let Self {
-            tasks,
-            task_queue,
-            waker_cache,
+            tasks, waker_cache, ..
         } = self;
 
-        while let Some(task_id) = task_queue.pop() {
-            let task = match tasks.get_mut(&task_id) {
-                Some(task) => task,
-                None => continue, // task no longer exists
-            };
-            let waker = waker_cache
-                .entry(task_id)
-                .or_insert_with(|| TaskWaker::new(task_id, task_queue.clone()));
-            let mut context = Context::from_waker(waker);
-            match task.poll(&mut context) {
-                Poll::Ready(()) => {
-                    // remove remnants of completed task
-                    tasks.remove(&task_id);
-                    waker_cache.remove(&task_id);
-                }
-                Poll::Pending => {}
+        let (task, waker) = match (tasks.get_mut(&task_id), waker_cache.get(&task_id)) {
+            (Some(task), Some(waker)) => (task, waker),
+            _ => return, // task no longer exists
+        };
+        // wakeups from now on need to poll the task again
+        waker.task_waker.unschedule();
+        let mut context = Context::from_waker(&waker.waker);
+
+        match task.poll(&mut context) {
+            Poll::Ready(()) => {
+                // remove remnants of completed task
+                tasks.remove(&task_id);
+                waker_cache.remove(&task_id);
             }
+            Poll::Pending => {}
+        }
+    }
+
+    /// Makes this the executor of the calling CPU, for `task::spawn` and for
+    /// wakeups from other CPUs.
+    fn make_global(&self) -> GlobalSpawner {
+        let cpu = percpu::current();
+        self.task_queue.owner.store(cpu.apic_id(), Ordering::SeqCst);
+        let replaced =
+            without_interrupts(|| GLOBAL_SPAWNERS.lock().insert(cpu.index(), self.spawner()));
+        GlobalSpawner {
+            cpu: cpu.index(),
+            replaced,
         }
     }
 
+    /// Runs the executor forever on the calling CPU.
     pub fn run(&mut self) -> ! {
+        let _global = self.make_global();
         loop {
             self.run_ready_tasks();
             self.sleep_if_idle();
         }
     }
 
+    /// Runs the tasks until there are none left. `task::spawn` only reaches
+    /// the executor while this runs.
     pub fn run_until_idle(&mut self) {
+        let _global = self.make_global();
         loop {
             self.run_ready_tasks();
 
             // Exit if no more tasks
-            if self.tasks.is_empty() {
+            if self.tasks.is_empty() && self.spawn_queue.is_empty() {
                 break;
             }
 
@@ -77,29 +203,192 @@ impl Executor {
         use x86_64::instructions::interrupts::{self, enable_and_hlt};
 
         interrupts::disable();
-        if self.task_queue.is_empty() {
+        // announce the nap before checking the queues, so that a task woken on
+        // another CPU either is seen here or sends a wakeup IPI
+        self.task_queue.sleeping.store(true, Ordering::SeqCst);
+        if self.task_queue.is_idle() && self.spawn_queue.is_empty() {
             enable_and_hlt();
         } else {
             interrupts::enable();
         }
+        self.task_queue.sleeping.store(false, Ordering::SeqCst);
     }
 }
 
-struct TaskWaker {
-    task_id: TaskId,
-    task_queue: Arc<ArrayQueue<TaskId>>,
+/// Cloneable handle to spawn tasks onto an [`Executor`] that may be running
+/// somewhere else.
+///
+/// Spawned tasks are queued and picked up by the executor the next time it
+/// looks for ready tasks, so this can be used from within tasks. Spawning
+/// allocates, so interrupt handlers should defer to a task instead of
+/// spawning directly.
+#[derive(Clone)]
+pub struct Spawner {
+    spawn_queue: Arc<SpawnQueue>,
+    task_queue: Arc<RunQueue>,
 }
 
-impl TaskWaker {
-    fn new(task_id: TaskId, task_queue: Arc<ArrayQueue<TaskId>>) -> Waker {
-        Waker::from(Arc::new(TaskWaker {
+impl Spawner {
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (task, handle) = Task::joinable(future);
+        self.spawn_queue.push(SendTask(task));
+        self.task_queue.unpark();
+        handle
+    }
+
+    pub fn spawn_named<F>(&self, name: impl Into<String>, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (task, handle) = Task::joinable(future);
+        self.spawn_queue.push(SendTask(task.with_name(name)));
+        self.task_queue.unpark();
+        handle
+    }
+}
+
+/// A task created from a future that is `Send`.
+struct SendTask(Task);
+
+// SAFETY: only constructed by `Spawner` from `Send` futures with `Send` outputs
+unsafe impl Send for SendTask {}
+
+struct SpawnQueue {
+    tasks: Mutex<VecDeque<SendTask>>,
+}
+
+impl SpawnQueue {
+    fn new() -> Self {
+        SpawnQueue {
+            tasks: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn push(&self, task: SendTask) {
+        without_interrupts(|| self.tasks.lock().push_back(task));
+    }
+
+    fn pop(&self) -> Option<Task> {
+        without_interrupts(|| self.tasks.lock().pop_front()).map(|task| task.0)
+    }
+
+    fn is_empty(&self) -> bool {
+        without_interrupts(|| self.tasks.lock().is_empty())
+    }
+}
+
+/// Value of [`RunQueue::owner`] while the executor is not running.
+const NO_CPU: u32 = u32::MAX;
+
+/// Queue of woken tasks that still need to be polled.
+///
+/// Pushing never blocks or allocates, so tasks can be woken from interrupt
+/// handlers and from other CPUs. If the queue is full, the wakeup is only
+/// recorded in the flag of the task and the executor falls back to scanning
+/// all tasks.
+struct RunQueue {
+    queue: ArrayQueue<TaskId>,
+    overflowed: AtomicBool,
+    /// APIC ID of the CPU running the executor.
+    owner: AtomicU32,
+    /// Set while the executor halts because it has nothing to do.
+    sleeping: AtomicBool,
+}
+
+impl RunQueue {
+    fn new(capacity: usize) -> Self {
+        RunQueue {
+            queue: ArrayQueue::new(capacity),
+            overflowed: AtomicBool::new(false),
+            owner: AtomicU32::new(NO_CPU),
+            sleeping: AtomicBool::new(false),
+        }
+    }
+
+    fn push(&self, task_id: TaskId) {
+        if self.queue.push(task_id).is_err() {
+            self.overflowed.store(true, Ordering::SeqCst);
+        }
+        self.unpark();
+    }
+
+    /// Wakes up the executor if it halts on another CPU. On the same CPU, the
+    /// interrupt that caused the wakeup already ended the halt.
+    fn unpark(&self) {
+        if !self.sleeping.load(Ordering::SeqCst) {
+            return;
+        }
+        let owner = self.owner.load(Ordering::SeqCst);
+        if owner != NO_CPU && owner != percpu::current().apic_id() {
+            apic::send_ipi(owner, apic::WAKEUP_VECTOR);
+        }
+    }
+
+    fn pop(&self) -> Option<TaskId> {
+        self.queue.pop()
+    }
+
+    fn take_overflow(&self) -> bool {
+        self.overflowed.swap(false, Ordering::SeqCst)
+    }
+
+    fn is_idle(&self) -> bool {
+        self.queue.is_empty() && !self.overflowed.load(Ordering::SeqCst)
+    }
+}
+
+struct CachedWaker {
+    task_waker: Arc<TaskWaker>,
+    waker: Waker,
+}
+
+impl CachedWaker {
+    fn new(task_id: TaskId, stats: Arc<TaskStats>, task_queue: Arc<RunQueue>) -> Self {
+        let task_waker = Arc::new(TaskWaker {
             task_id,
+            stats,
+            scheduled: AtomicBool::new(false),
             task_queue,
-        }))
+        });
+        CachedWaker {
+            waker: Waker::from(task_waker.clone()),
+            task_waker,
+        }
     }
+}
 
+struct TaskWaker {
+    task_id: TaskId,
+    stats: Arc<TaskStats>,
+    /// Set while the task is waiting to be polled, so repeated wakeups only
+    /// enqueue the task once.
+    scheduled: AtomicBool,
+    task_queue: Arc<RunQueue>,
+}
+
+impl TaskWaker {
     fn wake_task(&self) {
-        self.task_queue.push(self.task_id).expect("task_queue full");
+        self.stats.record_wake();
+        self.schedule();
+    }
+
+    fn schedule(&self) {
+        if !self.scheduled.swap(true, Ordering::SeqCst) {
+            self.task_queue.push(self.task_id);
+        }
+    }
+
+    fn unschedule(&self) {
+        self.scheduled.store(false, Ordering::SeqCst);
+    }
+
+    fn is_scheduled(&self) -> bool {
+        self.scheduled.load(Ordering::SeqCst)
     }
 }
 
@@ -0,0 +1,110 @@
+use alloc::sync::Arc;
+use core::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll},
+};
+
+use futures_util::{future::AbortHandle, task::AtomicWaker};
+use spin::Mutex;
+
+/// Reason why a task did not produce an output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinError {
+    /// The task was aborted through its [`JoinHandle`] before it completed.
+    Cancelled,
+}
+
+impl JoinError {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            JoinError::Cancelled => "task was cancelled",
+        }
+    }
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// State shared between a running task and its [`JoinHandle`].
+pub(super) struct JoinState<T> {
+    output: Mutex<Option<Result<T, JoinError>>>,
+    /// Stays set after the handle took the output.
+    finished: AtomicBool,
+    waker: AtomicWaker,
+}
+
+impl<T> JoinState<T> {
+    pub(super) fn new() -> Self {
+        JoinState {
+            output: Mutex::new(None),
+            finished: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    pub(super) fn finish(&self, result: Result<T, JoinError>) {
+        *self.output.lock() = Some(result);
+        self.finished.store(true, Ordering::SeqCst);
+        self.waker.wake();
+    }
+}
+
+/// Handle to a spawned task that resolves to the output of the task.
+///
+/// Dropping the handle detaches the task, it keeps running in the background.
+pub struct JoinHandle<T> {
+    state: Arc<JoinState<T>>,
+    abort_handle: AbortHandle,
+}
+
+impl<T> JoinHandle<T> {
+    pub(super) fn new(state: Arc<JoinState<T>>, abort_handle: AbortHandle) -> Self {
+        JoinHandle {
+            state,
+            abort_handle,
+        }
+    }
+
+    /// Requests cancellation of the task. The future of the task is dropped the
+    /// next time the executor picks it up and the handle resolves to
+    /// [`JoinError::Cancelled`]. Has no effect if the task already finished.
+    pub fn abort(&self) {
+        self.abort_handle.abort();
+    }
+
+    /// Returns a handle that can abort the task without waiting for its output.
+    pub fn abort_handle(&self) -> AbortHandle {
+        self.abort_handle.clone()
+    }
+
+    /// Whether the task completed or was cancelled, also after the handle
+    /// resolved.
+    pub fn is_finished(&self) -> bool {
+        self.state.finished.load(Ordering::SeqCst)
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        if let Some(result) = self.state.output.lock().take() {
+            return Poll::Ready(result);
+        }
+
+        self.state.waker.register(cx.waker());
+        match self.state.output.lock().take() {
+            Some(result) => {
+                self.state.waker.take();
+                Poll::Ready(result)
+            }
+            None => Poll::Pending,
+        }
+    }
+}
@@ -1,36 +1,208 @@
-use alloc::boxed::Box;
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
 use core::{
+    any::type_name,
+    fmt,
     future::Future,
     pin::Pin,
     sync::atomic::{AtomicU64, Ordering},
     task::{Context, Poll},
+    time::Duration,
 };
+use futures_util::future::{AbortHandle, Abortable};
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use crate::task::join::JoinState;
 
 pub mod executor;
-pub mod keyboard;
+pub mod join;
 pub mod shell;
 pub mod simple_executor;
+pub mod sync;
+
+pub use executor::Spawner;
+pub use join::{JoinError, JoinHandle};
+
+/// Statistics of all tasks that were handed to an executor and have not finished yet.
+static TASK_REGISTRY: Mutex<BTreeMap<TaskId, Arc<TaskStats>>> = Mutex::new(BTreeMap::new());
+
+/// Spawns `future` on the running executor, see [`Spawner::spawn`].
+///
+/// Panics if no executor is running.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    spawner().spawn(future)
+}
+
+/// Spawns `future` with the given name on the running executor, see
+/// [`Spawner::spawn_named`].
+///
+/// Panics if no executor is running.
+pub fn spawn_named<F>(name: impl Into<String>, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    spawner().spawn_named(name, future)
+}
+
+fn spawner() -> Spawner {
+    executor::global_spawner().expect("no executor is running")
+}
+
+pub struct TaskInfo {
+    pub id: TaskId,
+    pub name: String,
+    /// How often the executor polled the task.
+    pub polls: u64,
+    /// How often the task was woken, including wakeups that did not lead to a poll.
+    pub wakes: u64,
+    /// Total time spent inside the task's `poll`.
+    pub poll_time: Duration,
+}
+
+/// Lists all tasks that are currently alive on any executor.
+pub fn tasks() -> Vec<TaskInfo> {
+    without_interrupts(|| {
+        TASK_REGISTRY
+            .lock()
+            .iter()
+            .map(|(id, stats)| TaskInfo {
+                id: *id,
+                name: stats.name.clone(),
+                polls: stats.polls.load(Ordering::Relaxed),
+                wakes: stats.wakes.load(Ordering::Relaxed),
+                poll_time: crate::time::cycles_to_duration(
+                    stats.poll_cycles.load(Ordering::Relaxed),
+                ),
+            })
+            .collect()
+    })
+}
+
+/// Counters the executor updates while running a task.
+struct TaskStats {
+    name: String,
+    polls: AtomicU64,
+    wakes: AtomicU64,
+    poll_cycles: AtomicU64,
+}
+
+impl TaskStats {
+    fn new(name: String) -> Arc<Self> {
+        Arc::new(TaskStats {
+            name,
+            polls: AtomicU64::new(0),
+            wakes: AtomicU64::new(0),
+            poll_cycles: AtomicU64::new(0),
+        })
+    }
+
+    fn record_wake(&self) {
+        self.wakes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_poll(&self, cycles: u64) {
+        self.polls.fetch_add(1, Ordering::Relaxed);
+        self.poll_cycles.fetch_add(cycles, Ordering::Relaxed);
+    }
+}
 
 pub struct Task {
     id: TaskId,
+    stats: Arc<TaskStats>,
     future: Pin<Box<dyn Future<Output = ()>>>,
 }
 
 impl Task {
     pub fn new(future: impl Future<Output = ()> + 'static) -> Task {
-        Task {
+        let (task, _) = Task::joinable(future);
+        task
+    }
+
+    /// Creates a task together with a handle that resolves to the output of
+    /// `future` and can be used to abort the task.
+    ///
+    /// The task is named after the type of `future`, use [`Task::with_name`]
+    /// to pick a different name.
+    pub fn joinable<F>(future: F) -> (Task, JoinHandle<F::Output>)
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let name = default_name::<F>();
+
+        let (abort_handle, abort_registration) = AbortHandle::new_pair();
+        let state = Arc::new(JoinState::new());
+
+        let task_state = state.clone();
+        let future = async move {
+            let result = Abortable::new(future, abort_registration)
+                .await
+                .map_err(|_| JoinError::Cancelled);
+            task_state.finish(result);
+        };
+
+        let task = Task {
             id: TaskId::new(),
+            stats: TaskStats::new(name),
             future: Box::pin(future),
-        }
+        };
+        (task, JoinHandle::new(state, abort_handle))
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Task {
+        // the task is not registered yet, so nobody else holds the statistics
+        self.stats = TaskStats::new(name.into());
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.stats.name
+    }
+
+    fn register(&self) {
+        without_interrupts(|| TASK_REGISTRY.lock().insert(self.id, self.stats.clone()));
+    }
+
+    fn unregister(&self) {
+        without_interrupts(|| TASK_REGISTRY.lock().remove(&self.id));
     }
 
     fn poll(&mut self, context: &mut Context) -> Poll<()> {
-        self.future.as_mut().poll(context)
+        let start = crate::time::cycles();
+        let poll = self.future.as_mut().poll(context);
+        self.stats
+            .record_poll(crate::time::cycles().wrapping_sub(start));
+        poll
+    }
+}
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        self.unregister();
     }
 }
 
+/// Derives a readable task name from the type of a future, e.g.
+/// `task::shell::run` for the future returned by `shell::run()`.
+fn default_name<F>() -> String {
+    let name = type_name::<F>();
+    let name = name.trim_end_matches("::{{closure}}");
+    name.strip_prefix("rust_os::").unwrap_or(name).to_string()
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-struct TaskId(u64);
+pub struct TaskId(u64);
 
 impl TaskId {
     fn new() -> Self {
@@ -38,3 +210,9 @@ impl TaskId {
         TaskId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
     }
 }
+
+impl fmt::Display for TaskId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
@@ -0,0 +1,563 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::time::Duration;
+use futures_util::future::{Either, select};
+
+use super::cwd::WorkingDirectory;
+use super::env::{self, Environment};
+use super::fileutils::{self, DumpFormat};
+use super::io::Io;
+use super::{COMMANDS, Input, Shell, pager, script};
+use crate::filesystem::{FileType, with_filesystem};
+use crate::framebuffer::with_framebuffer_writer;
+use crate::input::keyboard;
+use crate::input::layout::Layout;
+use crate::task::TaskId;
+use crate::{println, time};
+
+/// How often `top` redraws its table.
+const TOP_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Exit status of a command that succeeded.
+pub(super) const SUCCESS: i32 = 0;
+/// Exit status of a command that failed.
+pub(super) const FAILURE: i32 = 1;
+/// Exit status of a command used with wrong arguments.
+pub(super) const MISUSE: i32 = 2;
+/// Exit status of a command that does not exist.
+const NOT_FOUND: i32 = 127;
+
+/// Runs a built-in command, `parts` holds the command and its arguments.
+/// Returns the exit status.
+pub(super) async fn execute_command(parts: &[&str], shell: &mut Shell, io: &mut Io) -> i32 {
+    if parts.is_empty() {
+        return SUCCESS;
+    }
+    if let [assignment] = parts[..]
+        && let Some((name, value)) = env::parse_assignment(assignment)
+    {
+        shell.env.set(name, value);
+        return SUCCESS;
+    }
+    match parts[0] {
+        "help" => {
+            write!(io.stdout, "Available commands: ");
+            for (i, cmd) in COMMANDS.iter().enumerate() {
+                if i > 0 {
+                    write!(io.stdout, ", ");
+                }
+                write!(io.stdout, "{}", cmd);
+            }
+            writeln!(io.stdout);
+            SUCCESS
+        }
+        "version" => {
+            writeln!(io.stdout, "RustOS v0.1.0");
+            SUCCESS
+        }
+        "clear" => {
+            with_framebuffer_writer(|writer| writer.clear());
+            SUCCESS
+        }
+        "exec" => {
+            let path = shell.cwd.resolve(parts.get(1).copied().unwrap_or(""));
+            cmd_exec(&path, &shell.env, io).await
+        }
+        "ls" => {
+            let path = shell.cwd.resolve(parts.get(1).copied().unwrap_or(""));
+            cmd_ls(&path, io)
+        }
+        "cat" => fileutils::cmd_cat(&parts[1..], &shell.cwd, io).await,
+        "cd" => {
+            let home = shell.env.get("HOME").unwrap_or("/");
+            let path = String::from(parts.get(1).copied().unwrap_or(home));
+            match shell.cwd.change(&path) {
+                Ok(()) => SUCCESS,
+                Err(e) => {
+                    writeln!(io.stderr, "cd: {}: {}", path, e);
+                    FAILURE
+                }
+            }
+        }
+        "pwd" => {
+            writeln!(io.stdout, "{}", shell.cwd.get());
+            SUCCESS
+        }
+        "pushd" | "popd" => cmd_pushd(parts, &mut shell.cwd, io),
+        "head" => fileutils::cmd_head(&parts[1..], &shell.cwd, io).await,
+        "tail" => fileutils::cmd_tail(&parts[1..], &shell.cwd, io).await,
+        "wc" => fileutils::cmd_wc(&parts[1..], &shell.cwd, io).await,
+        "grep" => fileutils::cmd_grep(&parts[1..], &shell.cwd, io).await,
+        "hexdump" => {
+            let format = DumpFormat::Canonical;
+            fileutils::cmd_hexdump(format, &parts[1..], &shell.cwd, io).await
+        }
+        "xxd" => fileutils::cmd_hexdump(DumpFormat::Xxd, &parts[1..], &shell.cwd, io).await,
+        "less" | "more" => {
+            let quit_at_end = parts[0] == "more";
+            pager::cmd_less(&parts[1..], quit_at_end, &shell.cwd, &mut shell.input, io).await
+        }
+        "find" => fileutils::cmd_find(&parts[1..], &shell.cwd, io),
+        "stat" => fileutils::cmd_stat(&parts[1..], &shell.cwd, io),
+        "jobs" => {
+            cmd_jobs(io);
+            SUCCESS
+        }
+        "top" => {
+            cmd_top(&mut shell.input).await;
+            SUCCESS
+        }
+        "loadkeys" => cmd_loadkeys(parts.get(1).copied(), io),
+        "dmesg" => {
+            write!(io.stdout, "{}", crate::logger::contents());
+            SUCCESS
+        }
+        "history" => {
+            for (number, entry) in shell.history.numbered() {
+                writeln!(io.stdout, "{:>5}  {}", number, entry);
+            }
+            SUCCESS
+        }
+        "set" => cmd_set(&parts[1..], &mut shell.env, io),
+        "unset" => {
+            for name in &parts[1..] {
+                shell.env.unset(name);
+            }
+            SUCCESS
+        }
+        "env" => {
+            for (name, value) in shell.env.exported() {
+                writeln!(io.stdout, "{}={}", name, value);
+            }
+            SUCCESS
+        }
+        "export" => cmd_export(&parts[1..], &mut shell.env, io),
+        "echo" => {
+            let (escapes, words) = match parts.get(1) {
+                Some(&"-e") => (true, &parts[2..]),
+                _ => (false, &parts[1..]),
+            };
+            if words.is_empty() {
+                writeln!(io.stderr, "Usage: echo [-e] <text>");
+                return MISUSE;
+            }
+            let text = words.join(" ");
+            match escapes {
+                true => writeln!(io.stdout, "{}", unescape(&text)),
+                false => writeln!(io.stdout, "{}", text),
+            }
+            SUCCESS
+        }
+        "source" | "sh" => {
+            let Some(path) = parts.get(1) else {
+                writeln!(io.stderr, "Usage: {} <file>", parts[0]);
+                return MISUSE;
+            };
+            let path = shell.cwd.resolve(path);
+            let path = path.as_str();
+            if parts[0] == "source" {
+                script::run_file(path, shell, io).await
+            } else {
+                script::run_file_in_subshell(path, shell, io).await
+            }
+        }
+        "exit" => cmd_exit(parts.get(1).copied(), shell, io),
+        "true" => SUCCESS,
+        "false" => FAILURE,
+        "test" => cmd_test(&parts[1..], &shell.cwd, io),
+        "[" => match parts[1..].split_last() {
+            Some((&"]", expression)) => cmd_test(expression, &shell.cwd, io),
+            _ => {
+                writeln!(io.stderr, "[: missing ']'");
+                MISUSE
+            }
+        },
+        "" => SUCCESS,
+        cmd => {
+            writeln!(
+                io.stderr,
+                "Unknown command: '{}'. Type 'help' for a list of commands.",
+                cmd
+            );
+            NOT_FOUND
+        }
+    }
+}
+
+fn cmd_ls(path: &str, io: &mut Io) -> i32 {
+    match with_filesystem(|fs| fs.read_dir(path)) {
+        Some(Ok(entries)) => {
+            if entries.is_empty() {
+                writeln!(io.stdout, "(empty)");
+                return SUCCESS;
+            }
+            for entry in entries {
+                match entry.file_type {
+                    FileType::Dir => {
+                        writeln!(io.stdout, "{}/", entry.name());
+                    }
+                    FileType::File => {
+                        writeln!(io.stdout, "{}  ({} bytes)", entry.name(), entry.size);
+                    }
+                    _ => {
+                        writeln!(io.stdout, "{}", entry.name());
+                    }
+                }
+            }
+            return SUCCESS;
+        }
+        Some(Err(e)) => writeln!(io.stderr, "ls: {}: {:?}", path, e),
+        None => writeln!(io.stderr, "ls: filesystem not initialized"),
+    }
+    FAILURE
+}
+
+fn cmd_jobs(io: &mut Io) {
+    writeln!(io.stdout, "{:>4}  NAME", "ID");
+    for task in crate::task::tasks() {
+        writeln!(io.stdout, "{:>4}  {}", task.id, task.name);
+    }
+}
+
+/// Switches the keyboard layout, or lists the layouts without an argument.
+fn cmd_loadkeys(name: Option<&str>, io: &mut Io) -> i32 {
+    let Some(name) = name else {
+        let current = keyboard::layout();
+        for layout in Layout::ALL {
+            let marker = if layout == current { '*' } else { ' ' };
+            writeln!(
+                io.stdout,
+                "{} {:<8} {}",
+                marker,
+                layout.name(),
+                layout.description()
+            );
+        }
+        return SUCCESS;
+    };
+    match name.parse() {
+        Ok(layout) => {
+            keyboard::set_layout(layout);
+            writeln!(io.stdout, "Keyboard layout set to {}", layout);
+            SUCCESS
+        }
+        Err(_) => {
+            writeln!(io.stderr, "loadkeys: unknown layout '{}'", name);
+            FAILURE
+        }
+    }
+}
+
+/// Shows the executor statistics of all tasks until a key is pressed.
+async fn cmd_top(input: &mut Input) {
+    let mut previous: BTreeMap<TaskId, Duration> = BTreeMap::new();
+    let mut last_refresh = time::uptime();
+
+    loop {
+        let now = time::uptime();
+        let interval = now - last_refresh;
+        last_refresh = now;
+
+        let mut tasks = crate::task::tasks();
+        let usage: BTreeMap<TaskId, u64> = tasks
+            .iter()
+            .map(|task| {
+                let busy = task.poll_time - previous.get(&task.id).copied().unwrap_or_default();
+                let permille = match interval.as_nanos() {
+                    0 => 0,
+                    total => (busy.as_nanos() * 1000 / total) as u64,
+                };
+                (task.id, permille)
+            })
+            .collect();
+        tasks.sort_by_key(|task| core::cmp::Reverse(usage[&task.id]));
+        previous = tasks.iter().map(|task| (task.id, task.poll_time)).collect();
+
+        with_framebuffer_writer(|writer| writer.clear());
+        println!(
+            "uptime {}s, {} tasks, press any key to quit",
+            now.as_secs(),
+            tasks.len()
+        );
+        println!();
+        println!(
+            "{:>4}  {:<28} {:>8} {:>8} {:>10} {:>6}",
+            "ID", "NAME", "POLLS", "WAKES", "TIME(ms)", "CPU%"
+        );
+        for task in &tasks {
+            let name = task.name.get(..28).unwrap_or(&task.name);
+            let permille = usage[&task.id];
+            println!(
+                "{:>4}  {:<28} {:>8} {:>8} {:>10} {:>4}.{}",
+                task.id,
+                name,
+                task.polls,
+                task.wakes,
+                task.poll_time.as_millis(),
+                permille / 10,
+                permille % 10
+            );
+        }
+
+        let sleep = core::pin::pin!(time::sleep(TOP_REFRESH_INTERVAL));
+        let key = core::pin::pin!(input.next_key());
+        if let Either::Right(_) = select(sleep, key).await {
+            break;
+        }
+    }
+
+    with_framebuffer_writer(|writer| writer.clear());
+}
+
+/// Lists all variables, or sets the given `NAME=value` variables.
+fn cmd_set(args: &[&str], env: &mut Environment, io: &mut Io) -> i32 {
+    if args.is_empty() {
+        for (name, value) in env.iter() {
+            writeln!(io.stdout, "{}={}", name, value);
+        }
+        return SUCCESS;
+    }
+    let mut status = SUCCESS;
+    for arg in args {
+        match env::parse_assignment(arg) {
+            Some((name, value)) => env.set(name, value),
+            None => {
+                writeln!(io.stderr, "set: expected NAME=value, got '{}'", arg);
+                status = FAILURE;
+            }
+        }
+    }
+    status
+}
+
+/// Exports `NAME` or `NAME=value` variables, or lists the exported ones.
+fn cmd_export(args: &[&str], env: &mut Environment, io: &mut Io) -> i32 {
+    if args.is_empty() {
+        for (name, value) in env.exported() {
+            writeln!(io.stdout, "export {}={}", name, value);
+        }
+        return SUCCESS;
+    }
+    let mut status = SUCCESS;
+    for arg in args {
+        let name = match env::parse_assignment(arg) {
+            Some((name, value)) => {
+                env.set(name, value);
+                name
+            }
+            None if env::is_valid_name(arg) => arg,
+            None => {
+                writeln!(io.stderr, "export: '{}': not a valid name", arg);
+                status = FAILURE;
+                continue;
+            }
+        };
+        env.export(name);
+    }
+    status
+}
+
+async fn cmd_exec(path: &str, env: &Environment, io: &mut Io) -> i32 {
+    if !path.ends_with(".wasm") {
+        writeln!(io.stderr, "exec: {}: expected .wasm file", path);
+        return FAILURE;
+    }
+    if crate::wasm_game::is_game_running() {
+        let console = crate::wasm_game::game_console();
+        writeln!(
+            io.stderr,
+            "exec: a game is already running on tty{}",
+            console + 1
+        );
+        return FAILURE;
+    }
+    let wasm_bytes = match with_filesystem(|fs| fs.read(path)) {
+        Some(Ok(content)) => content,
+        Some(Err(e)) => {
+            writeln!(io.stderr, "exec: {}: {:?}", path, e);
+            return FAILURE;
+        }
+        None => {
+            writeln!(io.stderr, "exec: filesystem not initialized");
+            return FAILURE;
+        }
+    };
+    writeln!(io.stdout, "Starting game {} ...", path);
+
+    with_framebuffer_writer(|w| w.clear());
+    let env = env
+        .exported()
+        .map(|(name, value)| (String::from(name), String::from(value)))
+        .collect();
+    crate::wasm_game::init_wasm_game(&wasm_bytes, env);
+    crate::wasm_game::forward_keys().await;
+    SUCCESS
+}
+
+/// Ends the running script, with the status of the last command if none is
+/// given.
+fn cmd_exit(status: Option<&str>, shell: &mut Shell, io: &mut Io) -> i32 {
+    let status = match status.map(str::parse) {
+        Some(Ok(status)) => status,
+        Some(Err(_)) => {
+            writeln!(io.stderr, "exit: numeric argument required");
+            return MISUSE;
+        }
+        None => shell.env.status(),
+    };
+    if !shell.in_script() {
+        writeln!(io.stderr, "exit: the shell cannot be left, only scripts");
+        return FAILURE;
+    }
+    shell.exit = Some(status);
+    status
+}
+
+/// Changes the directory with `pushd <dir>` or back with `popd`, then
+/// prints the directory stack. `pushd` without a directory swaps the current
+/// directory with the one on top of the stack.
+fn cmd_pushd(parts: &[&str], cwd: &mut WorkingDirectory, io: &mut Io) -> i32 {
+    let result = match (parts[0], parts.get(1)) {
+        ("pushd", Some(path)) => cwd.push(path),
+        ("pushd", None) => match cwd.swap() {
+            Some(result) => result,
+            None => {
+                writeln!(io.stderr, "pushd: no other directory");
+                return FAILURE;
+            }
+        },
+        _ => match cwd.pop() {
+            Some(result) => result,
+            None => {
+                writeln!(io.stderr, "popd: directory stack empty");
+                return FAILURE;
+            }
+        },
+    };
+    if let Err(e) = result {
+        writeln!(io.stderr, "{}: {}", parts[0], e);
+        return FAILURE;
+    }
+    let stack: Vec<&str> = cwd.stack().collect();
+    writeln!(io.stdout, "{}", stack.join(" "));
+    SUCCESS
+}
+
+/// Checks a file, a string or numbers.
+fn cmd_test(args: &[&str], cwd: &WorkingDirectory, io: &mut Io) -> i32 {
+    match evaluate_test(args, cwd) {
+        Ok(true) => SUCCESS,
+        Ok(false) => FAILURE,
+        Err(message) => {
+            writeln!(io.stderr, "test: {}", message);
+            MISUSE
+        }
+    }
+}
+
+/// Evaluates the expression of `test`: `-e`, `-f` and `-d` check a path,
+/// `-z` and `-n` a string, `=` and `!=` compare strings and `-eq`, `-ne`,
+/// `-lt`, `-le`, `-gt` and `-ge` numbers. `!` negates an expression.
+fn evaluate_test(args: &[&str], cwd: &WorkingDirectory) -> Result<bool, String> {
+    let file_type = |path: &str| {
+        let path = cwd.resolve(path);
+        with_filesystem(|fs| fs.metadata(&path).ok().map(|metadata| metadata.file_type)).flatten()
+    };
+    let number = |arg: &str| {
+        arg.parse::<i64>()
+            .map_err(|_| alloc::format!("{}: integer expected", arg))
+    };
+    Ok(match *args {
+        [] => false,
+        ["!", ref expression @ ..] => !evaluate_test(expression, cwd)?,
+        [string] => !string.is_empty(),
+        ["-e", path] => file_type(path).is_some(),
+        ["-f", path] => file_type(path) == Some(FileType::File),
+        ["-d", path] => file_type(path) == Some(FileType::Dir),
+        ["-z", string] => string.is_empty(),
+        ["-n", string] => !string.is_empty(),
+        [left, "=", right] => left == right,
+        [left, "!=", right] => left != right,
+        [left, operator, right] => {
+            let (left, right) = (number(left)?, number(right)?);
+            match operator {
+                "-eq" => left == right,
+                "-ne" => left != right,
+                "-lt" => left < right,
+                "-le" => left <= right,
+                "-gt" => left > right,
+                "-ge" => left >= right,
+                _ => return Err(alloc::format!("{}: unknown operator", operator)),
+            }
+        }
+        _ => return Err(String::from("too many arguments")),
+    })
+}
+
+/// Replaces the escapes `echo -e` understands: `\n`, `\t`, `\\` and `\e` or
+/// `\033` for the escape character that starts color sequences.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('e') => unescaped.push('\x1b'),
+            Some('0') => {
+                let digits: String = chars
+                    .clone()
+                    .take(3)
+                    .take_while(|c| c.is_digit(8))
+                    .collect();
+                chars.by_ref().take(digits.len()).for_each(drop);
+                // An empty \0 is NUL; values past a byte are kept literally.
+                match u8::try_from(u16::from_str_radix(&digits, 8).unwrap_or(0)) {
+                    Ok(value) => unescaped.push(char::from(value)),
+                    Err(_) => {
+                        unescaped.push_str("\\0");
+                        unescaped.push_str(&digits);
+                    }
+                }
+            }
+            Some(c) => {
+                if c != '\\' {
+                    unescaped.push('\\');
+                }
+                unescaped.push(c);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+#[test_case]
+fn test_unescape() {
+    assert_eq!(unescape("a\\tb\\n"), "a\tb\n");
+    assert_eq!(unescape("\\e[31mred\\033[0m"), "\x1b[31mred\x1b[0m");
+    assert_eq!(unescape("\\\\ \\x \\"), "\\ \\x \\");
+    assert_eq!(unescape("\\0 \\0101"), "\0 A");
+    assert_eq!(unescape("\\0777"), "\\0777");
+}
+
+#[test_case]
+fn test_evaluate_test() {
+    let cwd = WorkingDirectory::new();
+    assert_eq!(evaluate_test(&[], &cwd), Ok(false));
+    assert_eq!(evaluate_test(&["text"], &cwd), Ok(true));
+    assert_eq!(evaluate_test(&["-z", ""], &cwd), Ok(true));
+    assert_eq!(evaluate_test(&["!", "-n", ""], &cwd), Ok(true));
+    assert_eq!(evaluate_test(&["a", "=", "a"], &cwd), Ok(true));
+    assert_eq!(evaluate_test(&["a", "!=", "a"], &cwd), Ok(false));
+    assert_eq!(evaluate_test(&["2", "-lt", "10"], &cwd), Ok(true));
+    assert_eq!(evaluate_test(&["-3", "-ge", "2"], &cwd), Ok(false));
+    assert!(evaluate_test(&["a", "-eq", "1"], &cwd).is_err());
+    assert!(evaluate_test(&["1", "-xx", "1"], &cwd).is_err());
+    assert!(evaluate_test(&["a", "b", "c", "d"], &cwd).is_err());
+}
@@ -0,0 +1,115 @@
+use alloc::{string::String, vec::Vec};
+
+use crate::filesystem::{self, FileType, with_filesystem};
+
+/// Current directory of the shell, relative paths are resolved from it.
+/// `pushd` and `popd` keep the directories left behind on a stack.
+#[derive(Clone)]
+pub struct WorkingDirectory {
+    /// Absolute path without `.` or `..` components or trailing slashes.
+    current: String,
+    stack: Vec<String>,
+}
+
+impl WorkingDirectory {
+    pub fn new() -> Self {
+        WorkingDirectory {
+            current: String::from("/"),
+            stack: Vec::new(),
+        }
+    }
+
+    pub fn get(&self) -> &str {
+        &self.current
+    }
+
+    /// The absolute path of `path`, which is taken as relative to the
+    /// current directory unless it starts with `/`. `..` goes up to the
+    /// parent directory as written, not the one a link points into.
+    pub fn resolve(&self, path: &str) -> String {
+        let mut resolved = if path.starts_with('/') {
+            String::new()
+        } else {
+            self.current.clone()
+        };
+        for component in path.split('/') {
+            if component.is_empty() || component == "." {
+                continue;
+            }
+            if component == ".." {
+                let parent = resolved.rfind('/').unwrap_or(0);
+                resolved.truncate(parent);
+                continue;
+            }
+            if !resolved.ends_with('/') {
+                resolved.push('/');
+            }
+            resolved.push_str(component);
+        }
+        if resolved.is_empty() {
+            resolved.push('/');
+        }
+        resolved
+    }
+
+    /// Changes to the directory at `path`.
+    pub fn change(&mut self, path: &str) -> filesystem::Result<()> {
+        let path = self.resolve(path);
+        if path != "/" {
+            let metadata =
+                with_filesystem(|fs| fs.metadata(&path)).ok_or(filesystem::Error::NotFound)??;
+            if metadata.file_type != FileType::Dir {
+                return Err(filesystem::Error::UnexpectedFileType);
+            }
+        }
+        self.current = path;
+        Ok(())
+    }
+
+    /// Changes to `path` and puts the directory left on the stack.
+    pub fn push(&mut self, path: &str) -> filesystem::Result<()> {
+        let previous = self.current.clone();
+        self.change(path)?;
+        self.stack.push(previous);
+        Ok(())
+    }
+
+    /// Changes back to the directory on top of the stack, `None` if the
+    /// stack is empty.
+    pub fn pop(&mut self) -> Option<filesystem::Result<()>> {
+        let path = self.stack.pop()?;
+        Some(self.change(&path))
+    }
+
+    /// Swaps the current directory with the one on top of the stack, `None`
+    /// if the stack is empty.
+    pub fn swap(&mut self) -> Option<filesystem::Result<()>> {
+        let top = self.stack.last()?.clone();
+        let previous = self.current.clone();
+        Some(self.change(&top).map(|()| {
+            if let Some(top) = self.stack.last_mut() {
+                *top = previous;
+            }
+        }))
+    }
+
+    /// The current directory followed by the stack, the newest first.
+    pub fn stack(&self) -> impl Iterator<Item = &str> {
+        core::iter::once(self.current.as_str()).chain(self.stack.iter().rev().map(String::as_str))
+    }
+}
+
+#[test_case]
+fn test_resolve() {
+    let mut cwd = WorkingDirectory::new();
+    assert_eq!(cwd.resolve(""), "/");
+    assert_eq!(cwd.resolve("apps/./snake.wasm"), "/apps/snake.wasm");
+    cwd.current = String::from("/tmp");
+    assert_eq!(cwd.resolve("file1.txt"), "/tmp/file1.txt");
+    assert_eq!(cwd.resolve("./dir//"), "/tmp/dir");
+    assert_eq!(cwd.resolve("/etc/rc"), "/etc/rc");
+    assert_eq!(cwd.resolve("//"), "/");
+    assert_eq!(cwd.resolve("."), "/tmp");
+    assert_eq!(cwd.resolve(".."), "/");
+    assert_eq!(cwd.resolve("../etc/../../apps"), "/apps");
+}
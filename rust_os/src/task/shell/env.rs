@@ -0,0 +1,120 @@
+use alloc::{collections::BTreeMap, string::String};
+
+/// Shell variables. Exported variables are passed on to the programs the
+/// shell starts.
+#[derive(Clone)]
+pub struct Environment {
+    variables: BTreeMap<String, Variable>,
+    /// Exit status of the last command, `$?`.
+    status: i32,
+}
+
+#[derive(Clone)]
+struct Variable {
+    value: String,
+    exported: bool,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        let mut env = Environment {
+            variables: BTreeMap::new(),
+            status: 0,
+        };
+        env.set("HOME", "/");
+        env.export("HOME");
+        env
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.variables.get(name).map(|v| v.value.as_str())
+    }
+
+    /// Sets a variable, it stays exported if it was exported before.
+    pub fn set(&mut self, name: &str, value: &str) {
+        match self.variables.get_mut(name) {
+            Some(variable) => variable.value = String::from(value),
+            None => {
+                let variable = Variable {
+                    value: String::from(value),
+                    exported: false,
+                };
+                self.variables.insert(String::from(name), variable);
+            }
+        }
+    }
+
+    /// Marks a variable as exported, an unset variable is exported empty.
+    pub fn export(&mut self, name: &str) {
+        self.variables
+            .entry(String::from(name))
+            .or_insert_with(|| Variable {
+                value: String::new(),
+                exported: false,
+            })
+            .exported = true;
+    }
+
+    pub fn unset(&mut self, name: &str) {
+        self.variables.remove(name);
+    }
+
+    pub fn status(&self) -> i32 {
+        self.status
+    }
+
+    pub fn set_status(&mut self, status: i32) {
+        self.status = status;
+    }
+
+    /// A copy with only the exported variables, as a script started with `sh`
+    /// sees them.
+    pub fn subshell(&self) -> Self {
+        let variables = self
+            .variables
+            .iter()
+            .filter(|(_, variable)| variable.exported)
+            .map(|(name, variable)| {
+                let variable = Variable {
+                    value: variable.value.clone(),
+                    exported: true,
+                };
+                (name.clone(), variable)
+            })
+            .collect();
+        Environment {
+            variables,
+            status: self.status,
+        }
+    }
+
+    /// All variables with their values, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.variables
+            .iter()
+            .map(|(name, variable)| (name.as_str(), variable.value.as_str()))
+    }
+
+    /// The exported variables, sorted by name.
+    pub fn exported(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.variables
+            .iter()
+            .filter(|(_, variable)| variable.exported)
+            .map(|(name, variable)| (name.as_str(), variable.value.as_str()))
+    }
+}
+
+/// Whether `name` can be used as a variable name: a letter or underscore,
+/// followed by letters, digits and underscores.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Splits `NAME=value` into name and value if `NAME` is a valid name.
+pub fn parse_assignment(word: &str) -> Option<(&str, &str)> {
+    word.split_once('=').filter(|(name, _)| is_valid_name(name))
+}
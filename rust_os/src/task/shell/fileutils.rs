@@ -0,0 +1,788 @@
+use alloc::{
+    collections::VecDeque,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use super::commands::{FAILURE, MISUSE, SUCCESS};
+use super::cwd::WorkingDirectory;
+use super::io::{CHUNK_SIZE, Io, Output, Stdin};
+use super::pattern::Regex;
+use crate::filesystem::{self, FileType, with_filesystem};
+
+/// How many lines `head` and `tail` print if no count is given.
+const DEFAULT_LINES: usize = 10;
+/// How many bytes `hexdump` and `xxd` show per line.
+const BYTES_PER_LINE: usize = 16;
+
+/// Bytes of a file, read a chunk at a time with `FileSystem::read_into`, or
+/// of the standard input.
+enum Source<'a> {
+    File { path: String, position: usize },
+    Stdin(&'a mut Stdin),
+}
+
+impl Source<'_> {
+    async fn read(&mut self, buffer: &mut [u8]) -> filesystem::Result<usize> {
+        match self {
+            Source::File { path, position } => {
+                let read = with_filesystem(|fs| fs.read_into(path, *position, buffer))
+                    .ok_or(filesystem::Error::NotFound)??;
+                *position += read;
+                Ok(read)
+            }
+            Source::Stdin(stdin) => Ok(stdin.read(buffer).await.unwrap_or(0)),
+        }
+    }
+}
+
+/// The files named by `paths` with their names as given, or the standard
+/// input if there are none. `None` if the standard input is the console.
+fn sources<'a>(
+    paths: &[&str],
+    cwd: &WorkingDirectory,
+    stdin: &'a mut Stdin,
+) -> Option<Vec<(String, Source<'a>)>> {
+    if paths.is_empty() {
+        if let Stdin::Console = stdin {
+            return None;
+        }
+        return Some(alloc::vec![(String::new(), Source::Stdin(stdin))]);
+    }
+    let sources = paths
+        .iter()
+        .map(|path| {
+            let source = Source::File {
+                path: cwd.resolve(path),
+                position: 0,
+            };
+            (String::from(*path), source)
+        })
+        .collect();
+    Some(sources)
+}
+
+/// Splits a source into lines without reading all of it at once.
+struct Lines<'a> {
+    source: Source<'a>,
+    buffer: Vec<u8>,
+    end: bool,
+}
+
+impl<'a> Lines<'a> {
+    fn new(source: Source<'a>) -> Self {
+        Lines {
+            source,
+            buffer: Vec::new(),
+            end: false,
+        }
+    }
+
+    /// The next line without its newline, `None` at the end.
+    async fn next(&mut self) -> filesystem::Result<Option<String>> {
+        loop {
+            if let Some(newline) = self.buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.buffer.drain(..=newline).collect();
+                return Ok(Some(String::from_utf8_lossy(&line[..newline]).into_owned()));
+            }
+            if self.end {
+                if self.buffer.is_empty() {
+                    return Ok(None);
+                }
+                let line = core::mem::take(&mut self.buffer);
+                return Ok(Some(String::from_utf8_lossy(&line).into_owned()));
+            }
+            let mut chunk = [0; CHUNK_SIZE];
+            let read = self.source.read(&mut chunk).await?;
+            self.buffer.extend_from_slice(&chunk[..read]);
+            self.end = read == 0;
+        }
+    }
+}
+
+/// Prints files or the standard input as they are, a chunk at a time.
+pub(super) async fn cmd_cat(paths: &[&str], cwd: &WorkingDirectory, io: &mut Io) -> i32 {
+    let Some(sources) = sources(paths, cwd, &mut io.stdin) else {
+        writeln!(io.stderr, "Usage: cat <file>...");
+        return MISUSE;
+    };
+    let mut status = SUCCESS;
+    for (name, mut source) in sources {
+        let mut chunk = [0; CHUNK_SIZE];
+        // bytes of a character cut off by the end of the last chunk
+        let mut kept = 0;
+        loop {
+            match source.read(&mut chunk[kept..]).await {
+                Ok(0) => {
+                    io.stdout.write_bytes(&chunk[..kept]);
+                    break;
+                }
+                Ok(read) => {
+                    let end = kept + read;
+                    let whole = without_cut_off_char(&chunk[..end]);
+                    io.stdout.write_bytes(&chunk[..whole]);
+                    chunk.copy_within(whole..end, 0);
+                    kept = end - whole;
+                    io.stdout.flush().await;
+                }
+                Err(e) => {
+                    writeln!(io.stderr, "cat: {}: {}", name, e);
+                    status = FAILURE;
+                    break;
+                }
+            }
+        }
+    }
+    status
+}
+
+/// How many bytes of `bytes` are left without a UTF-8 character that is cut
+/// off at the end.
+fn without_cut_off_char(bytes: &[u8]) -> usize {
+    // a character is at most 4 bytes long
+    for start in (bytes.len().saturating_sub(3)..bytes.len()).rev() {
+        let length = match bytes[start] {
+            0x80..=0xbf => continue,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 1,
+        };
+        return match start + length > bytes.len() {
+            true => start,
+            false => bytes.len(),
+        };
+    }
+    bytes.len()
+}
+
+/// Takes `-n N`, `-nN` or `-N` from the arguments of `head` and `tail`.
+fn line_count<'a>(args: &[&'a str]) -> Result<(usize, Vec<&'a str>), String> {
+    let mut count = DEFAULT_LINES;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        let number = match arg.strip_prefix('-') {
+            Some("n") => *args.next().ok_or("option requires an argument -- 'n'")?,
+            Some(option) if !option.is_empty() => option.strip_prefix('n').unwrap_or(option),
+            _ => {
+                paths.push(arg);
+                continue;
+            }
+        };
+        count = number
+            .parse()
+            .map_err(|_| format!("invalid number of lines: '{}'", number))?;
+    }
+    Ok((count, paths))
+}
+
+/// Prints the header `head` and `tail` put before each of several files.
+fn print_name(stdout: &mut Output, name: &str, first: bool) {
+    let separator = if first { "" } else { "\n" };
+    writeln!(stdout, "{}==> {} <==", separator, name);
+}
+
+/// Prints the first lines of files or the standard input.
+pub(super) async fn cmd_head(args: &[&str], cwd: &WorkingDirectory, io: &mut Io) -> i32 {
+    let (count, paths) = match line_count(args) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            writeln!(io.stderr, "head: {}", message);
+            return MISUSE;
+        }
+    };
+    let Some(sources) = sources(&paths, cwd, &mut io.stdin) else {
+        writeln!(io.stderr, "Usage: head [-n lines] <file>...");
+        return MISUSE;
+    };
+    let show_names = sources.len() > 1;
+    let mut status = SUCCESS;
+    for (i, (name, source)) in sources.into_iter().enumerate() {
+        if show_names {
+            print_name(&mut io.stdout, &name, i == 0);
+        }
+        let mut lines = Lines::new(source);
+        for _ in 0..count {
+            match lines.next().await {
+                Ok(Some(line)) => {
+                    writeln!(io.stdout, "{}", line);
+                    io.stdout.flush().await;
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    writeln!(io.stderr, "head: {}: {}", name, e);
+                    status = FAILURE;
+                    break;
+                }
+            }
+        }
+    }
+    status
+}
+
+/// Prints the last lines of files or the standard input. Only those lines
+/// are kept while reading.
+pub(super) async fn cmd_tail(args: &[&str], cwd: &WorkingDirectory, io: &mut Io) -> i32 {
+    let (count, paths) = match line_count(args) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            writeln!(io.stderr, "tail: {}", message);
+            return MISUSE;
+        }
+    };
+    let Some(sources) = sources(&paths, cwd, &mut io.stdin) else {
+        writeln!(io.stderr, "Usage: tail [-n lines] <file>...");
+        return MISUSE;
+    };
+    let show_names = sources.len() > 1;
+    let mut status = SUCCESS;
+    for (i, (name, source)) in sources.into_iter().enumerate() {
+        let mut lines = Lines::new(source);
+        let mut last = VecDeque::new();
+        loop {
+            match lines.next().await {
+                Ok(Some(line)) => {
+                    if last.len() == count {
+                        last.pop_front();
+                    }
+                    if count > 0 {
+                        last.push_back(line);
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    writeln!(io.stderr, "tail: {}: {}", name, e);
+                    status = FAILURE;
+                    break;
+                }
+            }
+        }
+        if show_names {
+            print_name(&mut io.stdout, &name, i == 0);
+        }
+        for line in last {
+            writeln!(io.stdout, "{}", line);
+            io.stdout.flush().await;
+        }
+    }
+    status
+}
+
+#[derive(Default)]
+struct Counts {
+    lines: usize,
+    words: usize,
+    bytes: usize,
+}
+
+impl Counts {
+    async fn of(mut source: Source<'_>) -> filesystem::Result<Self> {
+        let mut counts = Counts::default();
+        let mut in_word = false;
+        let mut chunk = [0; CHUNK_SIZE];
+        loop {
+            let read = source.read(&mut chunk).await?;
+            if read == 0 {
+                return Ok(counts);
+            }
+            counts.bytes += read;
+            for &byte in &chunk[..read] {
+                if byte == b'\n' {
+                    counts.lines += 1;
+                }
+                if byte.is_ascii_whitespace() {
+                    in_word = false;
+                } else if !in_word {
+                    counts.words += 1;
+                    in_word = true;
+                }
+            }
+        }
+    }
+}
+
+/// Counts the lines, words and bytes of files or the standard input. `-l`,
+/// `-w` and `-c` select the counts shown.
+pub(super) async fn cmd_wc(args: &[&str], cwd: &WorkingDirectory, io: &mut Io) -> i32 {
+    let (options, paths): (Vec<&str>, Vec<&str>) = args
+        .iter()
+        .partition(|arg| arg.len() > 1 && arg.starts_with('-'));
+    let (mut lines, mut words, mut bytes) = (false, false, false);
+    for option in &options {
+        for flag in option[1..].chars() {
+            match flag {
+                'l' => lines = true,
+                'w' => words = true,
+                'c' => bytes = true,
+                flag => {
+                    writeln!(io.stderr, "wc: invalid option -- '{}'", flag);
+                    return MISUSE;
+                }
+            }
+        }
+    }
+    if !(lines || words || bytes) {
+        (lines, words, bytes) = (true, true, true);
+    }
+    let Some(sources) = sources(&paths, cwd, &mut io.stdin) else {
+        writeln!(io.stderr, "Usage: wc [-lwc] <file>...");
+        return MISUSE;
+    };
+
+    let count = sources.len();
+    let mut total = Counts::default();
+    let mut status = SUCCESS;
+    let print = |stdout: &mut Output, counts: &Counts, name: &str| {
+        let columns = [
+            (lines, counts.lines),
+            (words, counts.words),
+            (bytes, counts.bytes),
+        ];
+        for (_, value) in columns.iter().filter(|(shown, _)| *shown) {
+            write!(stdout, "{:>7} ", value);
+        }
+        writeln!(stdout, "{}", name);
+    };
+    for (name, source) in sources {
+        match Counts::of(source).await {
+            Ok(counts) => {
+                print(&mut io.stdout, &counts, &name);
+                total.lines += counts.lines;
+                total.words += counts.words;
+                total.bytes += counts.bytes;
+            }
+            Err(e) => {
+                writeln!(io.stderr, "wc: {}: {}", name, e);
+                status = FAILURE;
+            }
+        }
+    }
+    if count > 1 {
+        print(&mut io.stdout, &total, "total");
+    }
+    status
+}
+
+/// Prints the lines of files or the standard input that match a pattern.
+/// `-i` ignores case, `-v` selects the lines that do not match, `-n` adds
+/// line numbers and `-c` only counts. Fails if no line was selected.
+pub(super) async fn cmd_grep(args: &[&str], cwd: &WorkingDirectory, io: &mut Io) -> i32 {
+    let (mut ignore_case, mut invert, mut numbers, mut count_only) = (false, false, false, false);
+    let mut args = args.iter().copied().peekable();
+    while let Some(option) = args.next_if(|arg| arg.len() > 1 && arg.starts_with('-')) {
+        for flag in option[1..].chars() {
+            match flag {
+                'i' => ignore_case = true,
+                'v' => invert = true,
+                'n' => numbers = true,
+                'c' => count_only = true,
+                flag => {
+                    writeln!(io.stderr, "grep: invalid option -- '{}'", flag);
+                    return MISUSE;
+                }
+            }
+        }
+    }
+    let Some(pattern) = args.next() else {
+        writeln!(io.stderr, "Usage: grep [-ivnc] <pattern> <file>...");
+        return MISUSE;
+    };
+    let regex = match Regex::new(pattern, ignore_case) {
+        Ok(regex) => regex,
+        Err(e) => {
+            writeln!(io.stderr, "grep: {}", e);
+            return MISUSE;
+        }
+    };
+    let paths: Vec<&str> = args.collect();
+    let Some(sources) = sources(&paths, cwd, &mut io.stdin) else {
+        writeln!(io.stderr, "Usage: grep [-ivnc] <pattern> <file>...");
+        return MISUSE;
+    };
+
+    let show_names = sources.len() > 1;
+    let mut selected = 0;
+    let mut error = false;
+    for (name, source) in sources {
+        let prefix = if show_names {
+            format!("{}:", name)
+        } else {
+            String::new()
+        };
+        let mut lines = Lines::new(source);
+        let mut matches = 0;
+        for number in 1.. {
+            let line = match lines.next().await {
+                Ok(Some(line)) => line,
+                Ok(None) => break,
+                Err(e) => {
+                    writeln!(io.stderr, "grep: {}: {}", name, e);
+                    error = true;
+                    break;
+                }
+            };
+            if regex.is_match(&line) == invert {
+                continue;
+            }
+            matches += 1;
+            if count_only {
+                continue;
+            }
+            match numbers {
+                true => writeln!(io.stdout, "{}{}:{}", prefix, number, line),
+                false => writeln!(io.stdout, "{}{}", prefix, line),
+            }
+            io.stdout.flush().await;
+        }
+        if count_only {
+            writeln!(io.stdout, "{}{}", prefix, matches);
+        }
+        selected += matches;
+    }
+    match (error, selected) {
+        (true, _) => MISUSE,
+        (false, 0) => FAILURE,
+        (false, _) => SUCCESS,
+    }
+}
+
+/// Layout of the lines of a hex dump.
+#[derive(Clone, Copy)]
+pub(super) enum DumpFormat {
+    /// `hexdump -C`: bytes one by one, in two groups of eight.
+    Canonical,
+    /// `xxd`: bytes in pairs.
+    Xxd,
+}
+
+/// Prints files or the standard input in hexadecimal next to their printable
+/// characters, one line at a time.
+pub(super) async fn cmd_hexdump(
+    format: DumpFormat,
+    args: &[&str],
+    cwd: &WorkingDirectory,
+    io: &mut Io,
+) -> i32 {
+    // the canonical format is the only one, so `-C` can be left out
+    let paths: Vec<&str> = args.iter().copied().filter(|arg| *arg != "-C").collect();
+    let Some(sources) = sources(&paths, cwd, &mut io.stdin) else {
+        writeln!(io.stderr, "Usage: hexdump [-C] <file>... | xxd <file>");
+        return MISUSE;
+    };
+
+    let mut offset = 0;
+    let mut line = Vec::with_capacity(BYTES_PER_LINE);
+    let mut status = SUCCESS;
+    // like `hexdump`, several files are dumped as if they were one
+    for (name, mut source) in sources {
+        let mut chunk = [0; CHUNK_SIZE];
+        loop {
+            let read = match source.read(&mut chunk).await {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) => {
+                    writeln!(io.stderr, "hexdump: {}: {}", name, e);
+                    status = FAILURE;
+                    break;
+                }
+            };
+            for &byte in &chunk[..read] {
+                line.push(byte);
+                if line.len() == BYTES_PER_LINE {
+                    print_dump_line(&mut io.stdout, format, offset, &line);
+                    offset += line.len();
+                    line.clear();
+                }
+            }
+            io.stdout.flush().await;
+        }
+    }
+    if !line.is_empty() {
+        print_dump_line(&mut io.stdout, format, offset, &line);
+        offset += line.len();
+    }
+    if let DumpFormat::Canonical = format
+        && offset > 0
+    {
+        writeln!(io.stdout, "{:08x}", offset);
+    }
+    status
+}
+
+fn print_dump_line(stdout: &mut Output, format: DumpFormat, offset: usize, bytes: &[u8]) {
+    let text: String = bytes
+        .iter()
+        .map(|&b| match b {
+            0x20..=0x7e => b as char,
+            _ => '.',
+        })
+        .collect();
+    let mut hex = String::new();
+    for i in 0..BYTES_PER_LINE {
+        let byte = bytes
+            .get(i)
+            .map_or(String::from("  "), |b| format!("{:02x}", b));
+        match format {
+            DumpFormat::Canonical => {
+                if i == BYTES_PER_LINE / 2 {
+                    hex.push(' ');
+                }
+                hex.push_str(&byte);
+                hex.push(' ');
+            }
+            DumpFormat::Xxd => {
+                if i > 0 && i % 2 == 0 {
+                    hex.push(' ');
+                }
+                hex.push_str(&byte);
+            }
+        }
+    }
+    match format {
+        DumpFormat::Canonical => writeln!(stdout, "{:08x}  {} |{}|", offset, hex, text),
+        DumpFormat::Xxd => writeln!(stdout, "{:08x}: {}  {}", offset, hex, text),
+    }
+}
+
+/// What `find` looks for.
+#[derive(Default)]
+struct FindFilter {
+    name: Option<Regex>,
+    file_type: Option<FileType>,
+    size: Option<(core::cmp::Ordering, usize)>,
+}
+
+impl FindFilter {
+    fn matches(&self, name: &str, file_type: FileType, size: usize) -> bool {
+        self.name
+            .as_ref()
+            .is_none_or(|pattern| pattern.is_match(name))
+            && self.file_type.is_none_or(|expected| expected == file_type)
+            && self
+                .size
+                .is_none_or(|(ordering, limit)| size.cmp(&limit) == ordering)
+    }
+}
+
+/// Parses the `-size` argument: a number of bytes, optionally with a `k` or
+/// `M` suffix, and `+` for more or `-` for less. `None` if it is invalid or
+/// does not fit in a `usize`.
+fn parse_size(size: &str) -> Option<(core::cmp::Ordering, usize)> {
+    let (ordering, size) = match size.as_bytes().first() {
+        Some(b'+') => (core::cmp::Ordering::Greater, &size[1..]),
+        Some(b'-') => (core::cmp::Ordering::Less, &size[1..]),
+        _ => (core::cmp::Ordering::Equal, size),
+    };
+    let (number, unit) = match size.char_indices().last()? {
+        (i, 'c') => (&size[..i], 1),
+        (i, 'k') => (&size[..i], 1024),
+        (i, 'M') => (&size[..i], 1024 * 1024),
+        _ => (size, 1),
+    };
+    Some((ordering, number.parse::<usize>().ok()?.checked_mul(unit)?))
+}
+
+/// Walks directories and prints the entries that match `-name`, `-type` and
+/// `-size`. Only the metadata is read.
+pub(super) fn cmd_find(args: &[&str], cwd: &WorkingDirectory, io: &mut Io) -> i32 {
+    let mut filter = FindFilter::default();
+    let mut starts = Vec::new();
+    let mut args = args.iter().copied();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') {
+            starts.push(arg);
+            continue;
+        }
+        let Some(value) = args.next() else {
+            writeln!(io.stderr, "find: missing argument to '{}'", arg);
+            return MISUSE;
+        };
+        let valid = match arg {
+            "-name" => Regex::glob(value)
+                .map(|glob| filter.name = Some(glob))
+                .is_ok(),
+            "-type" => {
+                filter.file_type = match value {
+                    "f" => Some(FileType::File),
+                    "d" => Some(FileType::Dir),
+                    "l" => Some(FileType::SymLink),
+                    _ => None,
+                };
+                filter.file_type.is_some()
+            }
+            "-size" => {
+                filter.size = parse_size(value);
+                filter.size.is_some()
+            }
+            _ => {
+                writeln!(io.stderr, "find: unknown predicate '{}'", arg);
+                return MISUSE;
+            }
+        };
+        if !valid {
+            writeln!(io.stderr, "find: invalid argument '{}' to '{}'", value, arg);
+            return MISUSE;
+        }
+    }
+    if starts.is_empty() {
+        starts.push(".");
+    }
+
+    let mut status = SUCCESS;
+    for start in starts {
+        let absolute = cwd.resolve(start);
+        let metadata = match with_filesystem(|fs| fs.metadata(&absolute)) {
+            Some(Ok(metadata)) => metadata,
+            // the ramdisk may have no entry for its root
+            _ if absolute == "/" => filesystem::FileMetadata {
+                path: String::new(),
+                size: 0,
+                file_type: FileType::Dir,
+            },
+            Some(Err(e)) => {
+                writeln!(io.stderr, "find: '{}': {}", start, e);
+                status = FAILURE;
+                continue;
+            }
+            None => {
+                writeln!(io.stderr, "find: filesystem not initialized");
+                return FAILURE;
+            }
+        };
+        let name = start
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or(start);
+        if filter.matches(name, metadata.file_type, metadata.size) {
+            writeln!(io.stdout, "{}", start);
+        }
+        if metadata.file_type != FileType::Dir {
+            continue;
+        }
+
+        let mut directories = alloc::vec![(start.to_string(), absolute)];
+        while let Some((shown, absolute)) = directories.pop() {
+            let mut entries = match with_filesystem(|fs| fs.read_dir(&absolute)) {
+                Some(Ok(entries)) => entries,
+                _ => {
+                    writeln!(io.stderr, "find: '{}': cannot read directory", shown);
+                    status = FAILURE;
+                    continue;
+                }
+            };
+            entries.sort_by(|a, b| a.name().cmp(b.name()));
+            let mut subdirectories = Vec::new();
+            for entry in &entries {
+                let child = join(&shown, entry.name());
+                if filter.matches(entry.name(), entry.file_type, entry.size) {
+                    writeln!(io.stdout, "{}", child);
+                }
+                if entry.file_type == FileType::Dir {
+                    subdirectories.push((child, join(&absolute, entry.name())));
+                }
+            }
+            // popped in order, so each directory is listed before the next
+            directories.extend(subdirectories.into_iter().rev());
+        }
+    }
+    status
+}
+
+fn join(directory: &str, name: &str) -> String {
+    match directory.ends_with('/') {
+        true => format!("{}{}", directory, name),
+        false => format!("{}/{}", directory, name),
+    }
+}
+
+/// Prints the size and type of files. Symbolic links are shown themselves,
+/// with their target.
+pub(super) fn cmd_stat(paths: &[&str], cwd: &WorkingDirectory, io: &mut Io) -> i32 {
+    if paths.is_empty() {
+        writeln!(io.stderr, "Usage: stat <file>...");
+        return MISUSE;
+    }
+    let mut status = SUCCESS;
+    for path in paths {
+        let absolute = cwd.resolve(path);
+        let result = with_filesystem(|fs| {
+            let metadata = fs.symlink_metadata(&absolute)?;
+            let target = match metadata.file_type {
+                FileType::SymLink => Some(fs.read_link(&absolute)?),
+                _ => None,
+            };
+            filesystem::Result::Ok((metadata, target))
+        });
+        match result {
+            Some(Ok((metadata, target))) => {
+                match target {
+                    Some(target) => writeln!(io.stdout, "  File: {} -> {}", path, target),
+                    None => writeln!(io.stdout, "  File: {}", path),
+                }
+                let file_type = match metadata.file_type {
+                    FileType::File => "regular file",
+                    FileType::Dir => "directory",
+                    FileType::SymLink => "symbolic link",
+                    FileType::HardLink => "hard link",
+                };
+                writeln!(
+                    io.stdout,
+                    "  Size: {:<10} Type: {}",
+                    metadata.size, file_type
+                );
+            }
+            Some(Err(e)) => {
+                writeln!(io.stderr, "stat: {}: {}", path, e);
+                status = FAILURE;
+            }
+            None => {
+                writeln!(io.stderr, "stat: filesystem not initialized");
+                return FAILURE;
+            }
+        }
+    }
+    status
+}
+
+#[test_case]
+fn test_without_cut_off_char() {
+    assert_eq!(without_cut_off_char(b"abc"), 3);
+    assert_eq!(without_cut_off_char("a\u{e4}".as_bytes()), 3);
+    assert_eq!(without_cut_off_char(&"a\u{e4}".as_bytes()[..2]), 1);
+    assert_eq!(without_cut_off_char(&"\u{1f600}".as_bytes()[..3]), 0);
+    assert_eq!(without_cut_off_char(&[b'a', 0xff]), 2);
+}
+
+#[test_case]
+fn test_line_count() {
+    assert_eq!(
+        line_count(&["a.txt"]),
+        Ok((DEFAULT_LINES, alloc::vec!["a.txt"]))
+    );
+    assert_eq!(
+        line_count(&["-n", "3", "a.txt"]),
+        Ok((3, alloc::vec!["a.txt"]))
+    );
+    assert_eq!(line_count(&["-n5"]), Ok((5, Vec::new())));
+    assert_eq!(
+        line_count(&["-2", "a", "b"]),
+        Ok((2, alloc::vec!["a", "b"]))
+    );
+    assert!(line_count(&["-n"]).is_err());
+    assert!(line_count(&["-x"]).is_err());
+}
+
+#[test_case]
+fn test_parse_size() {
+    use core::cmp::Ordering;
+    assert_eq!(parse_size("100"), Some((Ordering::Equal, 100)));
+    assert_eq!(parse_size("+2k"), Some((Ordering::Greater, 2048)));
+    assert_eq!(parse_size("-1M"), Some((Ordering::Less, 1024 * 1024)));
+    assert_eq!(parse_size("12c"), Some((Ordering::Equal, 12)));
+    assert_eq!(parse_size("k"), None);
+    assert_eq!(parse_size(&format!("{}M", usize::MAX / 1024)), None);
+    assert_eq!(parse_size(""), None);
+}
@@ -0,0 +1,107 @@
+use alloc::{collections::VecDeque, string::String};
+
+use crate::filesystem::{self, with_filesystem};
+
+/// Number of commands kept in the history.
+const HISTORY_CAPACITY: usize = 100;
+
+/// Previously executed commands. Entries are numbered from 1 on, the number
+/// of an entry does not change when older entries are dropped.
+#[derive(Clone)]
+pub struct History {
+    entries: VecDeque<String>,
+    /// Number of the oldest entry.
+    first_number: usize,
+}
+
+impl History {
+    pub fn new() -> Self {
+        History {
+            entries: VecDeque::new(),
+            first_number: 1,
+        }
+    }
+
+    /// Reads the history from `path`, an unreadable file gives an empty history.
+    pub fn load(path: &str) -> Self {
+        let mut history = History::new();
+        if let Some(Ok(content)) = with_filesystem(|fs| fs.read_to_string(path)) {
+            content.lines().for_each(|line| history.push(line));
+        }
+        history
+    }
+
+    /// Writes the history to `path`, one command per line.
+    pub fn save(&self, path: &str) -> filesystem::Result<()> {
+        let mut content = String::new();
+        for entry in &self.entries {
+            content.push_str(entry);
+            content.push('\n');
+        }
+        with_filesystem(|fs| fs.write(path, content.as_bytes())).unwrap_or(Ok(()))
+    }
+
+    /// Appends `command` unless it is empty or repeats the last entry.
+    pub fn push(&mut self, command: &str) {
+        let command = command.trim();
+        if command.is_empty() || self.entries.back().is_some_and(|last| last == command) {
+            return;
+        }
+        if self.entries.len() == HISTORY_CAPACITY {
+            self.entries.pop_front();
+            self.first_number += 1;
+        }
+        self.entries.push_back(String::from(command));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Entry at `index`, counted from the oldest entry that is still kept.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(String::as_str)
+    }
+
+    /// Entry with the number shown by the `history` command.
+    pub fn by_number(&self, number: usize) -> Option<&str> {
+        self.get(number.checked_sub(self.first_number)?)
+    }
+
+    pub fn last(&self) -> Option<&str> {
+        self.entries.back().map(String::as_str)
+    }
+
+    /// Entries with their numbers, oldest first.
+    pub fn numbered(&self) -> impl Iterator<Item = (usize, &str)> {
+        (self.first_number..).zip(self.entries.iter().map(String::as_str))
+    }
+
+    /// Index of the newest entry before `before` that contains `query`.
+    pub fn search(&self, query: &str, before: usize) -> Option<usize> {
+        (0..before.min(self.len()))
+            .rev()
+            .find(|&index| self.entries[index].contains(query))
+    }
+}
+
+#[test_case]
+fn test_history_numbers_and_search() {
+    let mut history = History::new();
+    for i in 0..HISTORY_CAPACITY + 2 {
+        history.push(&alloc::format!("echo {}", i));
+    }
+    history.push(&alloc::format!("echo {}", HISTORY_CAPACITY + 1));
+    history.push("   ");
+
+    assert_eq!(history.len(), HISTORY_CAPACITY);
+    assert_eq!(history.by_number(1), None);
+    assert_eq!(history.by_number(3), Some("echo 2"));
+    assert_eq!(history.numbered().next(), Some((3, "echo 2")));
+    assert_eq!(
+        history.search("echo 1", history.len()),
+        Some(HISTORY_CAPACITY - 1)
+    );
+    assert_eq!(history.search("echo 2", 1), Some(0));
+    assert_eq!(history.search("missing", history.len()), None);
+}
@@ -0,0 +1,217 @@
+use alloc::{fmt, string::String, vec::Vec};
+
+use crate::filesystem::{self, with_filesystem};
+use crate::print;
+use crate::task::sync::pipe::{BrokenPipe, PipeReader, PipeWriter};
+
+/// How many bytes are read from a file at a time.
+pub(super) const CHUNK_SIZE: usize = 512;
+
+/// Standard input, output and error of a command.
+pub struct Io {
+    pub stdin: Stdin,
+    pub stdout: Output,
+    pub stderr: Output,
+}
+
+impl Io {
+    pub fn console() -> Self {
+        Io {
+            stdin: Stdin::Console,
+            stdout: Output::Console,
+            stderr: Output::Console,
+        }
+    }
+}
+
+pub enum Stdin {
+    /// Nothing was redirected, interactive commands read the keyboard.
+    Console,
+    Pipe(PipeReader),
+    /// A file redirected with `<`, read a chunk at a time.
+    File {
+        path: String,
+        position: usize,
+    },
+    /// The command before in the pipeline wrote into a file instead.
+    Empty,
+}
+
+impl Stdin {
+    /// Everything the input provides, `None` for the console.
+    pub async fn read_to_end(&mut self) -> Option<Vec<u8>> {
+        match self {
+            Stdin::Console => None,
+            Stdin::Pipe(reader) => Some(reader.read_to_end().await),
+            Stdin::File { .. } => {
+                let mut bytes = Vec::new();
+                let mut chunk = [0; CHUNK_SIZE];
+                while let Some(read @ 1..) = self.read(&mut chunk).await {
+                    bytes.extend_from_slice(&chunk[..read]);
+                }
+                Some(bytes)
+            }
+            Stdin::Empty => Some(Vec::new()),
+        }
+    }
+
+    /// Reads up to `buffer.len()` bytes, 0 at the end of the input or if
+    /// the file can no longer be read. `None` for the console.
+    pub async fn read(&mut self, buffer: &mut [u8]) -> Option<usize> {
+        match self {
+            Stdin::Console => None,
+            Stdin::Pipe(reader) => Some(reader.read(buffer).await),
+            Stdin::File { path, position } => {
+                let read = with_filesystem(|fs| fs.read_into(path, *position, buffer))
+                    .and_then(Result::ok)
+                    .unwrap_or(0);
+                *position += read;
+                Some(read)
+            }
+            Stdin::Empty => Some(0),
+        }
+    }
+}
+
+pub enum Output {
+    Console,
+    /// Collects the output and passes it into the pipe a chunk at a time.
+    Pipe {
+        writer: PipeWriter,
+        pending: Vec<u8>,
+    },
+    /// Collects the output and writes it to the file a chunk at a time.
+    File {
+        path: String,
+        /// Set once the first chunk replaced the old content of the file.
+        append: bool,
+        content: Vec<u8>,
+        /// The first write that failed, reported by [`Output::finish`].
+        error: Option<filesystem::Error>,
+    },
+}
+
+impl Output {
+    pub fn pipe(writer: PipeWriter) -> Self {
+        Output::Pipe {
+            writer,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn file(path: &str, append: bool) -> Self {
+        Output::File {
+            path: String::from(path),
+            append,
+            content: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Writes formatted output, so `write!` and `writeln!` need no error
+    /// handling.
+    pub fn write_fmt(&mut self, args: fmt::Arguments) {
+        match self {
+            Output::Console => print!("{}", args),
+            Output::Pipe { pending, .. }
+            | Output::File {
+                content: pending, ..
+            } => match args.as_str() {
+                Some(s) => pending.extend_from_slice(s.as_bytes()),
+                None => pending.extend_from_slice(fmt::format(args).as_bytes()),
+            },
+        }
+        self.pass_on();
+    }
+
+    pub fn write_str(&mut self, s: &str) {
+        self.write_fmt(format_args!("{}", s));
+    }
+
+    /// Writes bytes that need not be UTF-8. The console shows invalid ones
+    /// as replacement characters.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        match self {
+            Output::Console => print!("{}", String::from_utf8_lossy(bytes)),
+            Output::Pipe { pending, .. }
+            | Output::File {
+                content: pending, ..
+            } => pending.extend_from_slice(bytes),
+        }
+        self.pass_on();
+    }
+
+    /// Passes on the collected output once there is a chunk of it. A pipe
+    /// takes only what fits, the rest waits for [`Output::flush`].
+    fn pass_on(&mut self) {
+        match self {
+            Output::Console => {}
+            Output::Pipe { writer, pending } => {
+                if pending.len() >= CHUNK_SIZE {
+                    match writer.try_write(pending) {
+                        Ok(written) => drop(pending.drain(..written)),
+                        Err(BrokenPipe) => pending.clear(),
+                    }
+                }
+            }
+            Output::File { content, .. } => {
+                if content.len() >= CHUNK_SIZE {
+                    self.write_file();
+                }
+            }
+        }
+    }
+
+    /// Writes the collected output of a file redirection to the filesystem.
+    /// After a failed write the output is dropped.
+    fn write_file(&mut self) {
+        let Output::File {
+            path,
+            append,
+            content,
+            error,
+        } = self
+        else {
+            return;
+        };
+        if error.is_none() {
+            let result = with_filesystem(|fs| match append {
+                true => fs.append(path, content),
+                false => fs.write(path, content),
+            });
+            match result {
+                Some(Ok(())) => *append = true,
+                Some(Err(e)) => *error = Some(e),
+                None => *error = Some(filesystem::Error::NotFound),
+            }
+        }
+        content.clear();
+    }
+
+    /// Passes the output collected for a pipe on, waiting while the pipe is
+    /// full. Commands that write a lot call this between chunks so they
+    /// don't get ahead of the reader. Output into a pipe whose reader is
+    /// gone is dropped.
+    pub async fn flush(&mut self) {
+        if let Output::Pipe { writer, pending } = self {
+            let _ = writer.write(pending).await;
+            pending.clear();
+        }
+    }
+
+    /// Passes on what is left of the output. A pipe is closed and the rest
+    /// of a file redirection is written to the filesystem.
+    pub async fn finish(mut self) -> Result<(), (String, filesystem::Error)> {
+        self.flush().await;
+        // also creates or truncates the file when nothing was written
+        self.write_file();
+        match self {
+            Output::File {
+                path,
+                error: Some(e),
+                ..
+            } => Err((path, e)),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,420 @@
+use crate::filesystem::{self, FileType, with_filesystem};
+use crate::input::keyboard::KeyEvents;
+use crate::input::line_editor::LineEditor;
+use crate::input::terminal::TerminalKeys;
+use crate::{config, console, print, println};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use futures_util::StreamExt;
+use futures_util::future::{Either, select};
+use pc_keyboard::{DecodedKey, KeyCode};
+
+mod commands;
+mod cwd;
+mod env;
+mod fileutils;
+mod history;
+mod io;
+mod pager;
+mod parser;
+mod pattern;
+mod script;
+
+use cwd::WorkingDirectory;
+use env::Environment;
+use history::History;
+use io::Io;
+
+const COMMANDS: &[&str] = &[
+    "help", "echo", "cat", "ls", "version", "clear", "exec", "jobs", "top", "loadkeys", "dmesg",
+    "history", "set", "unset", "env", "export", "source", "sh", "exit", "true", "false", "test",
+    "cd", "pwd", "pushd", "popd", "head", "tail", "wc", "grep", "hexdump", "xxd", "find", "stat",
+    "less", "more",
+];
+
+/// Where the history is kept if `SHELL_HISTORY_FILE` is not set.
+const DEFAULT_HISTORY_FILE: &str = "/tmp/.history";
+
+/// Script run when the shell starts, before the first prompt.
+const STARTUP_SCRIPT: &str = "/etc/rc";
+
+// control characters of Ctrl+letter key combinations
+const CTRL_C: char = '\x03';
+const CTRL_G: char = '\x07';
+const CTRL_R: char = '\x12';
+const ESCAPE: char = '\x1b';
+
+pub async fn run() {
+    let mut shell = Shell::new();
+    // the startup script runs once, not for every console
+    if console::current() == console::SHELL_CONSOLE
+        && with_filesystem(|fs| fs.metadata(STARTUP_SCRIPT).is_ok()) == Some(true)
+    {
+        script::run_file(STARTUP_SCRIPT, &mut shell, &mut Io::console()).await;
+    }
+
+    loop {
+        shell.print_prompt();
+        let Some(line) = shell.read_line().await else {
+            break;
+        };
+        let Some(command) = shell.expand_history(&line) else {
+            continue;
+        };
+        shell.history.push(&command);
+        shell.save_history();
+        script::execute(&command, &mut shell).await;
+    }
+}
+
+struct Shell {
+    input: Input,
+    history: History,
+    history_file: String,
+    env: Environment,
+    cwd: WorkingDirectory,
+    /// How many scripts are running inside each other.
+    script_depth: usize,
+    /// Status passed to `exit`, the running script ends when it is set.
+    exit: Option<i32>,
+}
+
+/// How a reverse search was left.
+enum SearchResult {
+    /// Enter was pressed, the line is executed.
+    Execute(String),
+    /// The line is edited further.
+    Edit(String),
+}
+
+impl Shell {
+    fn new() -> Self {
+        let history_file = config::get("SHELL_HISTORY_FILE").unwrap_or(DEFAULT_HISTORY_FILE);
+        // the shells on the other consoles keep their history apart
+        let history_file = match console::current() {
+            console::SHELL_CONSOLE => String::from(history_file),
+            console => format!("{}.{}", history_file, console + 1),
+        };
+        Shell {
+            input: Input::new(),
+            history: History::load(&history_file),
+            history_file,
+            env: Environment::new(),
+            cwd: WorkingDirectory::new(),
+            script_depth: 0,
+            exit: None,
+        }
+    }
+
+    /// A copy to run a command of a pipeline other than the last one in, as
+    /// a subshell. What the command changes stays in the copy, and it reads
+    /// no keys.
+    fn pipeline_stage(&self) -> Self {
+        Shell {
+            input: Input::none(),
+            history: self.history.clone(),
+            history_file: self.history_file.clone(),
+            env: self.env.clone(),
+            cwd: self.cwd.clone(),
+            script_depth: self.script_depth,
+            exit: None,
+        }
+    }
+
+    fn in_script(&self) -> bool {
+        self.script_depth > 0
+    }
+
+    /// Prints the prompt with the current directory.
+    fn print_prompt(&self) {
+        print!("{}> ", self.cwd.get());
+    }
+
+    /// Reads a line, Up and Down browse the history and Ctrl-R searches it.
+    async fn read_line(&mut self) -> Option<String> {
+        let mut editor = LineEditor::new();
+        // index of the history entry shown, `history.len()` is the new line
+        let mut position = self.history.len();
+        let mut draft = String::new();
+
+        loop {
+            let Some(key) = editor.handle_key(self.input.next_key().await?) else {
+                continue;
+            };
+            match key {
+                DecodedKey::Unicode('\n') => {
+                    let line = editor.finish();
+                    println!();
+                    return Some(line);
+                }
+                DecodedKey::Unicode(CTRL_C) => {
+                    editor.finish();
+                    println!("^C");
+                    return Some(String::new());
+                }
+                DecodedKey::Unicode('\t') => self.autocomplete(&mut editor),
+                DecodedKey::Unicode(CTRL_R) => {
+                    clear_line();
+                    let line = match self.reverse_search(editor.text()).await? {
+                        SearchResult::Execute(found) => {
+                            self.print_prompt();
+                            println!("{}", found);
+                            return Some(found);
+                        }
+                        SearchResult::Edit(found) => found,
+                    };
+                    self.print_prompt();
+                    editor = LineEditor::new();
+                    editor.set_text(&line);
+                    position = self.history.len();
+                }
+                DecodedKey::RawKey(KeyCode::ArrowUp) if position > 0 => {
+                    if position == self.history.len() {
+                        draft = editor.text();
+                    }
+                    position -= 1;
+                    editor.set_text(self.history.get(position).unwrap_or_default());
+                }
+                DecodedKey::RawKey(KeyCode::ArrowDown) if position < self.history.len() => {
+                    position += 1;
+                    editor.set_text(self.history.get(position).unwrap_or(&draft));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Searches the history for entries containing the typed text, newest
+    /// first. Ctrl-R continues with older entries, Escape, Ctrl-G or Ctrl-C
+    /// return to `line`.
+    async fn reverse_search(&mut self, line: String) -> Option<SearchResult> {
+        let mut query = String::new();
+        let mut found: Option<usize> = None;
+        self.print_search(&query, found);
+
+        loop {
+            let newest = self.history.len();
+            match self.input.next_key().await? {
+                DecodedKey::Unicode(CTRL_R) => {
+                    let before = found.unwrap_or(newest);
+                    found = self.history.search(&query, before).or(found);
+                }
+                DecodedKey::Unicode('\x08') => {
+                    query.pop();
+                    found = self.history.search(&query, newest);
+                }
+                DecodedKey::Unicode('\n') => {
+                    clear_line();
+                    let found = found.and_then(|index| self.history.get(index));
+                    return Some(SearchResult::Execute(found.map_or(line, String::from)));
+                }
+                DecodedKey::Unicode(CTRL_C | CTRL_G | ESCAPE)
+                | DecodedKey::RawKey(KeyCode::Escape) => {
+                    clear_line();
+                    return Some(SearchResult::Edit(line));
+                }
+                DecodedKey::Unicode(c) if !c.is_control() => {
+                    query.push(c);
+                    // the current match may still contain the longer query
+                    let before = found.map_or(newest, |index| index + 1);
+                    found = self.history.search(&query, before);
+                }
+                DecodedKey::RawKey(
+                    KeyCode::ArrowLeft
+                    | KeyCode::ArrowRight
+                    | KeyCode::ArrowUp
+                    | KeyCode::ArrowDown
+                    | KeyCode::Home
+                    | KeyCode::End,
+                ) => {
+                    clear_line();
+                    let found = found.and_then(|index| self.history.get(index));
+                    return Some(SearchResult::Edit(found.map_or(line, String::from)));
+                }
+                _ => continue,
+            }
+            clear_line();
+            self.print_search(&query, found);
+        }
+    }
+
+    fn print_search(&self, query: &str, found: Option<usize>) {
+        let entry = found.and_then(|index| self.history.get(index));
+        let prompt = match entry {
+            Some(entry) => format!("(reverse-i-search)`{}': {}", query, entry),
+            None if query.is_empty() => String::from("(reverse-i-search)`': "),
+            None => format!("(failed reverse-i-search)`{}': ", query),
+        };
+        print!("{}", prompt);
+    }
+
+    /// Replaces a leading `!n` with history entry `n` and `!!` with the last
+    /// entry. Prints the expanded command.
+    fn expand_history(&self, line: &str) -> Option<String> {
+        let line = line.trim();
+        let Some(designator) = line.strip_prefix('!') else {
+            return Some(String::from(line));
+        };
+        let (event, rest) = designator
+            .split_once(char::is_whitespace)
+            .unwrap_or((designator, ""));
+        let entry = match event {
+            "!" => self.history.last(),
+            number => number.parse().ok().and_then(|n| self.history.by_number(n)),
+        };
+        let Some(entry) = entry else {
+            println!("!{}: event not found", event);
+            return None;
+        };
+        let mut command = String::from(entry);
+        if !rest.is_empty() {
+            command.push(' ');
+            command.push_str(rest);
+        }
+        println!("{}", command);
+        Some(command)
+    }
+
+    fn save_history(&self) {
+        match self.history.save(&self.history_file) {
+            Ok(()) | Err(filesystem::Error::PermissionDenied | filesystem::Error::NotFound) => {}
+            Err(e) => log::debug!("saving history to {} failed: {:?}", self.history_file, e),
+        }
+    }
+
+    /// Completes the command or path in front of the cursor.
+    fn autocomplete(&self, editor: &mut LineEditor) {
+        let buffer = editor.text_before_cursor();
+        let args: Vec<&str> = buffer.split_whitespace().collect();
+        let is_new_arg = buffer.ends_with(' ');
+
+        if args.is_empty() || (args.len() == 1 && !is_new_arg) {
+            let prefix = if args.is_empty() { "" } else { args[0] };
+
+            let matches: Vec<&str> = COMMANDS
+                .iter()
+                .copied()
+                .filter(|c| c.starts_with(prefix))
+                .collect();
+
+            if matches.len() == 1 {
+                let completion = matches[0];
+                let remaining = &completion[prefix.len()..];
+                editor.insert_str(remaining);
+                editor.insert(' ');
+            } else if matches.len() > 1 {
+                println!();
+                for m in matches {
+                    print!("{} ", m);
+                }
+                println!();
+                self.print_prompt();
+                editor.draw();
+            }
+        } else {
+            let last_arg = if is_new_arg { "" } else { args.last().unwrap() };
+
+            let (dir, file_prefix) = if let Some(idx) = last_arg.rfind('/') {
+                let d = &last_arg[..idx];
+                let f = &last_arg[idx + 1..];
+                (if d.is_empty() { "/" } else { d }, f)
+            } else {
+                ("", last_arg)
+            };
+            let dir = self.cwd.resolve(dir);
+
+            let matches = with_filesystem(|fs| {
+                if let Ok(entries) = fs.read_dir(&dir) {
+                    entries
+                        .into_iter()
+                        .filter(|e| e.name().starts_with(file_prefix))
+                        .map(|e| (String::from(e.name()), e.file_type))
+                        .collect::<Vec<_>>()
+                } else {
+                    Vec::new()
+                }
+            });
+
+            if let Some(matches) = matches {
+                if matches.len() == 1 {
+                    let (name, ftype) = &matches[0];
+                    let remaining = &name[file_prefix.len()..];
+                    editor.insert_str(remaining);
+                    if *ftype == FileType::Dir {
+                        editor.insert('/');
+                    }
+                } else if matches.len() > 1 {
+                    println!();
+                    for (name, ftype) in matches {
+                        print!("{}{}", name, if ftype == FileType::Dir { "/" } else { "" });
+                        print!(" ");
+                    }
+                    println!();
+                    self.print_prompt();
+                    editor.draw();
+                }
+            }
+        }
+    }
+}
+
+/// Erases the line the cursor is in.
+fn clear_line() {
+    print!("\r\x1b[K");
+}
+
+/// Keyboard and serial input of the shell. Programs started by the shell
+/// take the keyboard focus while they run.
+struct Input {
+    /// `None` in the stages of a pipeline that do not read keys.
+    events: Option<KeyEvents>,
+    /// Only the shell on the first console reads the serial port.
+    terminal: Option<TerminalKeys>,
+}
+
+impl Input {
+    fn new() -> Self {
+        Input {
+            events: Some(KeyEvents::focus()),
+            terminal: (console::current() == console::SHELL_CONSOLE).then(TerminalKeys::new),
+        }
+    }
+
+    /// Input without keys, reading it ends right away.
+    fn none() -> Self {
+        Input {
+            events: None,
+            terminal: None,
+        }
+    }
+
+    async fn next_key(&mut self) -> Option<DecodedKey> {
+        loop {
+            let Some(terminal) = self.terminal.as_mut() else {
+                return self.next_keyboard_key().await;
+            };
+            let events = self.events.as_mut()?;
+            match select(events.next(), terminal.next()).await {
+                Either::Left((Some(event), _)) => {
+                    if let Some(key) = event.pressed() {
+                        return Some(key);
+                    }
+                }
+                Either::Left((None, _)) => return None,
+                Either::Right((Some(key), _)) => return Some(key),
+                // without a serial port only the keyboard is left
+                Either::Right((None, _)) => self.terminal = None,
+            }
+        }
+    }
+
+    async fn next_keyboard_key(&mut self) -> Option<DecodedKey> {
+        while let Some(event) = self.events.as_mut()?.next().await {
+            if let Some(key) = event.pressed() {
+                return Some(key);
+            }
+        }
+        None
+    }
+}
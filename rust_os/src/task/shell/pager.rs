@@ -0,0 +1,297 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use pc_keyboard::{DecodedKey, KeyCode};
+
+use super::commands::{FAILURE, MISUSE, SUCCESS};
+use super::cwd::WorkingDirectory;
+use super::io::{Io, Output};
+use super::pattern::Regex;
+use super::{CTRL_C, ESCAPE, Input};
+use crate::filesystem::with_filesystem;
+use crate::framebuffer::{self, with_framebuffer_writer};
+use crate::{print, println};
+
+/// Tab stops are this many columns apart.
+const TAB_WIDTH: usize = 8;
+
+/// Shows a file or the standard input a screen at a time, as `less` and
+/// `more` do. `more` quits when scrolling past the end.
+pub(super) async fn cmd_less(
+    args: &[&str],
+    quit_at_end: bool,
+    cwd: &WorkingDirectory,
+    input: &mut Input,
+    io: &mut Io,
+) -> i32 {
+    let command = if quit_at_end { "more" } else { "less" };
+    let (name, content) = match args {
+        [] => match io.stdin.read_to_end().await {
+            Some(content) => (String::new(), content),
+            None => {
+                writeln!(io.stderr, "Usage: {} <file>", command);
+                return MISUSE;
+            }
+        },
+        [path] => match with_filesystem(|fs| fs.read(&cwd.resolve(path))) {
+            Some(Ok(content)) => (String::from(*path), content),
+            Some(Err(e)) => {
+                writeln!(io.stderr, "{}: {}: {}", command, path, e);
+                return FAILURE;
+            }
+            None => {
+                writeln!(io.stderr, "{}: filesystem not initialized", command);
+                return FAILURE;
+            }
+        },
+        _ => {
+            writeln!(io.stderr, "Usage: {} <file>", command);
+            return MISUSE;
+        }
+    };
+    let text = String::from_utf8_lossy(&content);
+
+    // like `cat` if the output is not the screen
+    if !matches!(io.stdout, Output::Console) {
+        io.stdout.write_str(&text);
+        return SUCCESS;
+    }
+
+    let (columns, rows) = framebuffer::text_size();
+    let mut pager = Pager {
+        lines: wrap(&text, columns),
+        top: 0,
+        rows: rows.saturating_sub(1).max(1),
+        columns,
+        name,
+        search: None,
+        matched: None,
+        quit_at_end,
+    };
+    pager.run(input).await;
+    with_framebuffer_writer(|writer| writer.clear());
+    SUCCESS
+}
+
+struct Pager {
+    /// The text split into screen lines.
+    lines: Vec<String>,
+    /// Index of the first line on the screen.
+    top: usize,
+    /// Lines of text shown above the status line.
+    rows: usize,
+    columns: usize,
+    name: String,
+    search: Option<Regex>,
+    /// The line the last search stopped at.
+    matched: Option<usize>,
+    quit_at_end: bool,
+}
+
+impl Pager {
+    async fn run(&mut self, input: &mut Input) {
+        let mut message = None;
+        loop {
+            self.draw(message.take());
+            let Some(key) = input.next_key().await else {
+                return;
+            };
+            match key {
+                DecodedKey::Unicode('q' | 'Q' | CTRL_C | ESCAPE)
+                | DecodedKey::RawKey(KeyCode::Escape) => return,
+                DecodedKey::Unicode(' ' | 'f') | DecodedKey::RawKey(KeyCode::PageDown) => {
+                    if self.quit_at_end && self.at_end() {
+                        return;
+                    }
+                    self.scroll_down(self.rows);
+                }
+                DecodedKey::Unicode('\n' | 'j' | 'e') | DecodedKey::RawKey(KeyCode::ArrowDown) => {
+                    if self.quit_at_end && self.at_end() {
+                        return;
+                    }
+                    self.scroll_down(1);
+                }
+                DecodedKey::Unicode('b') | DecodedKey::RawKey(KeyCode::PageUp) => {
+                    self.scroll_up(self.rows)
+                }
+                DecodedKey::Unicode('k' | 'y') | DecodedKey::RawKey(KeyCode::ArrowUp) => {
+                    self.scroll_up(1)
+                }
+                DecodedKey::Unicode('d') => self.scroll_down(self.rows / 2),
+                DecodedKey::Unicode('u') => self.scroll_up(self.rows / 2),
+                DecodedKey::Unicode('g' | '<') | DecodedKey::RawKey(KeyCode::Home) => self.top = 0,
+                DecodedKey::Unicode('G' | '>') | DecodedKey::RawKey(KeyCode::End) => {
+                    self.top = self.last_top()
+                }
+                DecodedKey::Unicode('/') => {
+                    let Some(pattern) = self.read_pattern(input).await else {
+                        continue;
+                    };
+                    match Regex::new(&pattern, false) {
+                        Ok(regex) => {
+                            self.search = Some(regex);
+                            self.matched = None;
+                            message = self.find(true);
+                        }
+                        Err(e) => message = Some(e.to_string()),
+                    }
+                }
+                DecodedKey::Unicode('n') => message = self.find(true),
+                DecodedKey::Unicode('N') => message = self.find(false),
+                _ => {}
+            }
+        }
+    }
+
+    /// The first line on the screen when the last line is at the bottom.
+    fn last_top(&self) -> usize {
+        self.lines.len().saturating_sub(self.rows)
+    }
+
+    fn at_end(&self) -> bool {
+        self.top >= self.last_top()
+    }
+
+    fn scroll_down(&mut self, lines: usize) {
+        self.top = (self.top + lines.max(1)).min(self.last_top());
+    }
+
+    fn scroll_up(&mut self, lines: usize) {
+        self.top = self.top.saturating_sub(lines.max(1));
+    }
+
+    /// Scrolls to the next line matching the search, or the previous one
+    /// going `forward`. Returns a message if there is none.
+    fn find(&mut self, forward: bool) -> Option<String> {
+        let Some(regex) = &self.search else {
+            return Some(String::from("No previous search"));
+        };
+        // continue from the last match while it is on the screen
+        let visible = self.top..self.top + self.rows;
+        let from = self.matched.filter(|line| visible.contains(line));
+        let is_match = |&line: &usize| regex.is_match(&self.lines[line]);
+        let found = match forward {
+            true => (from.map_or(self.top, |line| line + 1)..self.lines.len()).find(is_match),
+            false => (0..from.unwrap_or(self.top)).rev().find(is_match),
+        };
+        let Some(line) = found else {
+            return Some(String::from("Pattern not found"));
+        };
+        self.matched = Some(line);
+        self.top = line.min(self.last_top());
+        None
+    }
+
+    /// Reads a search pattern on the status line, `None` if it is cancelled.
+    async fn read_pattern(&self, input: &mut Input) -> Option<String> {
+        print!("\r\x1b[K/");
+        let mut pattern = String::new();
+        loop {
+            match input.next_key().await? {
+                DecodedKey::Unicode('\n') => return Some(pattern),
+                DecodedKey::Unicode(CTRL_C | ESCAPE) | DecodedKey::RawKey(KeyCode::Escape) => {
+                    return None;
+                }
+                // backspace on an empty pattern cancels the search
+                DecodedKey::Unicode('\x08') => {
+                    pattern.pop()?;
+                    print!("\x08");
+                }
+                DecodedKey::Unicode(c) if !c.is_control() => {
+                    if pattern.chars().count() + 2 < self.columns {
+                        pattern.push(c);
+                        print!("{}", c);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn draw(&self, message: Option<String>) {
+        with_framebuffer_writer(|writer| writer.clear());
+        let shown = self.lines.iter().skip(self.top).take(self.rows);
+        let count = shown.len();
+        for line in shown {
+            println!("{}", line);
+        }
+        for _ in count..self.rows {
+            println!("~");
+        }
+        let status = message.unwrap_or_else(|| self.status());
+        // a full line would wrap and clear the screen
+        let status: String = status.chars().take(self.columns - 1).collect();
+        print!("{}", status);
+    }
+
+    fn status(&self) -> String {
+        let name = match self.name.as_str() {
+            "" => "(standard input)",
+            name => name,
+        };
+        if self.lines.is_empty() {
+            return format!("{} (empty)", name);
+        }
+        let last = (self.top + self.rows).min(self.lines.len());
+        let position = match self.at_end() {
+            true => String::from("(END)"),
+            false => format!("{}%", last * 100 / self.lines.len()),
+        };
+        format!(
+            "{} lines {}-{}/{} {}",
+            name,
+            self.top + 1,
+            last,
+            self.lines.len(),
+            position
+        )
+    }
+}
+
+/// Splits text into screen lines of at most `columns` characters. Tabs are
+/// expanded and other control characters shown as `^X`.
+fn wrap(text: &str, columns: usize) -> Vec<String> {
+    let columns = columns.max(1);
+    let mut lines = Vec::new();
+    for text_line in text.lines() {
+        let mut line = String::new();
+        let mut width = 0;
+        let mut push = |line: &mut String, width: &mut usize, c: char| {
+            if *width == columns {
+                lines.push(core::mem::take(line));
+                *width = 0;
+            }
+            line.push(c);
+            *width += 1;
+        };
+        for c in text_line.chars() {
+            match c {
+                '\t' => {
+                    for _ in 0..TAB_WIDTH - width % TAB_WIDTH {
+                        push(&mut line, &mut width, ' ');
+                    }
+                }
+                '\r' => {}
+                c if c.is_control() && (c as u32) < 0x20 => {
+                    push(&mut line, &mut width, '^');
+                    push(&mut line, &mut width, char::from(c as u8 ^ 0x40));
+                }
+                c => push(&mut line, &mut width, c),
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+#[test_case]
+fn test_wrap() {
+    assert_eq!(wrap("", 10), Vec::<String>::new());
+    assert_eq!(wrap("one\n\ntwo\n", 10), ["one", "", "two"]);
+    assert_eq!(wrap("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+    assert_eq!(wrap("abcd", 4), ["abcd"]);
+    assert_eq!(wrap("a\tb", 10), ["a       b"]);
+    assert_eq!(wrap("a\x1bb\r", 10), ["a^[b"]);
+}
@@ -0,0 +1,624 @@
+use alloc::{
+    string::{String, ToString},
+    vec::{IntoIter, Vec},
+};
+use core::{fmt, iter::Peekable, str::Chars};
+
+use super::env::{self, Environment};
+
+/// Words with a meaning of their own at the start of a command.
+const KEYWORDS: &[&str] = &[
+    "if", "then", "elif", "else", "fi", "for", "in", "do", "done",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    UnterminatedQuote(char),
+    UnterminatedBrace,
+    TrailingBackslash,
+    /// An operator without the command or file name it needs.
+    UnexpectedToken(Operator),
+    UnexpectedKeyword(&'static str),
+    Expected(&'static str),
+    UnexpectedEnd,
+    Unsupported(char),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnterminatedQuote(quote) => write!(f, "unterminated {} quote", quote),
+            ParseError::UnterminatedBrace => write!(f, "missing '}}' in variable"),
+            ParseError::TrailingBackslash => write!(f, "backslash at end of line"),
+            ParseError::UnexpectedToken(operator) => {
+                write!(f, "syntax error near unexpected token '{}'", operator)
+            }
+            ParseError::UnexpectedKeyword(keyword) => {
+                write!(f, "syntax error near unexpected '{}'", keyword)
+            }
+            ParseError::Expected(expected) => write!(f, "syntax error: expected {}", expected),
+            ParseError::UnexpectedEnd => write!(f, "syntax error: unexpected end of input"),
+            ParseError::Unsupported(c) => write!(f, "'{}' is not supported", c),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Word(Word),
+    Operator(Operator),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    /// `|`
+    Pipe,
+    /// `<`
+    Input,
+    /// `>`
+    Output,
+    /// `>>`
+    Append,
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+    /// `;`
+    Semicolon,
+    Newline,
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operator::Pipe => "|",
+            Operator::Input => "<",
+            Operator::Output => ">",
+            Operator::Append => ">>",
+            Operator::And => "&&",
+            Operator::Or => "||",
+            Operator::Semicolon => ";",
+            Operator::Newline => "newline",
+        })
+    }
+}
+
+/// A word of a command. Variables in it are expanded when the command runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Word {
+    parts: Vec<Part>,
+    /// Whether the word contains quotes or backslashes.
+    quoted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Variable(String),
+}
+
+impl Word {
+    fn push(&mut self, c: char) {
+        match self.parts.last_mut() {
+            Some(Part::Literal(literal)) => literal.push(c),
+            _ => self.parts.push(Part::Literal(c.to_string())),
+        }
+    }
+
+    /// The word as written if it has no quotes or variables.
+    pub fn as_plain(&self) -> Option<&str> {
+        match &self.parts[..] {
+            [Part::Literal(literal)] if !self.quoted => Some(literal),
+            _ => None,
+        }
+    }
+
+    /// The word with its variables replaced by their values. A word made only
+    /// of unquoted variables that are empty is no word at all.
+    pub fn expand(&self, env: &Environment) -> Option<String> {
+        let mut value = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => value.push_str(literal),
+                Part::Variable(name) if name == "?" => value.push_str(&env.status().to_string()),
+                Part::Variable(name) => value.push_str(env.get(name).unwrap_or_default()),
+            }
+        }
+        let only_variables = self.parts.iter().all(|p| matches!(p, Part::Variable(_)));
+        (self.quoted || !only_variables || !value.is_empty()).then_some(value)
+    }
+}
+
+/// Splits a script into words and operators.
+///
+/// Words are separated by whitespace and the operators `|`, `<`, `>`, `>>`,
+/// `&&`, `||`, `;` and newlines. A `#` at the start of a word comments out the
+/// rest of the line. Single quotes keep everything up to the next single
+/// quote, double quotes keep whitespace but still expand variables. A
+/// backslash keeps the next character as is, inside double quotes only before
+/// `"`, `\` and `$`. `$NAME`, `${NAME}` and `$?`, the exit status of the last
+/// command, are expanded when the command runs. Expanded values are not split
+/// into words.
+pub fn tokenize(script: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut word = Word::default();
+    // quotes make a word even if it is empty
+    let mut in_word = false;
+    let mut chars = script.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '#' if !in_word => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+                continue;
+            }
+            c if c.is_whitespace() || matches!(c, '|' | '<' | '>' | '&' | ';') => {
+                if in_word {
+                    tokens.push(Token::Word(core::mem::take(&mut word)));
+                    in_word = false;
+                }
+                let operator = match c {
+                    '\n' => Operator::Newline,
+                    ';' => Operator::Semicolon,
+                    '|' if chars.next_if_eq(&'|').is_some() => Operator::Or,
+                    '|' => Operator::Pipe,
+                    '&' if chars.next_if_eq(&'&').is_some() => Operator::And,
+                    '&' => return Err(ParseError::Unsupported('&')),
+                    '<' => Operator::Input,
+                    '>' if chars.next_if_eq(&'>').is_some() => Operator::Append,
+                    '>' => Operator::Output,
+                    _ => continue,
+                };
+                tokens.push(Token::Operator(operator));
+                continue;
+            }
+            '\'' => {
+                word.quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(ParseError::UnterminatedQuote('\'')),
+                    }
+                }
+            }
+            '"' => {
+                word.quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(ParseError::UnterminatedQuote('"')),
+                        },
+                        Some('$') => variable(&mut chars, &mut word)?,
+                        Some(c) => word.push(c),
+                        None => return Err(ParseError::UnterminatedQuote('"')),
+                    }
+                }
+            }
+            '\\' => {
+                // an escaped keyword is an ordinary word, like a quoted one
+                word.quoted = true;
+                word.push(chars.next().ok_or(ParseError::TrailingBackslash)?);
+            }
+            '$' => variable(&mut chars, &mut word)?,
+            c => word.push(c),
+        }
+        in_word = true;
+    }
+    if in_word {
+        tokens.push(Token::Word(word));
+    }
+    Ok(tokens)
+}
+
+/// Adds the variable named after a `$` to `word`, or the `$` itself if no
+/// name follows.
+fn variable(chars: &mut Peekable<Chars>, word: &mut Word) -> Result<(), ParseError> {
+    let mut name = String::new();
+    if chars.next_if_eq(&'{').is_some() {
+        loop {
+            match chars.next() {
+                Some('}') => break,
+                Some(c) => name.push(c),
+                None => return Err(ParseError::UnterminatedBrace),
+            }
+        }
+    } else if chars.next_if_eq(&'?').is_some() {
+        name.push('?');
+    } else {
+        while let Some(c) = chars.next_if(|&c| c.is_ascii_alphanumeric() || c == '_') {
+            name.push(c);
+        }
+        if name.is_empty() {
+            word.push('$');
+            return Ok(());
+        }
+    }
+    word.parts.push(Part::Variable(name));
+    Ok(())
+}
+
+/// Commands separated by `;` or newlines.
+pub type List = Vec<AndOr>;
+
+/// Commands connected by `&&` and `||`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct AndOr {
+    pub first: Compound,
+    pub rest: Vec<(Connector, Compound)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connector {
+    /// Runs the next command if the previous one succeeded.
+    And,
+    /// Runs the next command if the previous one failed.
+    Or,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Compound {
+    /// Commands connected by pipes.
+    Pipeline(Vec<Command>),
+    If {
+        condition: List,
+        then: List,
+        otherwise: List,
+    },
+    For {
+        variable: String,
+        words: Vec<Word>,
+        body: List,
+    },
+}
+
+/// A command of a pipeline with its redirections.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Command {
+    pub args: Vec<Word>,
+    /// File read as standard input.
+    pub input: Option<Word>,
+    /// File written with the standard output.
+    pub output: Option<OutputFile>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct OutputFile {
+    pub path: Word,
+    /// Whether the output is added to the end of the file instead of
+    /// replacing it.
+    pub append: bool,
+}
+
+/// Parses the tokens of a command line or script.
+pub fn parse(tokens: Vec<Token>) -> Result<List, ParseError> {
+    let mut parser = Parser {
+        tokens: tokens.into_iter().peekable(),
+    };
+    let list = parser.list(&[])?;
+    match parser.tokens.next() {
+        None => Ok(list),
+        Some(Token::Operator(operator)) => Err(ParseError::UnexpectedToken(operator)),
+        Some(Token::Word(word)) => Err(unexpected_word(&word)),
+    }
+}
+
+fn unexpected_word(word: &Word) -> ParseError {
+    match word.as_plain().and_then(keyword) {
+        Some(keyword) => ParseError::UnexpectedKeyword(keyword),
+        None => ParseError::Expected("';' or newline"),
+    }
+}
+
+fn keyword(word: &str) -> Option<&'static str> {
+    KEYWORDS.iter().copied().find(|keyword| *keyword == word)
+}
+
+struct Parser {
+    tokens: Peekable<IntoIter<Token>>,
+}
+
+impl Parser {
+    fn peek_keyword(&mut self) -> Option<&'static str> {
+        match self.tokens.peek() {
+            Some(Token::Word(word)) => word.as_plain().and_then(keyword),
+            _ => None,
+        }
+    }
+
+    fn expect_keyword(&mut self, expected: &'static str) -> Result<(), ParseError> {
+        match self.tokens.next() {
+            Some(Token::Word(word)) if word.as_plain() == Some(expected) => Ok(()),
+            Some(_) => Err(ParseError::Expected(expected)),
+            None => Err(ParseError::UnexpectedEnd),
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        let is_separator = |token: &Token| {
+            matches!(
+                token,
+                Token::Operator(Operator::Semicolon | Operator::Newline)
+            )
+        };
+        while self.tokens.next_if(is_separator).is_some() {}
+    }
+
+    fn skip_newlines(&mut self) {
+        let newline = Token::Operator(Operator::Newline);
+        while self.tokens.next_if_eq(&newline).is_some() {}
+    }
+
+    /// Parses commands until the end or one of the `terminators` keywords.
+    fn list(&mut self, terminators: &[&str]) -> Result<List, ParseError> {
+        let mut list = Vec::new();
+        loop {
+            self.skip_separators();
+            let terminated = self
+                .peek_keyword()
+                .is_some_and(|keyword| terminators.contains(&keyword));
+            if terminated || self.tokens.peek().is_none() {
+                return Ok(list);
+            }
+            list.push(self.and_or()?);
+            match self.tokens.peek() {
+                None | Some(Token::Operator(Operator::Semicolon | Operator::Newline)) => {}
+                Some(Token::Operator(operator)) => {
+                    return Err(ParseError::UnexpectedToken(*operator));
+                }
+                Some(Token::Word(word)) => return Err(unexpected_word(word)),
+            }
+        }
+    }
+
+    fn and_or(&mut self) -> Result<AndOr, ParseError> {
+        let first = self.compound()?;
+        let mut rest = Vec::new();
+        loop {
+            let connector = match self.tokens.peek() {
+                Some(Token::Operator(Operator::And)) => Connector::And,
+                Some(Token::Operator(Operator::Or)) => Connector::Or,
+                _ => return Ok(AndOr { first, rest }),
+            };
+            self.tokens.next();
+            self.skip_newlines();
+            rest.push((connector, self.compound()?));
+        }
+    }
+
+    fn compound(&mut self) -> Result<Compound, ParseError> {
+        match self.peek_keyword() {
+            Some("if") => {
+                self.tokens.next();
+                self.if_clause()
+            }
+            Some("for") => {
+                self.tokens.next();
+                self.for_clause()
+            }
+            Some(keyword) => Err(ParseError::UnexpectedKeyword(keyword)),
+            None => self.pipeline().map(Compound::Pipeline),
+        }
+    }
+
+    /// Parses an `if` or `elif` clause after its keyword, up to the `fi`.
+    fn if_clause(&mut self) -> Result<Compound, ParseError> {
+        let condition = self.list(&["then"])?;
+        self.expect_keyword("then")?;
+        let then = self.list(&["elif", "else", "fi"])?;
+        let otherwise = match self.tokens.next().as_ref().and_then(|token| match token {
+            Token::Word(word) => word.as_plain().and_then(keyword),
+            Token::Operator(_) => None,
+        }) {
+            Some("elif") => alloc::vec![AndOr {
+                first: self.if_clause()?,
+                rest: Vec::new(),
+            }],
+            Some("else") => {
+                let otherwise = self.list(&["fi"])?;
+                self.expect_keyword("fi")?;
+                otherwise
+            }
+            Some(_) => Vec::new(),
+            None => return Err(ParseError::UnexpectedEnd),
+        };
+        Ok(Compound::If {
+            condition,
+            then,
+            otherwise,
+        })
+    }
+
+    /// Parses a `for NAME in WORDS; do LIST; done` loop after the `for`.
+    fn for_clause(&mut self) -> Result<Compound, ParseError> {
+        let variable = match self.tokens.next() {
+            Some(Token::Word(word)) => match word.as_plain() {
+                Some(name) if env::is_valid_name(name) => String::from(name),
+                _ => return Err(ParseError::Expected("a variable name")),
+            },
+            Some(_) => return Err(ParseError::Expected("a variable name")),
+            None => return Err(ParseError::UnexpectedEnd),
+        };
+        self.expect_keyword("in")?;
+        let mut words = Vec::new();
+        while let Some(Token::Word(word)) = self.tokens.next_if(|t| matches!(t, Token::Word(_))) {
+            words.push(word);
+        }
+        self.skip_separators();
+        self.expect_keyword("do")?;
+        let body = self.list(&["done"])?;
+        self.expect_keyword("done")?;
+        Ok(Compound::For {
+            variable,
+            words,
+            body,
+        })
+    }
+
+    fn pipeline(&mut self) -> Result<Vec<Command>, ParseError> {
+        let mut commands = alloc::vec![self.command()?];
+        let pipe = Token::Operator(Operator::Pipe);
+        while self.tokens.next_if_eq(&pipe).is_some() {
+            self.skip_newlines();
+            commands.push(self.command()?);
+        }
+        Ok(commands)
+    }
+
+    /// Parses the words and redirections of a command.
+    fn command(&mut self) -> Result<Command, ParseError> {
+        let mut command = Command::default();
+        loop {
+            let operator = match self.tokens.peek() {
+                Some(Token::Word(_)) => {
+                    if let Some(Token::Word(word)) = self.tokens.next() {
+                        command.args.push(word);
+                    }
+                    continue;
+                }
+                Some(Token::Operator(
+                    operator @ (Operator::Input | Operator::Output | Operator::Append),
+                )) => *operator,
+                Some(Token::Operator(operator)) if command == Command::default() => {
+                    return Err(ParseError::UnexpectedToken(*operator));
+                }
+                None if command == Command::default() => return Err(ParseError::UnexpectedEnd),
+                _ => return Ok(command),
+            };
+            self.tokens.next();
+            let path = match self.tokens.next() {
+                Some(Token::Word(path)) => path,
+                Some(Token::Operator(next)) => return Err(ParseError::UnexpectedToken(next)),
+                None => return Err(ParseError::UnexpectedEnd),
+            };
+            match operator {
+                Operator::Input => command.input = Some(path),
+                Operator::Append => command.output = Some(OutputFile { path, append: true }),
+                _ => {
+                    command.output = Some(OutputFile {
+                        path,
+                        append: false,
+                    })
+                }
+            }
+        }
+    }
+}
+
+#[test_case]
+fn test_tokenize() {
+    let mut env = Environment::new();
+    env.set("NAME", "two words");
+    env.set_status(2);
+    let words = |script: &str| {
+        tokenize(script).map(|tokens| {
+            tokens
+                .into_iter()
+                .filter_map(|token| match token {
+                    Token::Word(word) => word.expand(&env),
+                    Token::Operator(operator) => Some(alloc::format!("{}", operator)),
+                })
+                .collect::<Vec<_>>()
+        })
+    };
+
+    assert_eq!(
+        words("  echo  a   b "),
+        Ok(["echo", "a", "b"].map(String::from).into())
+    );
+    assert_eq!(
+        words(r#"echo "a  b" 'c $NAME' d\ e "\"\$" '' x"#),
+        Ok(["echo", "a  b", "c $NAME", "d e", "\"$", "", "x"]
+            .map(String::from)
+            .into())
+    );
+    assert_eq!(
+        words("echo $NAME ${NAME}s \"$NAME\" $MISSING $ $? end"),
+        Ok([
+            "echo",
+            "two words",
+            "two wordss",
+            "two words",
+            "$",
+            "2",
+            "end"
+        ]
+        .map(String::from)
+        .into())
+    );
+    assert_eq!(
+        words("cat<in|wc>>out '|' && a||b; c # comment\nd#e"),
+        Ok([
+            "cat", "<", "in", "|", "wc", ">>", "out", "|", "&&", "a", "||", "b", ";", "c",
+            "newline", "d#e"
+        ]
+        .map(String::from)
+        .into())
+    );
+    assert_eq!(words("echo 'a"), Err(ParseError::UnterminatedQuote('\'')));
+    assert_eq!(words("echo \\"), Err(ParseError::TrailingBackslash));
+    assert_eq!(words("echo ${NAME"), Err(ParseError::UnterminatedBrace));
+    assert_eq!(words("sleep 1 &"), Err(ParseError::Unsupported('&')));
+}
+
+#[test_case]
+fn test_parse() {
+    let parse = |script: &str| parse(tokenize(script).unwrap());
+    fn plain(words: &[Word]) -> Vec<&str> {
+        words.iter().map(|word| word.as_plain().unwrap()).collect()
+    }
+
+    let list = parse("cat < in.txt a | grep x > out.txt && echo ok; echo done\n").unwrap();
+    assert_eq!(list.len(), 2);
+    let Compound::Pipeline(commands) = &list[0].first else {
+        panic!("expected a pipeline");
+    };
+    assert_eq!(plain(&commands[0].args), ["cat", "a"]);
+    assert_eq!(
+        commands[0].input.as_ref().and_then(Word::as_plain),
+        Some("in.txt")
+    );
+    assert!(commands[1].output.as_ref().is_some_and(|o| !o.append));
+    assert_eq!(list[0].rest.len(), 1);
+    assert_eq!(list[0].rest[0].0, Connector::And);
+
+    let script =
+        "for x in a b\ndo\n  if true; then echo $x; elif false; then true; else echo no; fi\ndone";
+    let list = parse(script).unwrap();
+    let Compound::For {
+        variable,
+        words,
+        body,
+    } = &list[0].first
+    else {
+        panic!("expected a for loop");
+    };
+    assert_eq!(variable, "x");
+    assert_eq!(plain(words), ["a", "b"]);
+    let Compound::If { otherwise, .. } = &body[0].first else {
+        panic!("expected an if clause");
+    };
+    assert!(matches!(otherwise[0].first, Compound::If { .. }));
+
+    assert_eq!(parse("").unwrap(), []);
+    assert_eq!(parse("> empty.txt").unwrap().len(), 1);
+    assert_eq!(
+        parse("| wc"),
+        Err(ParseError::UnexpectedToken(Operator::Pipe))
+    );
+    assert_eq!(parse("ls |"), Err(ParseError::UnexpectedEnd));
+    assert_eq!(parse("ls >"), Err(ParseError::UnexpectedEnd));
+    assert_eq!(
+        parse("ls > | wc"),
+        Err(ParseError::UnexpectedToken(Operator::Pipe))
+    );
+    assert_eq!(parse("if true; then ls"), Err(ParseError::UnexpectedEnd));
+    assert_eq!(parse("fi"), Err(ParseError::UnexpectedKeyword("fi")));
+    assert_eq!(parse("echo 'fi'").unwrap().len(), 1);
+}
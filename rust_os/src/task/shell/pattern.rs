@@ -0,0 +1,259 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternError {
+    UnterminatedBracket,
+    TrailingBackslash,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::UnterminatedBracket => write!(f, "missing ']'"),
+            PatternError::TrailingBackslash => write!(f, "trailing backslash"),
+        }
+    }
+}
+
+/// A simple regular expression: `.` matches any character, `[abc]`, `[a-z]`
+/// and `[^abc]` a set of characters, `*`, `+` and `?` repeat what is before
+/// them, `^` and `$` anchor at the start and end and `\` escapes.
+///
+/// Matching backtracks, which is fine for the short lines of the ramdisk.
+pub struct Regex {
+    items: Vec<Item>,
+    anchored_start: bool,
+    anchored_end: bool,
+    ignore_case: bool,
+}
+
+struct Item {
+    atom: Atom,
+    min: usize,
+    max: usize,
+}
+
+enum Atom {
+    Char(char),
+    Any,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Regex {
+    pub fn new(pattern: &str, ignore_case: bool) -> Result<Self, PatternError> {
+        let mut chars: Vec<char> = pattern.chars().collect();
+        let anchored_start = chars.first() == Some(&'^');
+        if anchored_start {
+            chars.remove(0);
+        }
+        let anchored_end = chars.last() == Some(&'$') && !ends_escaped(&chars[..chars.len() - 1]);
+        if anchored_end {
+            chars.pop();
+        }
+
+        let mut items: Vec<Item> = Vec::new();
+        let mut chars = chars.into_iter().peekable();
+        while let Some(c) = chars.next() {
+            let (min, max) = match c {
+                '*' => (0, usize::MAX),
+                '+' => (1, usize::MAX),
+                '?' => (0, 1),
+                _ => (1, 1),
+            };
+            // a repetition without anything before it is an ordinary character
+            if (min, max) != (1, 1)
+                && let Some(last) = items.last_mut()
+                && (last.min, last.max) == (1, 1)
+            {
+                last.min = min;
+                last.max = max;
+                continue;
+            }
+            let atom = match c {
+                '.' => Atom::Any,
+                '[' => class(&mut chars, '^')?,
+                '\\' => Atom::Char(chars.next().ok_or(PatternError::TrailingBackslash)?),
+                c => Atom::Char(c),
+            };
+            items.push(Item {
+                atom,
+                min: 1,
+                max: 1,
+            });
+        }
+        Ok(Regex {
+            items,
+            anchored_start,
+            anchored_end,
+            ignore_case,
+        })
+    }
+
+    /// A shell wildcard pattern matching whole names: `*` matches any
+    /// characters, `?` one character and `[abc]` or `[!abc]` a set.
+    pub fn glob(pattern: &str) -> Result<Self, PatternError> {
+        let mut items = Vec::new();
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            let (atom, max) = match c {
+                '*' => (Atom::Any, usize::MAX),
+                '?' => (Atom::Any, 1),
+                '[' => (class(&mut chars, '!')?, 1),
+                '\\' => (
+                    Atom::Char(chars.next().ok_or(PatternError::TrailingBackslash)?),
+                    1,
+                ),
+                c => (Atom::Char(c), 1),
+            };
+            let min = if max == 1 { 1 } else { 0 };
+            items.push(Item { atom, min, max });
+        }
+        Ok(Regex {
+            items,
+            anchored_start: true,
+            anchored_end: true,
+            ignore_case: false,
+        })
+    }
+
+    /// Whether the pattern matches somewhere in `text`, or all of it for
+    /// globs.
+    pub fn is_match(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        if self.anchored_start {
+            return self.match_here(&self.items, &text);
+        }
+        (0..=text.len()).any(|start| self.match_here(&self.items, &text[start..]))
+    }
+
+    fn match_here(&self, items: &[Item], text: &[char]) -> bool {
+        let Some((item, rest)) = items.split_first() else {
+            return !self.anchored_end || text.is_empty();
+        };
+        let mut count = 0;
+        while count < item.max && count < text.len() && self.matches(&item.atom, text[count]) {
+            count += 1;
+        }
+        if count < item.min {
+            return false;
+        }
+        // the longest repetition first
+        (item.min..=count)
+            .rev()
+            .any(|n| self.match_here(rest, &text[n..]))
+    }
+
+    fn matches(&self, atom: &Atom, c: char) -> bool {
+        let equal = |a: char, b: char| match self.ignore_case {
+            true => a.to_lowercase().eq(b.to_lowercase()),
+            false => a == b,
+        };
+        match atom {
+            Atom::Any => true,
+            Atom::Char(expected) => equal(*expected, c),
+            Atom::Class { negated, ranges } => {
+                let in_range = |c: char| ranges.iter().any(|&(low, high)| low <= c && c <= high);
+                let found = match self.ignore_case {
+                    true => {
+                        in_range(c)
+                            || c.to_lowercase().any(in_range)
+                            || c.to_uppercase().any(in_range)
+                    }
+                    false => in_range(c),
+                };
+                found != *negated
+            }
+        }
+    }
+}
+
+/// Whether the characters end in an unescaped backslash.
+fn ends_escaped(chars: &[char]) -> bool {
+    chars.iter().rev().take_while(|&&c| c == '\\').count() % 2 == 1
+}
+
+/// Parses a bracket expression after its `[`, `negation` starts a negated
+/// one. A `]` right at the start is part of the set.
+fn class(
+    chars: &mut core::iter::Peekable<impl Iterator<Item = char>>,
+    negation: char,
+) -> Result<Atom, PatternError> {
+    let negated = chars.next_if_eq(&negation).is_some();
+    let mut ranges = Vec::new();
+    let mut first = true;
+    loop {
+        let low = match chars.next() {
+            Some(']') if !first => break,
+            Some(c) => c,
+            None => return Err(PatternError::UnterminatedBracket),
+        };
+        first = false;
+        let high = match chars.peek() {
+            Some('-') => {
+                chars.next();
+                match chars.next() {
+                    Some(']') => {
+                        // a `-` at the end is an ordinary character
+                        ranges.push((low, low));
+                        ranges.push(('-', '-'));
+                        break;
+                    }
+                    Some(high) => high,
+                    None => return Err(PatternError::UnterminatedBracket),
+                }
+            }
+            _ => low,
+        };
+        ranges.push((low, high));
+    }
+    Ok(Atom::Class { negated, ranges })
+}
+
+#[test_case]
+fn test_regex() {
+    let matches = |pattern: &str, text: &str| Regex::new(pattern, false).unwrap().is_match(text);
+
+    assert!(matches("config", "/etc/config.txt"));
+    assert!(!matches("^config", "/etc/config.txt"));
+    assert!(matches("txt$", "/etc/config.txt"));
+    assert!(matches("^a.c$", "abc"));
+    assert!(!matches("^a.c$", "abbc"));
+    assert!(matches("^ab*c$", "ac"));
+    assert!(matches("^ab+c$", "abbbc"));
+    assert!(!matches("^ab+c$", "ac"));
+    assert!(matches("^colou?r$", "color"));
+    assert!(matches("^[a-c]+[^0-9]$", "abcx"));
+    assert!(!matches("^[a-c]+[^0-9]$", "abc1"));
+    assert!(matches("a\\.b", "a.b"));
+    assert!(!matches("a\\.b", "axb"));
+    assert!(matches("^*x", "*x"));
+    assert!(matches("cost\\$", "cost$"));
+    assert!(matches("", "anything"));
+    assert!(Regex::new("HELLO", true).unwrap().is_match("hello world"));
+    assert!(Regex::new("[A-Z]", true).unwrap().is_match("x"));
+    assert_eq!(
+        Regex::new("[abc", false).err(),
+        Some(PatternError::UnterminatedBracket)
+    );
+    assert_eq!(
+        Regex::new("abc\\", false).err(),
+        Some(PatternError::TrailingBackslash)
+    );
+}
+
+#[test_case]
+fn test_glob() {
+    let matches = |pattern: &str, name: &str| Regex::glob(pattern).unwrap().is_match(name);
+
+    assert!(matches("*.txt", "file1.txt"));
+    assert!(!matches("*.txt", "file1.txt.bak"));
+    assert!(matches("file?.txt", "file1.txt"));
+    assert!(!matches("file?.txt", "file10.txt"));
+    assert!(matches("[!a-e]*", "snake.wasm"));
+    assert!(!matches("[!a-z]*", "snake.wasm"));
+    assert!(matches("*", ""));
+}
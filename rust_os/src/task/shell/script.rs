@@ -0,0 +1,222 @@
+use alloc::{string::String, vec::Vec};
+use futures_util::future::{FutureExt, LocalBoxFuture, join, join_all};
+
+use super::Shell;
+use super::commands::{self, FAILURE, MISUSE, SUCCESS};
+use super::io::{Io, Output, Stdin};
+use super::parser::{self, AndOr, Command, Compound, Connector, List};
+use crate::filesystem::with_filesystem;
+use crate::println;
+use crate::task::sync::pipe;
+
+/// How many bytes the pipe between two commands of a pipeline holds.
+const PIPE_CAPACITY: usize = 4096;
+
+/// Runs a command line or a whole script. Returns the exit status of the last
+/// command.
+pub async fn execute(script: &str, shell: &mut Shell) -> i32 {
+    match parser::tokenize(script).and_then(parser::parse) {
+        Ok(list) => execute_list(&list, shell).await,
+        Err(e) => {
+            println!("sh: {}", e);
+            shell.env.set_status(MISUSE);
+            MISUSE
+        }
+    }
+}
+
+/// Runs a script file in the current shell, as `source` does. Variables it
+/// sets stay set, and its `exit` ends the scripts that sourced it as well.
+pub async fn run_file(path: &str, shell: &mut Shell, io: &mut Io) -> i32 {
+    let script = match with_filesystem(|fs| fs.read_to_string(path)) {
+        Some(Ok(script)) => script,
+        Some(Err(e)) => {
+            writeln!(io.stderr, "sh: {}: {:?}", path, e);
+            return FAILURE;
+        }
+        None => {
+            writeln!(io.stderr, "sh: filesystem not initialized");
+            return FAILURE;
+        }
+    };
+    shell.script_depth += 1;
+    let status = execute(&script, shell).await;
+    shell.script_depth -= 1;
+    // back at the prompt the shell goes on
+    match shell.in_script() {
+        true => shell.exit.unwrap_or(status),
+        false => shell.exit.take().unwrap_or(status),
+    }
+}
+
+/// Runs a script file with a copy of the exported variables, as `sh` does.
+pub async fn run_file_in_subshell(path: &str, shell: &mut Shell, io: &mut Io) -> i32 {
+    let subshell = shell.env.subshell();
+    let env = core::mem::replace(&mut shell.env, subshell);
+    let status = run_file(path, shell, io).await;
+    // `exit` only ends the subshell
+    shell.exit = None;
+    shell.env = env;
+    status
+}
+
+/// Runs the commands one after another until the end or an `exit`.
+fn execute_list<'a>(list: &'a List, shell: &'a mut Shell) -> LocalBoxFuture<'a, i32> {
+    // boxed, because commands like `source` run lists themselves
+    async move {
+        let mut status = SUCCESS;
+        for and_or in list {
+            status = execute_and_or(and_or, shell).await;
+            if shell.exit.is_some() {
+                break;
+            }
+        }
+        status
+    }
+    .boxed_local()
+}
+
+async fn execute_and_or(and_or: &AndOr, shell: &mut Shell) -> i32 {
+    let mut status = execute_compound(&and_or.first, shell).await;
+    for (connector, compound) in &and_or.rest {
+        if shell.exit.is_some() {
+            break;
+        }
+        let run = match connector {
+            Connector::And => status == SUCCESS,
+            Connector::Or => status != SUCCESS,
+        };
+        if run {
+            status = execute_compound(compound, shell).await;
+        }
+    }
+    status
+}
+
+async fn execute_compound(compound: &Compound, shell: &mut Shell) -> i32 {
+    let status = match compound {
+        Compound::Pipeline(commands) => execute_pipeline(commands, shell).await,
+        Compound::If {
+            condition,
+            then,
+            otherwise,
+        } => {
+            let status = execute_list(condition, shell).await;
+            if shell.exit.is_some() {
+                status
+            } else if status == SUCCESS {
+                execute_list(then, shell).await
+            } else {
+                execute_list(otherwise, shell).await
+            }
+        }
+        Compound::For {
+            variable,
+            words,
+            body,
+        } => {
+            let values: Vec<String> = words
+                .iter()
+                .filter_map(|word| word.expand(&shell.env))
+                .collect();
+            let mut status = SUCCESS;
+            for value in values {
+                shell.env.set(variable, &value);
+                status = execute_list(body, shell).await;
+                if shell.exit.is_some() {
+                    break;
+                }
+            }
+            status
+        }
+    };
+    shell.env.set_status(status);
+    status
+}
+
+/// Runs the commands of a pipeline. Returns the exit status of the last one.
+///
+/// The commands run at the same time, each one reads what the one before
+/// wrote into the pipe between them and waits while the pipe it writes into
+/// is full. All but the last command run in a copy of the shell.
+async fn execute_pipeline(commands: &[Command], shell: &mut Shell) -> i32 {
+    let mut stdin = Stdin::Console;
+    let mut stages: Vec<Option<(Vec<String>, Io)>> = Vec::new();
+
+    for (i, command) in commands.iter().enumerate() {
+        let mut next_stdin = Stdin::Empty;
+        let stdout = match &command.output {
+            Some(file) => {
+                let path = shell
+                    .cwd
+                    .resolve(&file.path.expand(&shell.env).unwrap_or_default());
+                Output::file(&path, file.append)
+            }
+            None if i + 1 < commands.len() => {
+                let (writer, reader) = pipe::pipe(PIPE_CAPACITY);
+                next_stdin = Stdin::Pipe(reader);
+                Output::pipe(writer)
+            }
+            None => Output::Console,
+        };
+        let mut stdin = core::mem::replace(&mut stdin, next_stdin);
+        if let Some(path) = &command.input {
+            let path = shell
+                .cwd
+                .resolve(&path.expand(&shell.env).unwrap_or_default());
+            // an empty read shows whether the file can be read at all
+            match with_filesystem(|fs| fs.read_into(&path, 0, &mut [])) {
+                Some(Ok(_)) => stdin = Stdin::File { path, position: 0 },
+                Some(Err(e)) => {
+                    println!("sh: {}: {:?}", path, e);
+                    stages.push(None);
+                    continue;
+                }
+                None => {
+                    println!("sh: filesystem not initialized");
+                    stages.push(None);
+                    continue;
+                }
+            }
+        }
+        let io = Io {
+            stdin,
+            stdout,
+            ..Io::console()
+        };
+        let args = command
+            .args
+            .iter()
+            .filter_map(|word| word.expand(&shell.env))
+            .collect();
+        stages.push(Some((args, io)));
+    }
+
+    let Some(last) = stages.pop() else {
+        return SUCCESS;
+    };
+    let first: Vec<_> = stages
+        .into_iter()
+        .map(|stage| {
+            let mut subshell = shell.pipeline_stage();
+            async move { run_stage(stage, &mut subshell).await }
+        })
+        .collect();
+    let (_, status) = join(join_all(first), run_stage(last, shell)).await;
+    status
+}
+
+/// Runs a command of a pipeline, `None` if its input could not be opened.
+async fn run_stage(stage: Option<(Vec<String>, Io)>, shell: &mut Shell) -> i32 {
+    let Some((args, mut io)) = stage else {
+        return FAILURE;
+    };
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let mut status = commands::execute_command(&args, shell, &mut io).await;
+    // closing the pipe writer lets the next command see the end of its input
+    if let Err((path, e)) = io.stdout.finish().await {
+        println!("sh: {}: {:?}", path, e);
+        status = FAILURE;
+    }
+    status
+}
@@ -0,0 +1,12 @@
+pub mod mpsc;
+mod mutex;
+mod notify;
+pub mod oneshot;
+pub mod pipe;
+mod rwlock;
+mod semaphore;
+
+pub use mutex::{Mutex, MutexGuard};
+pub use notify::{Notified, Notify};
+pub use rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use semaphore::{Acquire, AcquireError, Semaphore, SemaphorePermit, TryAcquireError};
@@ -0,0 +1,149 @@
+use alloc::{collections::VecDeque, sync::Arc};
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicU8, Ordering},
+    task::{Context, Poll},
+};
+
+use futures_util::task::AtomicWaker;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+/// Wakes up tasks waiting for an event, without transferring any data.
+///
+/// [`Notify::notify_one`] stores a single permit if nobody is waiting, so a
+/// notification that arrives before the task starts waiting is not lost.
+/// Notifying never allocates and can be done from interrupt handlers.
+pub struct Notify {
+    state: Mutex<State>,
+}
+
+struct State {
+    permit: bool,
+    waiters: VecDeque<Arc<Waiter>>,
+}
+
+impl State {
+    fn notify_one(&mut self) {
+        match self.waiters.pop_front() {
+            Some(waiter) => {
+                waiter.status.store(NOTIFIED_ONE, Ordering::SeqCst);
+                waiter.waker.wake();
+            }
+            None => self.permit = true,
+        }
+    }
+}
+
+const WAITING: u8 = 0;
+const NOTIFIED_ONE: u8 = 1;
+const NOTIFIED_ALL: u8 = 2;
+
+struct Waiter {
+    status: AtomicU8,
+    waker: AtomicWaker,
+}
+
+impl Notify {
+    pub const fn new() -> Self {
+        Notify {
+            state: Mutex::new(State {
+                permit: false,
+                waiters: VecDeque::new(),
+            }),
+        }
+    }
+
+    fn with_state<R>(&self, f: impl FnOnce(&mut State) -> R) -> R {
+        without_interrupts(|| f(&mut self.state.lock()))
+    }
+
+    /// Wakes the longest waiting task, or lets the next call to
+    /// [`Notify::notified`] complete immediately if no task is waiting.
+    pub fn notify_one(&self) {
+        self.with_state(State::notify_one);
+    }
+
+    /// Wakes all tasks that are currently waiting. Does not store a permit.
+    pub fn notify_waiters(&self) {
+        self.with_state(|state| {
+            for waiter in state.waiters.drain(..) {
+                waiter.status.store(NOTIFIED_ALL, Ordering::SeqCst);
+                waiter.waker.wake();
+            }
+        });
+    }
+
+    /// Waits for a notification.
+    pub fn notified(&self) -> Notified<'_> {
+        Notified {
+            notify: self,
+            waiter: None,
+        }
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Notify::new()
+    }
+}
+
+/// Future returned by [`Notify::notified`].
+pub struct Notified<'a> {
+    notify: &'a Notify,
+    waiter: Option<Arc<Waiter>>,
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if let Some(waiter) = &self.waiter {
+            waiter.waker.register(cx.waker());
+            if waiter.status.load(Ordering::SeqCst) == WAITING {
+                return Poll::Pending;
+            }
+            self.waiter = None;
+            return Poll::Ready(());
+        }
+
+        let waiter = self.notify.with_state(|state| {
+            if core::mem::take(&mut state.permit) {
+                return None;
+            }
+            let waiter = Arc::new(Waiter {
+                status: AtomicU8::new(WAITING),
+                waker: AtomicWaker::new(),
+            });
+            waiter.waker.register(cx.waker());
+            state.waiters.push_back(waiter.clone());
+            Some(waiter)
+        });
+
+        match waiter {
+            Some(waiter) => {
+                self.waiter = Some(waiter);
+                Poll::Pending
+            }
+            None => Poll::Ready(()),
+        }
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        let Some(waiter) = self.waiter.take() else {
+            return;
+        };
+        // the status only changes while the state is locked
+        self.notify
+            .with_state(|state| match waiter.status.load(Ordering::SeqCst) {
+                WAITING => state.waiters.retain(|w| !Arc::ptr_eq(w, &waiter)),
+                // hand a notification that was meant for a single task to the next one
+                NOTIFIED_ONE => state.notify_one(),
+                _ => {}
+            });
+    }
+}
@@ -0,0 +1,171 @@
+use alloc::{collections::VecDeque, sync::Arc, vec::Vec};
+use core::{
+    fmt,
+    future::poll_fn,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll},
+};
+
+use futures_util::task::AtomicWaker;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+/// Error returned when writing into a pipe whose reader is gone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BrokenPipe;
+
+impl fmt::Display for BrokenPipe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("broken pipe")
+    }
+}
+
+struct Buffer {
+    bytes: Mutex<VecDeque<u8>>,
+    capacity: usize,
+    writer_closed: AtomicBool,
+    reader_closed: AtomicBool,
+    reader_waker: AtomicWaker,
+    writer_waker: AtomicWaker,
+}
+
+/// Creates a pipe that passes bytes from the writer to the reader and holds
+/// up to `capacity` of them.
+///
+/// The writer waits while the pipe is full, so the reader has to run in
+/// another task or be polled alongside it.
+///
+/// Panics if `capacity` is zero.
+pub fn pipe(capacity: usize) -> (PipeWriter, PipeReader) {
+    assert!(capacity > 0, "pipe capacity must not be zero");
+    let buffer = Arc::new(Buffer {
+        bytes: Mutex::new(VecDeque::with_capacity(capacity)),
+        capacity,
+        writer_closed: AtomicBool::new(false),
+        reader_closed: AtomicBool::new(false),
+        reader_waker: AtomicWaker::new(),
+        writer_waker: AtomicWaker::new(),
+    });
+    (
+        PipeWriter {
+            buffer: buffer.clone(),
+        },
+        PipeReader { buffer },
+    )
+}
+
+/// Writing end of a pipe. The reader sees the end of the data once it is
+/// dropped.
+pub struct PipeWriter {
+    buffer: Arc<Buffer>,
+}
+
+impl PipeWriter {
+    /// Writes all of `bytes`, waiting while the pipe is full.
+    pub async fn write(&self, mut bytes: &[u8]) -> Result<(), BrokenPipe> {
+        while !bytes.is_empty() {
+            let written = poll_fn(|cx| self.poll_write(cx, bytes)).await?;
+            bytes = &bytes[written..];
+        }
+        Ok(())
+    }
+
+    /// Writes as many bytes as fit without waiting and returns how many
+    /// that were.
+    pub fn try_write(&self, bytes: &[u8]) -> Result<usize, BrokenPipe> {
+        if self.buffer.reader_closed.load(Ordering::SeqCst) {
+            return Err(BrokenPipe);
+        }
+        let written = without_interrupts(|| {
+            let mut buffered = self.buffer.bytes.lock();
+            let written = (self.buffer.capacity - buffered.len()).min(bytes.len());
+            buffered.extend(&bytes[..written]);
+            written
+        });
+        if written > 0 {
+            self.buffer.reader_waker.wake();
+        }
+        Ok(written)
+    }
+
+    pub fn poll_write(&self, cx: &mut Context, bytes: &[u8]) -> Poll<Result<usize, BrokenPipe>> {
+        match self.try_write(bytes) {
+            Ok(0) if !bytes.is_empty() => {}
+            result => return Poll::Ready(result),
+        }
+        self.buffer.writer_waker.register(cx.waker());
+        match self.try_write(bytes) {
+            Ok(0) if !bytes.is_empty() => Poll::Pending,
+            result => Poll::Ready(result),
+        }
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        self.buffer.writer_closed.store(true, Ordering::SeqCst);
+        self.buffer.reader_waker.wake();
+    }
+}
+
+/// Reading end of a pipe.
+pub struct PipeReader {
+    buffer: Arc<Buffer>,
+}
+
+impl PipeReader {
+    /// Reads up to `buffer.len()` bytes, waiting until some are available.
+    /// Returns 0 once the writer is dropped and all bytes were read.
+    pub async fn read(&mut self, buffer: &mut [u8]) -> usize {
+        poll_fn(|cx| self.poll_read(cx, buffer)).await
+    }
+
+    /// Reads everything until the writer is dropped.
+    pub async fn read_to_end(&mut self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut chunk = [0; 256];
+        loop {
+            match self.read(&mut chunk).await {
+                0 => return bytes,
+                read => bytes.extend_from_slice(&chunk[..read]),
+            }
+        }
+    }
+
+    /// Reads the bytes that are available without waiting, `None` means the
+    /// pipe is empty but still open.
+    pub fn try_read(&mut self, buffer: &mut [u8]) -> Option<usize> {
+        // check before taking the bytes, the writer may close in between
+        let writer_closed = self.buffer.writer_closed.load(Ordering::SeqCst);
+        let read = without_interrupts(|| {
+            let mut bytes = self.buffer.bytes.lock();
+            let read = bytes.len().min(buffer.len());
+            for (slot, byte) in buffer.iter_mut().zip(bytes.drain(..read)) {
+                *slot = byte;
+            }
+            read
+        });
+        if read > 0 {
+            self.buffer.writer_waker.wake();
+        }
+        (read > 0 || writer_closed || buffer.is_empty()).then_some(read)
+    }
+
+    pub fn poll_read(&mut self, cx: &mut Context, buffer: &mut [u8]) -> Poll<usize> {
+        if let Some(read) = self.try_read(buffer) {
+            return Poll::Ready(read);
+        }
+        self.buffer.reader_waker.register(cx.waker());
+        match self.try_read(buffer) {
+            Some(read) => Poll::Ready(read),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        self.buffer.reader_closed.store(true, Ordering::SeqCst);
+        self.buffer.writer_waker.wake();
+    }
+}
@@ -0,0 +1,267 @@
+use alloc::{collections::VecDeque, sync::Arc};
+use core::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicU8, Ordering},
+    task::{Context, Poll},
+};
+
+use futures_util::task::AtomicWaker;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+/// Error returned by [`Semaphore::acquire`] if the semaphore was closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AcquireError;
+
+impl fmt::Display for AcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("semaphore closed")
+    }
+}
+
+/// Error returned by [`Semaphore::try_acquire`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryAcquireError {
+    Closed,
+    NoPermits,
+}
+
+impl fmt::Display for TryAcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryAcquireError::Closed => f.write_str("semaphore closed"),
+            TryAcquireError::NoPermits => f.write_str("no permits available"),
+        }
+    }
+}
+
+/// Async counting semaphore.
+///
+/// Waiters are served in FIFO order: once a task waits for permits, later
+/// acquisitions queue up behind it even if enough permits are available for
+/// them. This keeps large requests (e.g. writers of a `RwLock`) from starving.
+pub struct Semaphore {
+    state: Mutex<State>,
+}
+
+struct State {
+    permits: usize,
+    closed: bool,
+    waiters: VecDeque<Arc<Waiter>>,
+}
+
+const WAITING: u8 = 0;
+const GRANTED: u8 = 1;
+const CLOSED: u8 = 2;
+
+struct Waiter {
+    permits: usize,
+    status: AtomicU8,
+    waker: AtomicWaker,
+}
+
+impl State {
+    /// Hands out permits to waiters at the front of the queue.
+    fn assign_permits(&mut self) {
+        while let Some(waiter) = self.waiters.front() {
+            if self.permits < waiter.permits {
+                break;
+            }
+            self.permits -= waiter.permits;
+            waiter.status.store(GRANTED, Ordering::SeqCst);
+            waiter.waker.wake();
+            self.waiters.pop_front();
+        }
+    }
+}
+
+impl Semaphore {
+    /// Largest number of permits a semaphore can hold.
+    pub const MAX_PERMITS: usize = usize::MAX >> 3;
+
+    pub const fn new(permits: usize) -> Self {
+        assert!(permits <= Self::MAX_PERMITS, "too many permits");
+        Semaphore {
+            state: Mutex::new(State {
+                permits,
+                closed: false,
+                waiters: VecDeque::new(),
+            }),
+        }
+    }
+
+    fn with_state<R>(&self, f: impl FnOnce(&mut State) -> R) -> R {
+        without_interrupts(|| f(&mut self.state.lock()))
+    }
+
+    pub fn available_permits(&self) -> usize {
+        self.with_state(|state| state.permits)
+    }
+
+    /// Adds `permits` new permits, waking waiters that can now proceed.
+    ///
+    /// Does not allocate, so it can be used from interrupt handlers.
+    pub fn add_permits(&self, permits: usize) {
+        self.with_state(|state| {
+            state.permits += permits;
+            assert!(state.permits <= Self::MAX_PERMITS, "too many permits");
+            state.assign_permits();
+        });
+    }
+
+    pub fn acquire(&self) -> Acquire<'_> {
+        self.acquire_many(1)
+    }
+
+    /// Waits until `permits` permits are available and takes them.
+    ///
+    /// Waits forever if more permits are requested than the semaphore will
+    /// ever hold.
+    pub fn acquire_many(&self, permits: usize) -> Acquire<'_> {
+        Acquire {
+            semaphore: self,
+            permits,
+            waiter: None,
+        }
+    }
+
+    pub fn try_acquire(&self) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        self.try_acquire_many(1)
+    }
+
+    pub fn try_acquire_many(&self, permits: usize) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        self.with_state(|state| {
+            if state.closed {
+                Err(TryAcquireError::Closed)
+            } else if state.waiters.is_empty() && state.permits >= permits {
+                state.permits -= permits;
+                Ok(SemaphorePermit {
+                    semaphore: self,
+                    permits,
+                })
+            } else {
+                Err(TryAcquireError::NoPermits)
+            }
+        })
+    }
+
+    /// Closes the semaphore. All pending and future acquisitions fail, permits
+    /// that are already held stay valid.
+    pub fn close(&self) {
+        self.with_state(|state| {
+            state.closed = true;
+            for waiter in state.waiters.drain(..) {
+                waiter.status.store(CLOSED, Ordering::SeqCst);
+                waiter.waker.wake();
+            }
+        });
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.with_state(|state| state.closed)
+    }
+}
+
+/// Permits taken from a [`Semaphore`], returned to it when dropped.
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+}
+
+impl SemaphorePermit<'_> {
+    /// Consumes the permits without returning them to the semaphore.
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        if self.permits > 0 {
+            self.semaphore.add_permits(self.permits);
+        }
+    }
+}
+
+/// Future returned by [`Semaphore::acquire`] and [`Semaphore::acquire_many`].
+pub struct Acquire<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+    waiter: Option<Arc<Waiter>>,
+}
+
+impl<'a> Acquire<'a> {
+    fn permit(&self) -> SemaphorePermit<'a> {
+        SemaphorePermit {
+            semaphore: self.semaphore,
+            permits: self.permits,
+        }
+    }
+}
+
+impl<'a> Future for Acquire<'a> {
+    type Output = Result<SemaphorePermit<'a>, AcquireError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        if let Some(waiter) = &self.waiter {
+            waiter.waker.register(cx.waker());
+            let status = waiter.status.load(Ordering::SeqCst);
+            if status == WAITING {
+                return Poll::Pending;
+            }
+            self.waiter = None;
+            return match status {
+                GRANTED => Poll::Ready(Ok(self.permit())),
+                _ => Poll::Ready(Err(AcquireError)),
+            };
+        }
+
+        let permits = self.permits;
+        let waiter = self.semaphore.with_state(|state| {
+            if state.closed {
+                return Err(AcquireError);
+            }
+            if state.waiters.is_empty() && state.permits >= permits {
+                state.permits -= permits;
+                return Ok(None);
+            }
+            let waiter = Arc::new(Waiter {
+                permits,
+                status: AtomicU8::new(WAITING),
+                waker: AtomicWaker::new(),
+            });
+            waiter.waker.register(cx.waker());
+            state.waiters.push_back(waiter.clone());
+            Ok(Some(waiter))
+        });
+
+        match waiter {
+            Ok(None) => Poll::Ready(Ok(self.permit())),
+            Ok(Some(waiter)) => {
+                self.waiter = Some(waiter);
+                Poll::Pending
+            }
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        let Some(waiter) = self.waiter.take() else {
+            return;
+        };
+        self.semaphore.with_state(|state| {
+            match waiter.status.load(Ordering::SeqCst) {
+                // permits were handed to us but never picked up
+                GRANTED => state.permits += waiter.permits,
+                WAITING => state.waiters.retain(|w| !Arc::ptr_eq(w, &waiter)),
+                _ => return,
+            }
+            // waiters behind us may be able to proceed now
+            state.assign_permits();
+        });
+    }
+}
@@ -0,0 +1,111 @@
+use core::{
+    arch::x86_64::_rdtsc,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use x86_64::instructions::port::Port;
+
+use crate::task::sync::Notify;
+
+/// Input clock of the programmable interval timer.
+const PIT_FREQUENCY_HZ: u64 = 1_193_182;
+/// The PIT is left at its power-on divisor, which gives ~18.2 timer interrupts per second.
+const PIT_DIVISOR: u64 = 65536;
+/// Length of the busy wait used to measure the TSC frequency.
+const CALIBRATION_MS: u64 = 10;
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+static TSC_HZ: AtomicU64 = AtomicU64::new(0);
+static BOOT_TSC: AtomicU64 = AtomicU64::new(0);
+static TICK: Notify = Notify::new();
+
+/// Measures the frequency of the time stamp counter against the PIT.
+///
+/// Has to run before interrupts are enabled, it busy waits for ~10 ms.
+pub fn init() {
+    BOOT_TSC.store(cycles(), Ordering::SeqCst);
+    TSC_HZ.store(measure_tsc_frequency(), Ordering::SeqCst);
+}
+
+/// Called by the timer interrupt handler.
+pub(crate) fn tick() {
+    TICKS.fetch_add(1, Ordering::SeqCst);
+    TICK.notify_waiters();
+}
+
+/// Number of timer interrupts since boot.
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::SeqCst)
+}
+
+/// Raw value of the time stamp counter.
+pub fn cycles() -> u64 {
+    unsafe { _rdtsc() }
+}
+
+/// Converts a number of TSC cycles into a duration.
+pub fn cycles_to_duration(cycles: u64) -> Duration {
+    match TSC_HZ.load(Ordering::SeqCst) {
+        0 => Duration::ZERO,
+        hz => Duration::from_nanos((cycles as u128 * 1_000_000_000 / hz as u128) as u64),
+    }
+}
+
+/// Time since [`init`] was called.
+pub fn uptime() -> Duration {
+    cycles_to_duration(cycles().saturating_sub(BOOT_TSC.load(Ordering::SeqCst)))
+}
+
+/// Spins for `duration` without relying on interrupts.
+pub fn busy_wait(duration: Duration) {
+    let deadline = uptime() + duration;
+    while uptime() < deadline {
+        core::hint::spin_loop();
+    }
+}
+
+/// Duration between two timer interrupts.
+pub fn tick_duration() -> Duration {
+    Duration::from_nanos(PIT_DIVISOR * 1_000_000_000 / PIT_FREQUENCY_HZ)
+}
+
+/// Waits for at least `duration`, with the resolution of a timer tick.
+pub async fn sleep(duration: Duration) {
+    let tick_ns = tick_duration().as_nanos();
+    let length = duration.as_nanos().div_ceil(tick_ns) as u64;
+    let deadline = ticks() + length.max(1);
+    while ticks() < deadline {
+        TICK.notified().await;
+    }
+}
+
+fn measure_tsc_frequency() -> u64 {
+    let mut gate: Port<u8> = Port::new(0x61);
+    let mut command: Port<u8> = Port::new(0x43);
+    let mut channel_2: Port<u8> = Port::new(0x42);
+
+    let count = PIT_FREQUENCY_HZ * CALIBRATION_MS / 1000;
+    unsafe {
+        // enable the gate of channel 2 but keep the speaker off
+        let value = gate.read();
+        gate.write((value & !0x02) | 0x01);
+
+        // channel 2, lobyte/hibyte access, mode 0 (interrupt on terminal count)
+        command.write(0b1011_0000);
+        channel_2.write(count as u8);
+        channel_2.write((count >> 8) as u8);
+
+        // restart the countdown by toggling the gate
+        let value = gate.read() & !0x01;
+        gate.write(value);
+        gate.write(value | 0x01);
+
+        let start = cycles();
+        // bit 5 reports the output of channel 2, which goes high once the count hits zero
+        while gate.read() & 0x20 == 0 {}
+        let end = cycles();
+
+        (end - start) * 1000 / CALIBRATION_MS
+    }
+}
@@ -1,21 +1,26 @@
-use crate::framebuffer::clear_color;
-use crate::framebuffer::{self, Rgb};
-use crate::print;
-use crate::serial_println;
-use conquer_once::spin::OnceCell;
-use core::sync::atomic::{AtomicBool, Ordering};
-use pc_keyboard::{DecodedKey, HandleControl, Keyboard, ScancodeSet1, layouts};
+use crate::console;
+use crate::framebuffer::{self, Rgb, with_framebuffer_writer};
+use crate::input::keyboard::KeyEvents;
+use crate::input::mouse;
+use alloc::{string::String, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use futures_util::StreamExt;
+use pc_keyboard::DecodedKey;
 use spin::Mutex;
 use wasmi::{Caller, Engine, Func, Linker, Module, Store};
+use x86_64::instructions::interrupts::without_interrupts;
 
 static WASM_GAME: Mutex<Option<WasmGame>> = Mutex::new(None);
 static GAME_RUNNING: AtomicBool = AtomicBool::new(false);
-static GAME_KEYBOARD: OnceCell<Mutex<Keyboard<layouts::Us104Key, ScancodeSet1>>> =
-    OnceCell::uninit();
+/// The virtual console the game was started on.
+static GAME_CONSOLE: AtomicUsize = AtomicUsize::new(console::LOG_CONSOLE);
 static PENDING_KEY: Mutex<Option<u8>> = Mutex::new(None);
 
+/// Environment variables of a game as `(name, value)` pairs.
+pub type Environment = Vec<(String, String)>;
+
 pub struct WasmGame {
-    store: Store<()>,
+    store: Store<Environment>,
     game_update: Func,
     game_render: Func,
     set_direction: Func,
@@ -26,85 +31,76 @@ pub fn is_game_running() -> bool {
     GAME_RUNNING.load(Ordering::Relaxed)
 }
 
-pub fn handle_scancode(scancode: u8) {
-    //serial_println!("Scancode received: {}", scancode);
-
-    let keyboard_mutex = GAME_KEYBOARD.get_or_init(|| {
-        Mutex::new(Keyboard::new(
-            ScancodeSet1::new(),
-            layouts::Us104Key,
-            HandleControl::Ignore,
-        ))
-    });
-
-    let mut keyboard = keyboard_mutex.lock();
-
-    match keyboard.add_byte(scancode) {
-        Ok(Some(key_event)) => {
-            //serial_println!("KeyEvent received: {:?}", key_event);
+/// The virtual console the running game draws on.
+pub fn game_console() -> usize {
+    GAME_CONSOLE.load(Ordering::Relaxed)
+}
 
-            if let Some(key) = keyboard.process_keyevent(key_event) {
-                //serial_println!("DecodedKey: {:?}", key);
+/// Whether a game is running on the console on the screen. The game is
+/// paused while another console is shown.
+pub fn is_game_shown() -> bool {
+    is_game_running() && console::active() == game_console()
+}
 
-                let is_escape = matches!(key, DecodedKey::RawKey(pc_keyboard::KeyCode::Escape))
-                    || matches!(key, DecodedKey::Unicode('\u{1b}'));
+/// Takes the keyboard focus and forwards key presses to the running game
+/// until Escape is pressed, which ends the game.
+pub async fn forward_keys() {
+    let mut events = KeyEvents::focus();
+    while let Some(event) = events.next().await {
+        let Some(key) = event.pressed() else {
+            continue;
+        };
+
+        let is_escape = matches!(key, DecodedKey::RawKey(pc_keyboard::KeyCode::Escape))
+            || matches!(key, DecodedKey::Unicode('\u{1b}'));
+
+        if is_escape {
+            GAME_RUNNING.store(false, Ordering::Relaxed);
+            log::debug!("escape pressed, stopping the game");
+            with_framebuffer_writer(|writer| writer.clear());
+            return;
+        }
 
-                if is_escape {
-                    GAME_RUNNING.store(false, Ordering::Relaxed);
-                    serial_println!("ESC pressed - setting GAME_RUNNING to false");
-                    clear_color(Rgb { r: 0, g: 0, b: 0 });
-                    print!("> ");
-                    return;
+        let key_code: u8 = match key {
+            DecodedKey::Unicode(c) => c as u8,
+            DecodedKey::RawKey(code) => {
+                use pc_keyboard::KeyCode;
+                match code {
+                    KeyCode::ArrowUp => 88,
+                    KeyCode::ArrowDown => 102,
+                    KeyCode::ArrowLeft => 101,
+                    KeyCode::ArrowRight => 103,
+                    KeyCode::Backspace => 8,
+                    KeyCode::Return => 10,
+                    _ => code as u8,
                 }
-
-                let key_code: u8 = match key {
-                    DecodedKey::Unicode(c) => c as u8,
-                    DecodedKey::RawKey(code) => {
-                        use pc_keyboard::KeyCode;
-                        match code {
-                            KeyCode::ArrowUp => 88,
-                            KeyCode::ArrowDown => 102,
-                            KeyCode::ArrowLeft => 101,
-                            KeyCode::ArrowRight => 103,
-                            KeyCode::Backspace => 8,
-                            KeyCode::Return => 10,
-                            _ => code as u8,
-                        }
-                    }
-                };
-
-                //serial_println!("Sending key_code to WASM: {}", key_code);
-                *PENDING_KEY.lock() = Some(key_code);
-                //handle_key(key_code);
-                //render_game();
             }
-        }
-        Ok(None) => {
-            //serial_println!("add_byte returned None (partial sequence)");
-        }
-        Err(e) => {
-            serial_println!("add_byte error: {:?}", e);
-        }
+        };
+
+        // the key is handed to the game by the timer interrupt
+        without_interrupts(|| *PENDING_KEY.lock() = Some(key_code));
     }
 }
 
 pub fn process_pending_keys() {
     if let Some(key_code) = PENDING_KEY.lock().take() {
-        //serial_println!("Processing queued key_code: {}", key_code);
+        log::trace!("processing queued key code {}", key_code);
         handle_key(key_code);
     }
 }
 
 /// Initialize and start the WASM Snake game
-pub fn init_wasm_game(wasm_bytes: &[u8]) {
+pub fn init_wasm_game(wasm_bytes: &[u8], env: Environment) {
     let engine = Engine::default();
     let module = Module::new(&engine, wasm_bytes).expect("Failed to parse WASM module");
 
-    let mut store = Store::new(&engine, ());
+    let mut store = Store::new(&engine, env);
     let mut linker = Linker::new(&engine);
 
-    // Register framebuffer host functions
+    // Register framebuffer, pointer and environment host functions
     register_framebuffer_functions(&mut linker);
+    register_pointer_functions(&mut linker);
+    register_env_functions(&mut linker);
 
     // Instantiate the module
     let instance = linker
@@ -147,6 +143,7 @@ pub fn init_wasm_game(wasm_bytes: &[u8]) {
     };
 
     *WASM_GAME.lock() = Some(game);
+    GAME_CONSOLE.store(console::current(), Ordering::Relaxed);
     GAME_RUNNING.store(true, Ordering::Relaxed);
 }
 
@@ -195,6 +192,76 @@ pub fn handle_direction(direction: i32) {
     }
 }
 
+/// Register the mouse pointer functions as WASM host functions
+fn register_pointer_functions<T>(linker: &mut Linker<T>) {
+    // get_pointer_x() -> i32
+    linker
+        .func_wrap("env", "get_pointer_x", |_caller: Caller<T>| -> i32 {
+            mouse::position().0 as i32
+        })
+        .unwrap();
+
+    // get_pointer_y() -> i32
+    linker
+        .func_wrap("env", "get_pointer_y", |_caller: Caller<T>| -> i32 {
+            mouse::position().1 as i32
+        })
+        .unwrap();
+
+    // get_pointer_buttons() -> i32, bit 0 = left, bit 1 = right, bit 2 = middle
+    linker
+        .func_wrap("env", "get_pointer_buttons", |_caller: Caller<T>| -> i32 {
+            mouse::buttons().bits() as i32
+        })
+        .unwrap();
+}
+
+/// Register the environment variable functions as WASM host functions
+fn register_env_functions(linker: &mut Linker<Environment>) {
+    // get_env(name_ptr: i32, name_len: i32, value_ptr: i32, value_capacity: i32) -> i32
+    // Copies at most value_capacity bytes of the value and returns its full
+    // length, or -1 if the variable is not set.
+    linker
+        .func_wrap(
+            "env",
+            "get_env",
+            |mut caller: Caller<Environment>,
+             name_ptr: i32,
+             name_len: i32,
+             value_ptr: i32,
+             value_capacity: i32|
+             -> i32 {
+                let Some(memory) = caller.get_export("memory").and_then(|e| e.into_memory()) else {
+                    return -1;
+                };
+                // Compare against the name in linear memory directly so a bogus
+                // name_len can't make the host allocate.
+                let start = name_ptr as u32 as usize;
+                let end = start.saturating_add(name_len.max(0) as usize);
+                let Some(name) = memory.data(&caller).get(start..end) else {
+                    return -1;
+                };
+                let Some(value) = caller
+                    .data()
+                    .iter()
+                    .find(|(key, _)| key.as_bytes() == name)
+                    .map(|(_, value)| value.clone())
+                else {
+                    return -1;
+                };
+                let copied = value.len().min(value_capacity.max(0) as usize);
+                if memory
+                    .write(&mut caller, value_ptr as usize, &value.as_bytes()[..copied])
+                    .is_err()
+                {
+                    return -1;
+                }
+                value.len() as i32
+            },
+        )
+        .unwrap();
+}
+
 /// Register all framebuffer functions as WASM host functions
 fn register_framebuffer_functions<T>(linker: &mut Linker<T>) {
     // put_pixel(x: i32, y: i32, r: i32, g: i32, b: i32)
@@ -317,7 +384,7 @@ fn register_framebuffer_functions<T>(linker: &mut Linker<T>) {
 
             // Convert to string and print
             if let Ok(s) = core::str::from_utf8(&buffer[..len]) {
-                //crate::serial_println!("WASM println: ptr={}, len={}, str='{}'", ptr, len, s);
+                log::trace!("println from WASM: ptr={}, len={}", ptr, len);
                 crate::println!("{}", s);
             }
         })
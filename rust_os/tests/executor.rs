@@ -0,0 +1,186 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(rust_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate alloc;
+
+use alloc::{rc::Rc, sync::Arc, vec::Vec};
+use bootloader_api::BootInfo;
+use core::{
+    cell::RefCell,
+    future::pending,
+    panic::PanicInfo,
+    pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll},
+};
+use rust_os::{
+    default_entry_point, hlt_loop, init_kernel,
+    task::{self, JoinError, executor::Executor},
+};
+
+default_entry_point!(main);
+
+fn main(boot_info: &'static mut BootInfo) -> ! {
+    init_kernel(boot_info);
+    test_main();
+    hlt_loop()
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    rust_os::test_panic_handler(info)
+}
+
+#[test_case]
+fn join_handle_returns_output() {
+    let mut executor = Executor::new();
+    let result = Rc::new(RefCell::new(None));
+
+    let handle = executor.spawn(async { 41 + 1 });
+    let task_result = result.clone();
+    executor.spawn(async move {
+        *task_result.borrow_mut() = Some(handle.await);
+    });
+    executor.run_until_idle();
+
+    assert_eq!(*result.borrow(), Some(Ok(42)));
+}
+
+#[test_case]
+fn join_handle_stays_finished_after_resolving() {
+    let mut executor = Executor::new();
+    let finished = Rc::new(RefCell::new(None));
+
+    let mut handle = executor.spawn(async { 42 });
+    let task_finished = finished.clone();
+    executor.spawn(async move {
+        assert_eq!((&mut handle).await, Ok(42));
+        *task_finished.borrow_mut() = Some(handle.is_finished());
+    });
+    executor.run_until_idle();
+
+    assert_eq!(*finished.borrow(), Some(true));
+}
+
+#[test_case]
+fn aborted_task_is_cancelled() {
+    let mut executor = Executor::new();
+    let result = Rc::new(RefCell::new(None));
+
+    let handle = executor.spawn(pending::<()>());
+    let task_result = result.clone();
+    executor.spawn(async move {
+        handle.abort();
+        *task_result.borrow_mut() = Some(handle.await);
+    });
+    executor.run_until_idle();
+
+    assert_eq!(*result.borrow(), Some(Err(JoinError::Cancelled)));
+}
+
+#[test_case]
+fn join_handles_can_be_awaited_in_order() {
+    let mut executor = Executor::new();
+    let results = Rc::new(RefCell::new(Vec::new()));
+
+    let handles: Vec<_> = (0..5)
+        .map(|i| executor.spawn(async move { i * i }))
+        .collect();
+    let task_results = results.clone();
+    executor.spawn(async move {
+        for handle in handles {
+            let output = handle.await.unwrap();
+            task_results.borrow_mut().push(output);
+        }
+    });
+    executor.run_until_idle();
+
+    assert_eq!(*results.borrow(), [0, 1, 4, 9, 16]);
+}
+
+#[test_case]
+fn tasks_can_spawn_tasks() {
+    let mut executor = Executor::new();
+    let counter = Arc::new(AtomicUsize::new(0));
+
+    let task_counter = counter.clone();
+    executor.spawn(async move {
+        let handles: Vec<_> = (0..3)
+            .map(|_| {
+                let counter = task_counter.clone();
+                task::spawn(async move { counter.fetch_add(1, Ordering::SeqCst) })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        task_counter.fetch_add(10, Ordering::SeqCst);
+    });
+    executor.run_until_idle();
+
+    assert_eq!(counter.load(Ordering::SeqCst), 13);
+    assert!(task::tasks().is_empty());
+}
+
+#[test_case]
+fn full_run_queue_does_not_lose_wakeups() {
+    let mut executor = Executor::with_capacity(1);
+    let counter = Rc::new(RefCell::new(0));
+
+    for _ in 0..10 {
+        let counter = counter.clone();
+        executor.spawn(async move {
+            for _ in 0..3 {
+                YieldNow(false).await;
+                *counter.borrow_mut() += 1;
+            }
+        });
+    }
+    executor.run_until_idle();
+
+    assert_eq!(*counter.borrow(), 30);
+}
+
+#[test_case]
+fn task_statistics_count_polls_and_wakes() {
+    let mut executor = Executor::new();
+    let stats = Rc::new(RefCell::new(None));
+
+    executor.spawner().spawn_named("counted", async {
+        YieldNow(false).await;
+        YieldNow(false).await;
+    });
+    let task_stats = stats.clone();
+    executor.spawn(async move {
+        // runs after the first poll of the counted task
+        YieldNow(false).await;
+        *task_stats.borrow_mut() = task::tasks()
+            .into_iter()
+            .find(|task| task.name == "counted")
+            .map(|task| (task.polls, task.wakes));
+    });
+    executor.run_until_idle();
+
+    // the first `YieldNow` woke the task twice, but it was only polled once so far
+    assert_eq!(*stats.borrow(), Some((1, 2)));
+}
+
+/// Wakes itself multiple times and completes on the second poll.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
@@ -123,10 +123,25 @@ fn test_read_into_with_offset() {
 }
 
 #[test_case]
-fn test_invalid_path_traversal() {
+fn test_read_into_stops_at_end_of_file() {
     let mut fs = create_fs();
-    let err = fs.read("./dir/../dir/file1.txt").unwrap_err();
-    assert_eq!(err, Error::InvalidPathTraversal);
+    let mut buffer = [0u8; 32];
+    let bytes_read = fs.read_into("test.txt", 12, &mut buffer).unwrap();
+    assert_eq!(&buffer[..bytes_read], b"World!\n");
+    assert_eq!(fs.read_into("test.txt", 19, &mut buffer).unwrap(), 0);
+    assert_eq!(fs.read_into("test.txt", 100, &mut buffer).unwrap(), 0);
+}
+
+#[test_case]
+fn test_parent_dir_path() {
+    let mut fs = create_fs();
+    let content = fs.read_to_string("./dir/../dir/file1.txt").unwrap();
+    assert_eq!(content, "File 1 content\n");
+
+    // `..` at the root stays at the root
+    let content = fs.read_to_string("/../../test.txt").unwrap();
+    assert_eq!(content, "Test: Hello World!\n");
+    assert_eq!(fs.read_dir("dir/..").unwrap().len(), 2);
 
     let content = fs.read_to_string("./dir/file1.txt").unwrap();
     assert_eq!(content, "File 1 content\n");
@@ -174,6 +189,32 @@ fn test_file_not_found() {
     assert_eq!(err, Error::NotFound);
 }
 
+#[test_case]
+fn test_tar_filesystem_is_read_only() {
+    let mut fs = create_fs();
+    assert!(!fs.is_writable());
+    assert_eq!(fs.write("new.txt", b"new"), Err(Error::PermissionDenied));
+}
+
+#[test_case]
+fn test_memory_overlay_write() {
+    let mut fs = create_fs().with_memory_overlay();
+    fs.write("dir/new.txt", b"new\n").unwrap();
+    fs.append("/dir/new.txt", b"more\n").unwrap();
+    assert_eq!(fs.read_to_string("dir/new.txt").unwrap(), "new\nmore\n");
+
+    // files in the overlay hide those of the ramdisk
+    fs.write("test.txt", b"changed").unwrap();
+    assert_eq!(fs.read("test.txt").unwrap(), b"changed");
+    let entries = fs.read_dir("/").unwrap();
+    assert_eq!(entries.iter().filter(|e| e.name() == "test.txt").count(), 1);
+    let entries = fs.read_dir("dir").unwrap();
+    assert!(entries.iter().any(|e| e.name() == "new.txt" && e.size == 9));
+
+    assert_eq!(fs.write("missing/new.txt", b""), Err(Error::NotFound));
+    assert_eq!(fs.write("dir", b""), Err(Error::UnexpectedFileType));
+}
+
 fn create_fs() -> FileSystem {
     let ramdisk = *RAMDISK.get().unwrap();
     FileSystem::from_tar(ramdisk.into())
@@ -0,0 +1,137 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(rust_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader_api::BootInfo;
+use core::panic::PanicInfo;
+use pc_keyboard::{DecodedKey, KeyCode, KeyState};
+use rust_os::{
+    console, default_entry_point, hlt_loop, init_kernel,
+    input::{
+        keyboard::{self, KeyEvents},
+        layout::{Layout, ScancodeSetKind},
+    },
+};
+
+default_entry_point!(main);
+
+fn main(boot_info: &'static mut BootInfo) -> ! {
+    init_kernel(boot_info);
+    test_main();
+    hlt_loop()
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    rust_os::test_panic_handler(info)
+}
+
+/// Set 1 scancodes of pressing and releasing `A`.
+const PRESS_A: u8 = 0x1e;
+const RELEASE_A: u8 = 0x9e;
+/// Set 1 scancode of pressing the key labeled `Y` on a US keyboard.
+const PRESS_Y: u8 = 0x15;
+/// Set 1 scancodes of the left Alt key and F3.
+const PRESS_ALT: u8 = 0x38;
+const RELEASE_ALT: u8 = 0xb8;
+const PRESS_F3: u8 = 0x3d;
+const RELEASE_F3: u8 = 0xbd;
+
+#[test_case]
+fn key_events_are_decoded() {
+    let mut events = KeyEvents::focus();
+    keyboard::handle_scancode(PRESS_A);
+    keyboard::handle_scancode(RELEASE_A);
+
+    let press = events.try_next().unwrap();
+    assert_eq!(press.code, KeyCode::A);
+    assert_eq!(press.pressed(), Some(DecodedKey::Unicode('a')));
+    let release = events.try_next().unwrap();
+    assert_eq!(release.state, KeyState::Up);
+    assert_eq!(release.pressed(), None);
+    assert!(events.try_next().is_none());
+}
+
+#[test_case]
+fn only_the_top_of_the_focus_stack_receives_events() {
+    let mut shell = KeyEvents::focus();
+    let mut monitor = KeyEvents::monitor();
+    let mut game = KeyEvents::focus();
+    assert!(game.has_focus());
+    assert!(!shell.has_focus());
+
+    keyboard::handle_scancode(PRESS_A);
+    assert!(game.try_next().is_some());
+    assert!(shell.try_next().is_none());
+    assert!(monitor.try_next().is_some());
+
+    drop(game);
+    assert!(shell.has_focus());
+    keyboard::handle_scancode(RELEASE_A);
+    assert_eq!(shell.try_next().unwrap().state, KeyState::Up);
+    assert!(monitor.try_next().is_some());
+}
+
+#[test_case]
+fn layouts_can_be_switched() {
+    let mut events = KeyEvents::focus();
+    keyboard::set_layout(Layout::German);
+    keyboard::handle_scancode(PRESS_Y);
+    keyboard::set_layout(Layout::Us);
+    keyboard::handle_scancode(PRESS_Y);
+
+    assert_eq!(
+        events.try_next().unwrap().key,
+        Some(DecodedKey::Unicode('z'))
+    );
+    assert_eq!(
+        events.try_next().unwrap().key,
+        Some(DecodedKey::Unicode('y'))
+    );
+}
+
+#[test_case]
+fn scancode_set_2_is_decoded() {
+    let mut events = KeyEvents::focus();
+    keyboard::set_scancode_set(ScancodeSetKind::Set2);
+    // set 2 sends 0x1c for A going down and 0xf0 0x1c for it going up
+    for scancode in [0x1c, 0xf0, 0x1c] {
+        keyboard::handle_scancode(scancode);
+    }
+    keyboard::set_scancode_set(ScancodeSetKind::Set1);
+
+    assert_eq!(
+        events.try_next().unwrap().pressed(),
+        Some(DecodedKey::Unicode('a'))
+    );
+    assert_eq!(events.try_next().unwrap().state, KeyState::Up);
+}
+
+#[test_case]
+fn the_focus_belongs_to_a_console() {
+    let mut shell = KeyEvents::focus();
+    let mut other = console::with_console(console::CONSOLES - 1, KeyEvents::focus);
+    assert!(shell.has_focus());
+    assert!(other.has_focus());
+
+    keyboard::handle_scancode(PRESS_A);
+    keyboard::handle_scancode(RELEASE_A);
+    assert!(shell.try_next().is_some());
+    assert!(other.try_next().is_none());
+}
+
+#[test_case]
+fn alt_function_keys_are_not_passed_on() {
+    let mut events = KeyEvents::focus();
+    for scancode in [PRESS_ALT, PRESS_F3, RELEASE_F3, RELEASE_ALT] {
+        keyboard::handle_scancode(scancode);
+    }
+
+    assert_eq!(events.try_next().unwrap().code, KeyCode::LAlt);
+    assert_eq!(events.try_next().unwrap().code, KeyCode::LAlt);
+    assert!(events.try_next().is_none());
+    assert_eq!(console::take_request(), Some(2));
+    assert_eq!(console::take_request(), None);
+}
@@ -0,0 +1,74 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(rust_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader_api::BootInfo;
+use core::panic::PanicInfo;
+use rust_os::{
+    default_entry_point, hlt_loop, init_kernel,
+    input::mouse::{self, MouseEvents},
+};
+
+default_entry_point!(main);
+
+fn main(boot_info: &'static mut BootInfo) -> ! {
+    init_kernel(boot_info);
+    test_main();
+    hlt_loop()
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    rust_os::test_panic_handler(info)
+}
+
+/// Feeds a packet to the mouse driver, with a wheel byte if the mouse has one.
+fn send_packet(flags: u8, dx: u8, dy: u8, wheel: u8) {
+    for byte in [flags, dx, dy] {
+        mouse::handle_byte(byte);
+    }
+    if mouse::has_wheel() {
+        mouse::handle_byte(wheel);
+    }
+}
+
+#[test_case]
+fn packets_are_decoded() {
+    let mut events = MouseEvents::new();
+    // left button, moved 5 right and 3 up
+    send_packet(0b0000_1001, 5, 3, 0);
+    let event = events.try_next().unwrap();
+    assert_eq!((event.dx, event.dy), (5, -3));
+    assert!(event.buttons.left && !event.buttons.right);
+    assert_eq!(mouse::buttons().bits(), 1);
+
+    // both sign bits set, moved 2 left and 4 down
+    send_packet(0b0011_1000, 0xfe, 0xfc, 0);
+    let event = events.try_next().unwrap();
+    assert_eq!((event.dx, event.dy), (-2, 4));
+    assert_eq!(event.buttons.bits(), 0);
+    assert!(events.try_next().is_none());
+}
+
+#[test_case]
+fn bytes_out_of_sync_are_skipped() {
+    let mut events = MouseEvents::new();
+    // bit 3 is clear, so this cannot be the first byte of a packet
+    mouse::handle_byte(0x00);
+    send_packet(0b0000_1000, 1, 0, 0);
+    assert_eq!(events.try_next().unwrap().dx, 1);
+}
+
+#[test_case]
+fn pointer_stays_on_the_screen() {
+    let mut events = MouseEvents::new();
+    // moved as far left and up as a packet allows
+    for _ in 0..10 {
+        send_packet(0b0001_1000, 0x80, 0x7f, 0);
+    }
+    assert_eq!(mouse::position(), (0, 0));
+    let event = events.try_next().unwrap();
+    assert_eq!((event.x, event.y), (0, 0));
+}
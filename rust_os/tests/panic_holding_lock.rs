@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+use bootloader_api::BootInfo;
+use core::panic::PanicInfo;
+use rust_os::{
+    default_entry_point,
+    framebuffer::with_framebuffer_writer,
+    hlt_loop, init_kernel,
+    qemu::{QemuExitCode, exit_qemu},
+    serial_print, serial_println,
+};
+
+default_entry_point!(main);
+
+fn main(boot_info: &'static mut BootInfo) -> ! {
+    init_kernel(boot_info);
+    serial_print!("panic_holding_lock::panic_holding_lock...\t");
+    // the writer stays locked, the panic must not need it
+    with_framebuffer_writer(|_| panic!("panic while the framebuffer is locked"));
+    serial_println!("[test did not panic]");
+    exit_qemu(QemuExitCode::Failed);
+    hlt_loop()
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    serial_println!("[ok]");
+    exit_qemu(QemuExitCode::Success);
+    hlt_loop()
+}
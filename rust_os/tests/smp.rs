@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(rust_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader_api::BootInfo;
+use core::panic::PanicInfo;
+use rust_os::{default_entry_point, hlt_loop, init_kernel, smp};
+
+default_entry_point!(main);
+
+fn main(boot_info: &'static mut BootInfo) -> ! {
+    init_kernel(boot_info);
+    test_main();
+    hlt_loop()
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    rust_os::test_panic_handler(info)
+}
+
+#[test_case]
+fn bootstrap_processor_runs_first() {
+    let cpus = smp::cpus();
+    assert!(!cpus.is_empty());
+    assert_eq!(cpus[0].index(), 0);
+    assert!(cpus.iter().all(|cpu| cpu.is_online()));
+}
+
+#[test_case]
+fn per_cpu_data_belongs_to_the_calling_cpu() {
+    let cpu = smp::percpu::current();
+    assert_eq!(cpu.index(), 0);
+    if let Some(apic_id) = smp::apic::id() {
+        assert_eq!(cpu.apic_id(), apic_id);
+    }
+}
@@ -0,0 +1,250 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(rust_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate alloc;
+
+use alloc::{rc::Rc, vec, vec::Vec};
+use bootloader_api::BootInfo;
+use core::{cell::RefCell, panic::PanicInfo};
+use rust_os::{
+    default_entry_point, hlt_loop, init_kernel,
+    task::{
+        executor::Executor,
+        sync::{Mutex, Notify, RwLock, Semaphore, mpsc, oneshot, pipe},
+    },
+};
+
+default_entry_point!(main);
+
+fn main(boot_info: &'static mut BootInfo) -> ! {
+    init_kernel(boot_info);
+    test_main();
+    hlt_loop()
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    rust_os::test_panic_handler(info)
+}
+
+#[test_case]
+fn mutex_is_held_across_await_points() {
+    let mut executor = Executor::new();
+    let mutex = Rc::new(Mutex::new(Vec::new()));
+    let notify = Rc::new(Notify::new());
+
+    let (first_mutex, first_notify) = (mutex.clone(), notify.clone());
+    executor.spawn(async move {
+        let mut guard = first_mutex.lock().await;
+        guard.push(1);
+        first_notify.notified().await;
+        guard.push(2);
+    });
+    let second_mutex = mutex.clone();
+    executor.spawn(async move {
+        second_mutex.lock().await.push(3);
+    });
+    executor.spawn(async move {
+        notify.notify_one();
+    });
+    executor.run_until_idle();
+
+    assert_eq!(*mutex.try_lock().unwrap(), [1, 2, 3]);
+}
+
+#[test_case]
+fn rwlock_allows_readers_or_one_writer() {
+    let lock = RwLock::new(5);
+    {
+        let first = lock.try_read().unwrap();
+        let second = lock.try_read().unwrap();
+        assert_eq!(*first + *second, 10);
+        assert!(lock.try_write().is_none());
+    }
+    {
+        let mut writer = lock.try_write().unwrap();
+        *writer += 1;
+        assert!(lock.try_read().is_none());
+    }
+    assert_eq!(*lock.try_read().unwrap(), 6);
+}
+
+#[test_case]
+fn semaphore_limits_concurrency() {
+    let mut executor = Executor::new();
+    let semaphore = Rc::new(Semaphore::new(2));
+    let active = Rc::new(RefCell::new((0, 0)));
+    let release = Rc::new(Notify::new());
+
+    for _ in 0..5 {
+        let (semaphore, active, release) = (semaphore.clone(), active.clone(), release.clone());
+        executor.spawn(async move {
+            let _permit = semaphore.acquire().await.unwrap();
+            {
+                let mut active = active.borrow_mut();
+                active.0 += 1;
+                active.1 = active.1.max(active.0);
+            }
+            release.notified().await;
+            active.borrow_mut().0 -= 1;
+            // let the next task holding a permit finish
+            release.notify_one();
+        });
+    }
+    let task_release = release.clone();
+    executor.spawn(async move {
+        task_release.notify_one();
+    });
+    executor.run_until_idle();
+
+    assert_eq!(*active.borrow(), (0, 2));
+    assert_eq!(semaphore.available_permits(), 2);
+}
+
+#[test_case]
+fn bounded_channel_delivers_in_order() {
+    let mut executor = Executor::new();
+    let (sender, mut receiver) = mpsc::channel(2);
+    let received = Rc::new(RefCell::new(Vec::new()));
+
+    for offset in [0, 10] {
+        let sender = sender.clone();
+        executor.spawn(async move {
+            for i in 0..5 {
+                sender.send(offset + i).await.unwrap();
+            }
+        });
+    }
+    drop(sender);
+
+    let task_received = received.clone();
+    executor.spawn(async move {
+        while let Some(value) = receiver.recv().await {
+            task_received.borrow_mut().push(value);
+        }
+    });
+    executor.run_until_idle();
+
+    let received = received.borrow();
+    let first: Vec<_> = received.iter().copied().filter(|v| *v < 10).collect();
+    let second: Vec<_> = received.iter().copied().filter(|v| *v >= 10).collect();
+    assert_eq!(first, [0, 1, 2, 3, 4]);
+    assert_eq!(second, [10, 11, 12, 13, 14]);
+}
+
+#[test_case]
+fn pipe_delivers_bytes_until_writer_is_dropped() {
+    let mut executor = Executor::new();
+    let (writer, mut reader) = pipe::pipe(16);
+    let received = Rc::new(RefCell::new(Vec::new()));
+
+    let task_received = received.clone();
+    executor.spawn(async move {
+        let bytes = reader.read_to_end().await;
+        task_received.borrow_mut().extend(bytes);
+    });
+    executor.run_until_idle();
+    assert!(received.borrow().is_empty());
+
+    assert_eq!(writer.try_write(b"hello "), Ok(6));
+    assert_eq!(writer.try_write(b"pipe"), Ok(4));
+    executor.run_until_idle();
+    assert!(received.borrow().is_empty());
+
+    drop(writer);
+    executor.run_until_idle();
+    assert_eq!(*received.borrow(), b"hello pipe");
+
+    let (writer, reader) = pipe::pipe(16);
+    drop(reader);
+    assert_eq!(writer.try_write(b"lost"), Err(pipe::BrokenPipe));
+}
+
+#[test_case]
+fn pipe_writer_waits_while_full() {
+    let mut executor = Executor::new();
+    let (writer, mut reader) = pipe::pipe(4);
+    let written = Rc::new(RefCell::new(false));
+
+    let task_written = written.clone();
+    executor.spawn(async move {
+        writer.write(b"too long").await.unwrap();
+        *task_written.borrow_mut() = true;
+    });
+    executor.run_until_idle();
+    assert!(!*written.borrow());
+
+    let mut buffer = [0; 8];
+    assert_eq!(reader.try_read(&mut buffer), Some(4));
+    assert_eq!(&buffer[..4], b"too ");
+    executor.run_until_idle();
+    assert!(*written.borrow());
+    assert_eq!(reader.try_read(&mut buffer), Some(4));
+    assert_eq!(&buffer[..4], b"long");
+}
+
+#[test_case]
+fn sending_to_dropped_receiver_fails() {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    sender.send(1).unwrap();
+    drop(receiver);
+    assert_eq!(sender.send(2).unwrap_err().0, 2);
+
+    let (sender, receiver) = mpsc::channel(1);
+    drop(receiver);
+    assert!(matches!(
+        sender.try_send(3),
+        Err(mpsc::TrySendError::Closed(3))
+    ));
+}
+
+#[test_case]
+fn oneshot_transfers_value() {
+    let mut executor = Executor::new();
+    let results = Rc::new(RefCell::new(vec![]));
+
+    let (sender, receiver) = oneshot::channel();
+    let (dropped_sender, dropped_receiver) = oneshot::channel::<u32>();
+    let task_results = results.clone();
+    executor.spawn(async move {
+        let value = receiver.await;
+        let dropped = dropped_receiver.await;
+        task_results.borrow_mut().push(value);
+        task_results.borrow_mut().push(dropped);
+    });
+    executor.spawn(async move {
+        sender.send(7).unwrap();
+        drop(dropped_sender);
+    });
+    executor.run_until_idle();
+
+    assert_eq!(*results.borrow(), [Ok(7), Err(oneshot::RecvError)]);
+}
+
+#[test_case]
+fn notify_stores_one_permit() {
+    let mut executor = Executor::new();
+    let notify = Rc::new(Notify::new());
+    let woken = Rc::new(RefCell::new(0));
+
+    notify.notify_one();
+    notify.notify_one();
+    for _ in 0..2 {
+        let (notify, woken) = (notify.clone(), woken.clone());
+        executor.spawn(async move {
+            notify.notified().await;
+            *woken.borrow_mut() += 1;
+        });
+    }
+    let waker_notify = notify.clone();
+    executor.spawn(async move {
+        // only one of the two waiters consumed the stored permit
+        waker_notify.notify_waiters();
+    });
+    executor.run_until_idle();
+
+    assert_eq!(*woken.borrow(), 2);
+}
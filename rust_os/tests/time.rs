@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(rust_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader_api::BootInfo;
+use core::{panic::PanicInfo, time::Duration};
+use rust_os::{default_entry_point, hlt_loop, init_kernel, task::executor::Executor, time};
+
+default_entry_point!(main);
+
+fn main(boot_info: &'static mut BootInfo) -> ! {
+    init_kernel(boot_info);
+    test_main();
+    hlt_loop()
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    rust_os::test_panic_handler(info)
+}
+
+#[test_case]
+fn uptime_is_monotonic() {
+    let first = time::uptime();
+    let second = time::uptime();
+    assert!(first > Duration::ZERO);
+    assert!(second >= first);
+}
+
+#[test_case]
+fn sleep_waits_for_timer_ticks() {
+    let mut executor = Executor::new();
+    let start = time::ticks();
+    executor.spawn(time::sleep(time::tick_duration() * 3));
+    executor.run_until_idle();
+    assert!(time::ticks() >= start + 3);
+}